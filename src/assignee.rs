@@ -0,0 +1,340 @@
+//! Assignee name harmonization: organization ids from the many spellings of a name.
+//!
+//! Names go through three steps:
+//! 1. `normalize_name`: case, accents and punctuation are folded and legal forms stripped, so
+//!    "INTERNATIONAL BUSINESS MACHINES CORP." and "International Business Machines
+//!    Corporation" are both "INTERNATIONAL BUSINESS MACHINES".
+//! 2. `Aliases`: a dictionary for names no normalization can match, e.g. "IBM".
+//! 3. `Harmonizer::organizations`: names within Jaro-Winkler `threshold` of each other are
+//!    clustered (single linkage, within blocks sharing their first three characters).
+//!
+//! An organization's id is a hash of its most frequent normalized name, so ids are the same
+//! from run to run and don't depend on input order, only on which name is the most frequent.
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use crate::data::PatentGrant;
+use crate::error::{Error, ReadTable};
+use crate::matching::{fold, jaro_winkler, stable_id, Clusters};
+
+/// legal forms, stripped from the end of names (and "KABUSHIKI KAISHA" from the start too);
+/// longest first
+const LEGAL_FORMS: &[&[&str]] = &[
+    &["KABUSHIKI", "KAISHA"],
+    &["L", "L", "C"],
+    &["S", "P", "A"],
+    &["A", "S"],
+    &["B", "V"],
+    &["N", "V"],
+    &["S", "A"],
+    &["A", "G"],
+    &["CORPORATION"],
+    &["CORP"],
+    &["INCORPORATED"],
+    &["INC"],
+    &["LIMITED"],
+    &["LTD"],
+    &["LLC"],
+    &["LLP"],
+    &["LP"],
+    &["PLC"],
+    &["COMPANY"],
+    &["CO"],
+    &["GMBH"],
+    &["MBH"],
+    &["AG"],
+    &["KG"],
+    &["KGAA"],
+    &["SA"],
+    &["SAS"],
+    &["SARL"],
+    &["SRL"],
+    &["SPA"],
+    &["BV"],
+    &["NV"],
+    &["AB"],
+    &["AS"],
+    &["ASA"],
+    &["OY"],
+    &["OYJ"],
+    &["KK"],
+    &["PTY"],
+    &["SE"],
+    // left over from "& Co."
+    &["AND"],
+];
+
+/// Upper case, accents folded, "&" as "AND", other punctuation dropped, and legal forms
+/// stripped. Initials run together ("I.B.M." is "IBM"). Letters and digits outside ascii are
+/// kept, so names in other scripts stay distinct. Never strips a name to nothing.
+pub fn normalize_name(raw: &str) -> String {
+    let mut cleaned = String::with_capacity(raw.len());
+    for c in raw.chars().map(fold) {
+        match c {
+            '&' | '+' => cleaned.push_str(" AND "),
+            // dropped without a break: "I.B.M.", "O'Reilly"
+            '.' | '\'' | '`' => (),
+            c if c.is_alphanumeric() => cleaned.extend(c.to_uppercase()),
+            _ => cleaned.push(' '),
+        }
+    }
+    if cleaned.trim().is_empty() {
+        return raw.trim().to_owned();
+    }
+
+    let mut tokens: Vec<&str> = cleaned.split_whitespace().collect();
+    if tokens.first() == Some(&"THE") && tokens.len() > 1 {
+        tokens.remove(0);
+    }
+    if tokens.len() > 2 && tokens[..2] == ["KABUSHIKI", "KAISHA"] {
+        tokens.drain(..2);
+    }
+    while let Some(form) = LEGAL_FORMS.iter().find(|form| form.len() < tokens.len() && tokens.ends_with(form)) {
+        tokens.truncate(tokens.len() - form.len());
+    }
+
+    tokens.join(" ")
+}
+
+#[derive(Deserialize)]
+struct AliasRecord {
+    alias: String,
+    name: String,
+}
+
+/// Names to treat as another name, e.g. "IBM" as "International Business Machines". Both
+/// sides are normalized, so one entry covers every spelling that normalizes the same.
+#[derive(Debug, Default, Clone)]
+pub struct Aliases {
+    names: HashMap<String, String>,
+}
+
+impl Aliases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// csv with a header row: alias,name
+    pub fn from_reader<R: Read>(r: R) -> Result<Self, Error> {
+        let mut aliases = Self::new();
+
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: AliasRecord = record.context(ReadTable)?;
+            aliases.insert(&record.alias, &record.name);
+        }
+
+        Ok(aliases)
+    }
+
+    pub fn insert(&mut self, alias: &str, name: &str) {
+        self.names.insert(normalize_name(alias), normalize_name(name));
+    }
+
+    /// the normalized name, after aliasing
+    pub fn resolve(&self, raw: &str) -> String {
+        let normalized = normalize_name(raw);
+        self.names.get(&normalized).cloned().unwrap_or(normalized)
+    }
+}
+
+/// Collects assignee names over the corpus, then clusters them into organizations
+pub struct Harmonizer {
+    aliases: Aliases,
+    threshold: f64,
+    names: BTreeMap<String, u64>,
+}
+
+impl Harmonizer {
+    /// `threshold` is the Jaro-Winkler similarity at which normalized names are clustered;
+    /// above 1 only exact matches after normalization and aliasing are.
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            aliases: Aliases::new(),
+            threshold,
+            names: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_aliases(mut self, aliases: Aliases) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// counts the organization names of the grant's assignees; individuals are skipped
+    pub fn add(&mut self, pg: &PatentGrant) {
+        for assignee in &pg.us_bibliographic_data_grant.assignees {
+            if let Some(name) = assignee.orgname.as_deref().or(assignee.addressbook.orgname.as_deref()) {
+                self.add_name(name);
+            }
+        }
+    }
+
+    pub fn add_name(&mut self, raw: &str) {
+        let raw = raw.trim();
+        if !raw.is_empty() {
+            *self.names.entry(raw.to_owned()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn organizations(&self) -> Organizations {
+        // distinct normalized names, with their raw names and total count
+        let mut normalized: BTreeMap<String, (Vec<&str>, u64)> = BTreeMap::new();
+        for (raw, count) in &self.names {
+            let entry = normalized.entry(self.aliases.resolve(raw)).or_insert_with(|| (Vec::new(), 0));
+            entry.0.push(raw);
+            entry.1 += count;
+        }
+        let keys: Vec<&String> = normalized.keys().collect();
+
+        let mut clusters = Clusters::new(keys.len());
+        let mut blocks: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, key) in keys.iter().enumerate() {
+            blocks.entry(key.chars().take(3).collect()).or_default().push(i);
+        }
+        for block in blocks.values() {
+            for (n, &i) in block.iter().enumerate() {
+                for &j in &block[n + 1..] {
+                    // short names are too easily similar to be matched fuzzily
+                    if keys[i].len() >= 4 && keys[j].len() >= 4 && jaro_winkler(keys[i], keys[j]) >= self.threshold {
+                        clusters.union(i, j);
+                    }
+                }
+            }
+        }
+
+        let mut organizations = Organizations::default();
+        for group in clusters.groups() {
+            // most frequent normalized name, and most frequent raw name, ties to the first
+            let canonical = group.iter()
+                .max_by(|a, b| normalized[keys[**a]].1.cmp(&normalized[keys[**b]].1).then(b.cmp(a)))
+                .map(|i| keys[*i].clone())
+                .unwrap_or_default();
+            let mut variants: Vec<(&str, u64)> = group.iter()
+                .flat_map(|i| normalized[keys[*i]].0.iter().map(|raw| (*raw, self.names[*raw])))
+                .collect();
+            variants.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+            let index = organizations.organizations.len();
+            for (raw, _) in &variants {
+                organizations.by_name.insert((*raw).to_owned(), index);
+            }
+            organizations.organizations.push(Organization {
+                id: stable_id("O", &canonical),
+                name: variants[0].0.to_owned(),
+                normalized: canonical,
+                count: variants.iter().map(|(_, count)| count).sum(),
+                variants: variants.into_iter().map(|(raw, _)| raw.to_owned()).collect(),
+            });
+        }
+
+        organizations
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Organization {
+    pub id: String,
+    /// most frequent raw name
+    pub name: String,
+    /// most frequent normalized name, which the id is made from
+    pub normalized: String,
+    /// raw names, most frequent first
+    pub variants: Vec<String>,
+    /// assignee occurrences over all variants
+    pub count: u64,
+}
+
+/// One raw name and its organization; the row form of `Organizations`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct NameRow<'a> {
+    pub raw_name: &'a str,
+    pub normalized_name: String,
+    pub org_id: &'a str,
+    pub org_name: &'a str,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Organizations {
+    organizations: Vec<Organization>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Organizations {
+    pub fn organizations(&self) -> &[Organization] {
+        &self.organizations
+    }
+
+    /// the organization of a raw name as it was added
+    pub fn get(&self, raw: &str) -> Option<&Organization> {
+        self.by_name.get(raw.trim()).map(|i| &self.organizations[*i])
+    }
+
+    pub fn id(&self, raw: &str) -> Option<&str> {
+        self.get(raw).map(|org| org.id.as_str())
+    }
+
+    /// one row per raw name, grouped by organization
+    pub fn rows(&self) -> Vec<NameRow<'_>> {
+        self.organizations.iter()
+            .flat_map(|org| org.variants.iter().map(move |raw| NameRow {
+                raw_name: raw,
+                normalized_name: normalize_name(raw),
+                org_id: &org.id,
+                org_name: &org.name,
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_names() {
+        assert_eq!(normalize_name("International Business Machines Corporation"), "INTERNATIONAL BUSINESS MACHINES");
+        assert_eq!(normalize_name("INTERNATIONAL BUSINESS MACHINES CORP."), "INTERNATIONAL BUSINESS MACHINES");
+        assert_eq!(normalize_name("Robert Bosch GmbH & Co. KG"), "ROBERT BOSCH");
+        assert_eq!(normalize_name("Kabushiki Kaisha Toshiba"), "TOSHIBA");
+        assert_eq!(normalize_name("Nestlé S.A."), "NESTLE");
+        assert_eq!(normalize_name("The Procter & Gamble Company"), "PROCTER AND GAMBLE");
+        assert_eq!(normalize_name("I.B.M."), "IBM");
+        assert_eq!(normalize_name("Inc."), "INC");
+        assert_eq!(normalize_name("株式会社東芝"), "株式会社東芝");
+        assert_eq!(normalize_name("ОАО «Газпром»"), "ОАО ГАЗПРОМ");
+        assert_eq!(normalize_name(" -- "), "--");
+    }
+
+    #[test]
+    fn organizations() {
+        let mut aliases = Aliases::new();
+        aliases.insert("IBM", "International Business Machines");
+
+        let mut harmonizer = Harmonizer::new(0.95).with_aliases(aliases);
+        for name in &[
+            "International Business Machines Corporation",
+            "International Business Machines Corporation",
+            "INTERNATIONAL BUSINESS MACHINES CORP.",
+            "IBM",
+            "International Busines Machines Corp",
+            "Intel Corporation",
+            "株式会社東芝",
+            "ソニー株式会社",
+        ] {
+            harmonizer.add_name(name);
+        }
+
+        let orgs = harmonizer.organizations();
+        assert_eq!(orgs.organizations().len(), 4);
+        let ibm = orgs.get("IBM").unwrap();
+        assert_eq!(ibm.name, "International Business Machines Corporation");
+        assert_eq!(ibm.count, 5);
+        assert_eq!(orgs.id("International Busines Machines Corp"), Some(ibm.id.as_str()));
+        assert_eq!(ibm.id, stable_id("O", "INTERNATIONAL BUSINESS MACHINES"));
+        assert_ne!(orgs.id("Intel Corporation"), Some(ibm.id.as_str()));
+        assert_ne!(orgs.id("株式会社東芝"), orgs.id("ソニー株式会社"));
+    }
+}
@@ -5,13 +5,36 @@
 #![feature(custom_attribute)]
 
 use csv;
+use serde::Serialize;
 use snafu::{Snafu, ResultExt};
 use std::fs;
-use std::io::BufReader;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
-use uspto::{PatentGrants, PatentOutput};
+use uspto::data::PatentGrant;
+use uspto::tables::TablesWriter;
+use uspto::techspace::{self, CoClassification};
+use uspto::{Columns, PatentGrants, PatentOutput};
+use uspto::assignee::{Aliases, Harmonizer};
+use uspto::citation::{CitationGraph, CitationMeasures};
+use uspto::collaboration::{Actor, Collaboration, Weighting, Windows};
+use uspto::complexity::{Complexity, CountMatrix};
+use uspto::concordance::UspcConcordance;
+use uspto::country::{CountryCode, CountryNormalizer, SuccessorPolicy};
+use uspto::cube::{ClassCounting, CountryOf, Counting, Cube, Technology, YearOf};
 use uspto::fetch;
+use uspto::geocode::Gazetteer;
+use uspto::inventor::Disambiguator;
+use uspto::locarno::EditionConcordance;
+use uspto::network::NetworkFormat;
+use uspto::pgcopy::{PgCopyFormat, PgCopyWriter};
+use uspto::scheme::Level;
+use uspto::region::{RegionLevel, Regions};
+#[cfg(feature = "sqlite")]
+use uspto::sqlite::SqliteExport;
+#[cfg(feature = "columnar")]
+use uspto::columnar;
 
 fn main() {
     match run() {
@@ -36,28 +59,549 @@ fn run() -> Result<(), Error> {
 
             Ok(())
         },
-        Command::Process { data_filepath, target_filepath } => {
-            process(&data_filepath, &target_filepath)
+        Command::Process { data_filepath, target_filepath, format, columns, separator } => {
+            let columns = match columns {
+                Some(spec) => Some(Columns::new(&spec, &separator).context(UsPto)?),
+                None => None,
+            };
+            process(&data_filepath, &target_filepath, format, columns)
+        },
+        Command::Cube { data_filepaths, target_filepath, cube, rows } => {
+            if rows {
+                cube_rows(&data_filepaths, &target_filepath, &cube.cube()?)
+            } else {
+                aggregate_cube(&data_filepaths, &target_filepath, cube.cube()?)
+            }
+        },
+        Command::Complexity { data_filepaths, target_dir, cube, rca_threshold, reflections } => {
+            complexity(&data_filepaths, &target_dir, cube.cube()?, rca_threshold, reflections)
+        },
+        Command::Techspace {
+            data_filepaths,
+            target_filepath,
+            format,
+            source,
+            cube,
+            include_search,
+            min_cooccurrence,
+            rca_threshold,
+        } => {
+            let graph = match source {
+                ProximitySource::CoClassification => {
+                    let mut co = CoClassification::new(cube.technology, include_search);
+                    for_each_grant(&data_filepaths, |patent| {
+                        co.add(&patent);
+                        Ok(())
+                    })?;
+                    co.graph(min_cooccurrence)
+                },
+                ProximitySource::Rca => {
+                    let cube = fill_cube(&data_filepaths, cube.cube()?)?;
+                    let matrix = CountMatrix::from_cube(&cube, None);
+                    techspace::rca_graph(&Complexity::new(&matrix, rca_threshold))
+                },
+            };
+
+            let f = fs::File::create(&target_filepath)
+                .context(WriteOutput)?;
+            graph.write(format, BufWriter::new(f))
+                .context(UsPto)
+        },
+        Command::Assignees { data_filepaths, target_filepath, aliases, threshold } => {
+            assignees(&data_filepaths, &target_filepath, aliases.as_ref().map(|p| p.as_path()), threshold)
+        },
+        Command::Inventors { data_filepaths, target_filepath, threshold } => {
+            inventors(&data_filepaths, &target_filepath, threshold)
+        },
+        Command::Collaboration {
+            data_filepaths,
+            target_dir,
+            actor,
+            format,
+            weighting,
+            year,
+            window_length,
+            window_step,
+            window_start,
+            inventor_ids,
+            organization_ids,
+        } => {
+            let collaboration = Collaboration::new(actor, year)
+                .with_weighting(weighting);
+            let windows = window_length.map(|length| Windows { start: window_start, length, step: window_step.unwrap_or(length) });
+            collaboration_networks(
+                &data_filepaths,
+                &target_dir,
+                collaboration,
+                windows,
+                inventor_ids.as_ref().map(|p| p.as_path()),
+                organization_ids.as_ref().map(|p| p.as_path()),
+                format,
+            )
+        },
+        Command::Citations { data_filepaths, target_filepath, edges, windows, level } => {
+            citations(&data_filepaths, &target_filepath, edges.as_ref().map(|p| p.as_path()), &windows, level)
+        },
+        Command::Regions { data_filepaths, target_filepath, crosswalks } => {
+            regions(&data_filepaths, &target_filepath, crosswalks.regions(None)?)
+        },
+        Command::Geocode { data_filepaths, target_filepath, gazetteer, admin1, admin2, min_similarity } => {
+            geocode(
+                &data_filepaths,
+                &target_filepath,
+                &gazetteer,
+                admin1.as_ref().map(|p| p.as_path()),
+                admin2.as_ref().map(|p| p.as_path()),
+                min_similarity,
+            )
+        },
+        Command::Export { data_filepaths, sqlite, postgres, postgres_format } => {
+            export(
+                &data_filepaths,
+                sqlite.as_ref().map(|p| p.as_path()),
+                postgres.as_ref().map(|p| p.as_path()),
+                postgres_format.0,
+            )
         },
     }
 }
 
-fn process(data_path: &Path, target_path: &Path) -> Result<(), Error> {
-    let f = fs::File::open(data_path)
+/// Writes the aggregated cube as csv.
+fn aggregate_cube(data_paths: &[PathBuf], target_path: &Path, cube: Cube) -> Result<(), Error> {
+    let cube = fill_cube(data_paths, cube)?;
+
+    let f = fs::File::create(target_path)
+        .context(WriteOutput)?;
+    cube.write_csv(BufWriter::new(f))
+        .context(UsPto)?;
+
+    Ok(())
+}
+
+/// Calls `f` with each grant of each file. A parse error is reported and ends that file,
+/// but not the others.
+fn for_each_grant<F>(data_paths: &[PathBuf], mut f: F) -> Result<(), Error>
+    where F: FnMut(PatentGrant) -> Result<(), Error>,
+{
+    for data_path in data_paths {
+        let file = fs::File::open(data_path)
+            .context(OpenDataFile)?;
+
+        for patent_res in PatentGrants::from_reader(BufReader::new(file)) {
+            match patent_res {
+                Ok(patent) => f(patent)?,
+                Err(err) => {
+                    eprintln!("{}: {}", data_path.display(), err);
+                    break;
+                },
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Aggregates all files into one cube; only the cells are kept in memory.
+fn fill_cube(data_paths: &[PathBuf], mut cube: Cube) -> Result<Cube, Error> {
+    for_each_grant(data_paths, |patent| {
+        cube.add(&patent);
+        Ok(())
+    })?;
+
+    println!("Counted {} grants ({} without country or technology)", cube.grants(), cube.skipped());
+
+    Ok(cube)
+}
+
+/// Writes one csv per complexity table to `target_dir`, each covering every year in the cube.
+fn complexity(
+    data_paths: &[PathBuf],
+    target_dir: &Path,
+    cube: Cube,
+    rca_threshold: f64,
+    reflections: usize,
+    ) -> Result<(), Error>
+{
+    let cube = fill_cube(data_paths, cube)?;
+
+    fs::create_dir_all(target_dir)
+        .context(WriteOutput)?;
+    let create = |table: &str| csv::Writer::from_path(target_dir.join(format!("{}.csv", table)))
+        .context(WriteCsv);
+    let mut rca_wtr = create("rca")?;
+    let mut countries_wtr = create("countries")?;
+    let mut technologies_wtr = create("technologies")?;
+    let mut proximity_wtr = create("proximity")?;
+    let mut country_reflections_wtr = create("country_reflections")?;
+    let mut technology_reflections_wtr = create("technology_reflections")?;
+
+    for year in cube.years() {
+        let matrix = CountMatrix::from_cube(&cube, Some(year));
+        let complexity = Complexity::new(&matrix, rca_threshold);
+        let (country_reflections, technology_reflections) = complexity.reflections(reflections);
+
+        write_csv_rows(&mut rca_wtr, complexity.rca_rows())?;
+        write_csv_rows(&mut countries_wtr, complexity.country_rows())?;
+        write_csv_rows(&mut technologies_wtr, complexity.technology_rows())?;
+        write_csv_rows(&mut proximity_wtr, complexity.proximity_rows())?;
+        write_csv_rows(&mut country_reflections_wtr, country_reflections)?;
+        write_csv_rows(&mut technology_reflections_wtr, technology_reflections)?;
+    }
+
+    for wtr in vec![
+        &mut rca_wtr,
+        &mut countries_wtr,
+        &mut technologies_wtr,
+        &mut proximity_wtr,
+        &mut country_reflections_wtr,
+        &mut technology_reflections_wtr,
+    ] {
+        wtr.flush()
+            .context(WriteOutput)?;
+    }
+
+    Ok(())
+}
+
+fn write_csv_rows<T: Serialize>(wtr: &mut csv::Writer<fs::File>, rows: Vec<T>) -> Result<(), Error> {
+    for row in rows {
+        wtr.serialize(row)
+            .context(WriteCsv)?;
+    }
+    Ok(())
+}
+
+/// Writes each grant's weighted cells instead of aggregating them.
+fn cube_rows(data_paths: &[PathBuf], target_path: &Path, cube: &Cube) -> Result<(), Error> {
+    let mut wtr = csv::Writer::from_path(target_path)
+        .context(WriteCsv)?;
+
+    for_each_grant(data_paths, |patent| write_csv_rows(&mut wtr, cube.weights(&patent)))?;
+
+    wtr.flush()
+        .context(WriteOutput)
+}
+
+/// Clusters assignee names over all files and writes each raw name with its organization id.
+fn assignees(data_paths: &[PathBuf], target_path: &Path, aliases_path: Option<&Path>, threshold: f64) -> Result<(), Error> {
+    let mut harmonizer = Harmonizer::new(threshold);
+    if let Some(path) = aliases_path {
+        let f = fs::File::open(path)
+            .context(OpenDataFile)?;
+        harmonizer = harmonizer.with_aliases(Aliases::from_reader(BufReader::new(f)).context(UsPto)?);
+    }
+
+    for_each_grant(data_paths, |patent| {
+        harmonizer.add(&patent);
+        Ok(())
+    })?;
+
+    let organizations = harmonizer.organizations();
+    println!("{} organizations", organizations.organizations().len());
+
+    let mut wtr = csv::Writer::from_path(target_path)
+        .context(WriteCsv)?;
+    write_csv_rows(&mut wtr, organizations.rows())?;
+    wtr.flush()
+        .context(WriteOutput)
+}
+
+/// Disambiguates inventors over all files and writes each inventor mention with its inventor id.
+fn inventors(data_paths: &[PathBuf], target_path: &Path, threshold: f64) -> Result<(), Error> {
+    let mut disambiguator = Disambiguator::new(threshold);
+    for_each_grant(data_paths, |patent| {
+        disambiguator.add(&patent);
+        Ok(())
+    })?;
+
+    let ids = disambiguator.ids();
+    let mut distinct = ids.clone();
+    distinct.sort();
+    distinct.dedup();
+    println!("{} mentions, {} inventors", ids.len(), distinct.len());
+
+    let mut wtr = csv::Writer::from_path(target_path)
+        .context(WriteCsv)?;
+    write_csv_rows(&mut wtr, disambiguator.rows(&ids))?;
+    wtr.flush()
+        .context(WriteOutput)
+}
+
+/// Locates every inventor and assignee address against a GeoNames dump and writes one row
+/// per party.
+fn geocode(
+    data_paths: &[PathBuf],
+    target_path: &Path,
+    gazetteer_path: &Path,
+    admin1_path: Option<&Path>,
+    admin2_path: Option<&Path>,
+    min_similarity: f64,
+    ) -> Result<(), Error>
+{
+    let f = fs::File::open(gazetteer_path)
         .context(OpenDataFile)?;
-    let f = BufReader::new(f);
+    let mut gazetteer = Gazetteer::from_geonames(BufReader::new(f))
+        .context(UsPto)?
+        .with_min_similarity(min_similarity);
+    if let Some(path) = admin1_path {
+        let f = fs::File::open(path)
+            .context(OpenDataFile)?;
+        gazetteer = gazetteer.with_admin1_names(BufReader::new(f)).context(UsPto)?;
+    }
+    if let Some(path) = admin2_path {
+        let f = fs::File::open(path)
+            .context(OpenDataFile)?;
+        gazetteer = gazetteer.with_admin2_names(BufReader::new(f)).context(UsPto)?;
+    }
+    println!("{} places", gazetteer.len());
+
+    let mut wtr = csv::Writer::from_path(target_path)
+        .context(WriteCsv)?;
+    let (mut parties, mut located) = (0, 0);
+    for_each_grant(data_paths, |patent| {
+        let rows = gazetteer.rows(&patent);
+        parties += rows.len();
+        located += rows.iter().filter(|row| row.geonameid.is_some()).count();
+        write_csv_rows(&mut wtr, rows)
+    })?;
+    println!("{} of {} parties located", located, parties);
+
+    wtr.flush()
+        .context(WriteOutput)
+}
+
+/// Writes one collaboration network per time window, named by its first and last year
+/// (e.g. collaboration-2000-2004.graphml), or collaboration.graphml without windows.
+fn collaboration_networks(
+    data_paths: &[PathBuf],
+    target_dir: &Path,
+    mut collaboration: Collaboration,
+    windows: Option<Windows>,
+    inventor_ids_path: Option<&Path>,
+    organization_ids_path: Option<&Path>,
+    format: NetworkFormat,
+    ) -> Result<(), Error>
+{
+    if let Some(windows) = windows {
+        if windows.length == 0 || windows.step == 0 {
+            return Err(Error::CliEmptyWindow);
+        }
+        collaboration = collaboration.with_windows(windows);
+    }
+    if let Some(path) = inventor_ids_path {
+        let f = fs::File::open(path)
+            .context(OpenDataFile)?;
+        collaboration = collaboration.with_inventor_ids(BufReader::new(f)).context(UsPto)?;
+    }
+    if let Some(path) = organization_ids_path {
+        let f = fs::File::open(path)
+            .context(OpenDataFile)?;
+        collaboration = collaboration.with_organization_ids(BufReader::new(f)).context(UsPto)?;
+    }
+
+    for_each_grant(data_paths, |patent| {
+        collaboration.add(&patent);
+        Ok(())
+    })?;
+    println!(
+        "Counted {} grants ({} without actors or outside the windows)",
+        collaboration.grants() - collaboration.skipped(),
+        collaboration.skipped(),
+    );
+
+    fs::create_dir_all(target_dir)
+        .context(WriteOutput)?;
+    let extension = match format {
+        NetworkFormat::EdgeList => "csv",
+        NetworkFormat::GraphMl => "graphml",
+        NetworkFormat::Gexf => "gexf",
+    };
+    for (window, graph) in collaboration.graphs() {
+        let name = match window {
+            Some((first, last)) => format!("collaboration-{}-{}.{}", first, last, extension),
+            None => format!("collaboration.{}", extension),
+        };
+        let f = fs::File::create(target_dir.join(name))
+            .context(WriteOutput)?;
+        graph.write(format, BufWriter::new(f))
+            .context(UsPto)?;
+    }
+
+    Ok(())
+}
+
+/// Builds the citation graph of all files and writes each grant's citation measures, and
+/// optionally the citations themselves.
+fn citations(data_paths: &[PathBuf], target_path: &Path, edges_path: Option<&Path>, windows: &[u32], level: Level) -> Result<(), Error> {
+    let mut graph = CitationGraph::new(level);
+    for_each_grant(data_paths, |patent| {
+        graph.add(&patent);
+        Ok(())
+    })?;
+    println!("{} grants", graph.len());
+
+    let mut wtr = csv::Writer::from_path(target_path)
+        .context(WriteCsv)?;
+    wtr.write_record(CitationMeasures::header(windows))
+        .context(WriteCsv)?;
+    for measures in graph.measures(windows) {
+        wtr.write_record(measures.record())
+            .context(WriteCsv)?;
+    }
+    wtr.flush()
+        .context(WriteOutput)?;
+
+    if let Some(path) = edges_path {
+        let mut wtr = csv::Writer::from_path(path)
+            .context(WriteCsv)?;
+        write_csv_rows(&mut wtr, graph.edges())?;
+        wtr.flush()
+            .context(WriteOutput)?;
+    }
 
+    Ok(())
+}
+
+/// Writes the county and regions of every US inventor and assignee.
+fn regions(data_paths: &[PathBuf], target_path: &Path, regions: Regions) -> Result<(), Error> {
     let mut wtr = csv::Writer::from_path(target_path)
         .context(WriteCsv)?;
+    let mut unplaced = 0;
+    for_each_grant(data_paths, |patent| {
+        let rows = regions.rows(&patent);
+        unplaced += rows.iter().filter(|row| row.county.is_none()).count();
+        write_csv_rows(&mut wtr, rows)
+    })?;
+    println!("{} US parties without a county", unplaced);
+
+    wtr.flush()
+        .context(WriteOutput)
+}
+
+/// Loads weekly files into a sqlite database (one transaction per file) and/or writes
+/// postgres copy files, parsing each file once.
+fn export(
+    data_paths: &[PathBuf],
+    sqlite_path: Option<&Path>,
+    postgres_dir: Option<&Path>,
+    postgres_format: PgCopyFormat,
+    ) -> Result<(), Error>
+{
+    if sqlite_path.is_none() && postgres_dir.is_none() {
+        return Err(Error::CliNoExportTarget);
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    {
+        if sqlite_path.is_some() {
+            return Err(Error::FeatureDisabled { feature: "sqlite" });
+        }
+    }
+    #[cfg(feature = "sqlite")]
+    let mut sqlite = match sqlite_path {
+        Some(path) => Some(SqliteExport::open(path).context(UsPto)?),
+        None => None,
+    };
+
+    let mut postgres = match postgres_dir {
+        Some(dir) => Some(PgCopyWriter::from_dir(dir, postgres_format).context(UsPto)?),
+        None => None,
+    };
+
+    let mut export_weeks = || -> Result<(), Error> {
+        for data_path in data_paths {
+            let file_name = data_path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let f = fs::File::open(data_path)
+                .context(OpenDataFile)?;
+
+            // postgres rows are staged as grants stream past on their way to sqlite, and
+            // only written out once the week is in
+            let grants = PatentGrants::from_reader(BufReader::new(f))
+                .map(|grant_res| {
+                    let grant = grant_res?;
+                    if let Some(ref mut postgres) = postgres {
+                        postgres.write(&grant)?;
+                    }
+                    Ok(grant)
+                });
+
+            #[cfg(feature = "sqlite")]
+            let res = match sqlite {
+                Some(ref mut sqlite) => sqlite.load_week(&file_name, grants),
+                None => drain(grants),
+            };
+            #[cfg(not(feature = "sqlite"))]
+            let res = drain(grants);
+
+            // a bad grant costs its week in both outputs; a write error stops the run
+            match res {
+                Ok(count) => {
+                    if let Some(ref mut postgres) = postgres {
+                        postgres.commit()
+                            .context(UsPto)?;
+                    }
+                    println!("Exported {} grants from {}", count, file_name);
+                },
+                Err(err @ uspto::Error::Sqlite { .. }) | Err(err @ uspto::Error::WritePgCopy { .. }) => {
+                    return Err(err).context(UsPto);
+                },
+                Err(err) => {
+                    if let Some(ref mut postgres) = postgres {
+                        postgres.rollback();
+                    }
+                    eprintln!("{}: {}; skipped this week", data_path.display(), err);
+                },
+            }
+        }
+        Ok(())
+    };
+    let res = export_weeks();
+
+    // copy files need their trailer even when the run stops early
+    if let Some(postgres) = postgres {
+        let finished = postgres.finish()
+            .context(UsPto);
+        res?;
+        return finished;
+    }
+
+    res
+}
+
+/// consumes grants, stopping at the first error; returns the count
+fn drain<I>(grants: I) -> Result<usize, uspto::Error>
+    where I: Iterator<Item = Result<PatentGrant, uspto::Error>>,
+{
+    let mut count = 0;
+    for grant in grants {
+        grant?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn process(
+    data_path: &Path,
+    target_path: &Path,
+    format: OutputFormat,
+    columns: Option<Columns>,
+) -> Result<(), Error>
+{
+    let f = fs::File::open(data_path)
+        .context(OpenDataFile)?;
+    let f = BufReader::new(f);
+
+    let mut wtr = OutputWriter::from_path(target_path, format, columns)?;
 
     // deserialize returns an iter of PatentGrant
     let patents = PatentGrants::from_reader(f);
     for patent_res in patents {
         match patent_res {
             Ok(patent) => {
-                let output: PatentOutput = (&patent).into();
-                wtr.serialize(output)
-                    .context(WriteCsv)?;
+                wtr.write(&patent)?;
 
                 // Some examples for inspecting data. You may be able to also use grep
                 //if patent.us_bibliographic_data_grant.publication_reference.doc_number == "RE047539" {
@@ -90,13 +634,174 @@ fn process(data_path: &Path, target_path: &Path) -> Result<(), Error> {
         }
     }
 
-    wtr.flush()
-        .context(WriteOutput)?;
+    wtr.finish()?;
 
 
     Ok(())
 }
 
+/// Output formats for `process`
+#[derive(Debug, Clone, Copy)]
+enum OutputFormat {
+    /// flat `PatentOutput` rows
+    Csv,
+    /// one complete nested `PatentGrant` per line
+    Jsonl,
+    /// normalized csv tables, one per entity, in the target directory
+    Tables,
+    /// parquet, one row group per weekly file
+    #[cfg(feature = "columnar")]
+    Parquet,
+    /// arrow ipc file, one record batch per weekly file
+    #[cfg(feature = "columnar")]
+    Arrow,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            "tables" => Ok(OutputFormat::Tables),
+            #[cfg(feature = "columnar")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            #[cfg(feature = "columnar")]
+            "arrow" => Ok(OutputFormat::Arrow),
+            #[cfg(not(feature = "columnar"))]
+            "parquet" | "arrow" => Err(format!("output format {:?} requires the columnar feature", s)),
+            _ => Err(format!("unknown output format {:?}, expected one of: csv, jsonl, tables, parquet, arrow", s)),
+        }
+    }
+}
+
+/// cli parsing for `PgCopyFormat`, which lives in the lib
+#[derive(Debug)]
+struct PgCopyFormatArg(PgCopyFormat);
+
+impl FromStr for PgCopyFormatArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(PgCopyFormatArg(PgCopyFormat::Text)),
+            "binary" => Ok(PgCopyFormatArg(PgCopyFormat::Binary)),
+            _ => Err(format!("unknown postgres format {:?}, expected text or binary", s)),
+        }
+    }
+}
+
+/// Writes each patent as it's parsed; nothing is buffered beyond the io buffer,
+/// except for columnar formats, which hold the weekly file to write it as one row group.
+enum OutputWriter {
+    Csv(csv::Writer<fs::File>),
+    CsvColumns(csv::Writer<fs::File>, Columns),
+    Jsonl(BufWriter<fs::File>),
+    Tables(TablesWriter),
+    #[cfg(feature = "columnar")]
+    Parquet(columnar::ParquetWriter<fs::File>, Vec<PatentGrant>),
+    #[cfg(feature = "columnar")]
+    Arrow(columnar::IpcWriter<BufWriter<fs::File>>, Vec<PatentGrant>),
+}
+
+impl OutputWriter {
+    fn from_path(target_path: &Path, format: OutputFormat, columns: Option<Columns>) -> Result<Self, Error> {
+        match (format, columns) {
+            (OutputFormat::Csv, Some(columns)) => {
+                let mut wtr = csv::Writer::from_path(target_path)
+                    .context(WriteCsv)?;
+                wtr.write_record(columns.header())
+                    .context(WriteCsv)?;
+                Ok(OutputWriter::CsvColumns(wtr, columns))
+            },
+            (_, Some(_)) => Err(Error::CliColumnsNotCsv),
+            (OutputFormat::Csv, None) => {
+                let wtr = csv::Writer::from_path(target_path)
+                    .context(WriteCsv)?;
+                Ok(OutputWriter::Csv(wtr))
+            },
+            (OutputFormat::Jsonl, None) => {
+                let f = fs::File::create(target_path)
+                    .context(WriteOutput)?;
+                Ok(OutputWriter::Jsonl(BufWriter::new(f)))
+            },
+            (OutputFormat::Tables, None) => {
+                let wtr = TablesWriter::from_dir(target_path)
+                    .context(UsPto)?;
+                Ok(OutputWriter::Tables(wtr))
+            },
+            #[cfg(feature = "columnar")]
+            (OutputFormat::Parquet, None) => {
+                let f = fs::File::create(target_path)
+                    .context(WriteOutput)?;
+                let wtr = columnar::ParquetWriter::new(f)
+                    .context(UsPto)?;
+                Ok(OutputWriter::Parquet(wtr, Vec::new()))
+            },
+            #[cfg(feature = "columnar")]
+            (OutputFormat::Arrow, None) => {
+                let f = fs::File::create(target_path)
+                    .context(WriteOutput)?;
+                let wtr = columnar::IpcWriter::new(BufWriter::new(f))
+                    .context(UsPto)?;
+                Ok(OutputWriter::Arrow(wtr, Vec::new()))
+            },
+        }
+    }
+
+    fn write(&mut self, patent: &PatentGrant) -> Result<(), Error> {
+        match self {
+            OutputWriter::Csv(wtr) => {
+                let output: PatentOutput = patent.into();
+                wtr.serialize(output)
+                    .context(WriteCsv)
+            },
+            OutputWriter::CsvColumns(wtr, columns) => {
+                wtr.write_record(columns.row(patent))
+                    .context(WriteCsv)
+            },
+            OutputWriter::Jsonl(wtr) => {
+                serde_json::to_writer(&mut *wtr, patent)
+                    .context(WriteJson)?;
+                wtr.write_all(b"\n")
+                    .context(WriteOutput)
+            },
+            OutputWriter::Tables(wtr) => {
+                wtr.write(patent)
+                    .context(UsPto)
+            },
+            #[cfg(feature = "columnar")]
+            OutputWriter::Parquet(_, week) | OutputWriter::Arrow(_, week) => {
+                week.push(patent.clone());
+                Ok(())
+            },
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self {
+            OutputWriter::Csv(mut wtr) | OutputWriter::CsvColumns(mut wtr, _) => {
+                wtr.flush().context(WriteOutput)
+            },
+            OutputWriter::Jsonl(mut wtr) => wtr.flush().context(WriteOutput),
+            OutputWriter::Tables(mut wtr) => wtr.flush().context(UsPto),
+            #[cfg(feature = "columnar")]
+            OutputWriter::Parquet(mut wtr, week) => {
+                wtr.write_row_group(&week)
+                    .and_then(|_| wtr.close())
+                    .context(UsPto)
+            },
+            #[cfg(feature = "columnar")]
+            OutputWriter::Arrow(mut wtr, week) => {
+                wtr.write_batch(&week)
+                    .and_then(|_| wtr.finish())
+                    .context(UsPto)
+            },
+        }
+    }
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(name="uspto")]
 struct CliOpt {
@@ -120,13 +825,378 @@ enum Command {
         data_filepath: PathBuf,
         #[structopt(long="target-path", parse(from_os_str))]
         target_filepath: PathBuf,
+        /// csv (flat PatentOutput rows), jsonl (full PatentGrant per line),
+        /// tables (one csv per entity; target path is a directory),
+        /// or with the columnar feature, parquet or arrow
+        #[structopt(long="format", default_value="csv")]
+        format: OutputFormat,
+        /// comma-separated columns for csv output instead of the default layout,
+        /// e.g. doc_number,app_date,cpc_main,inventor_countries,n_claims
+        #[structopt(long="columns")]
+        columns: Option<String>,
+        /// joins multi-valued fields (e.g. inventor_countries) in --columns output
+        #[structopt(long="separator", default_value=";")]
+        separator: String,
     },
+    /// country × technology × year counts, as csv
+    #[structopt(name="cube")]
+    Cube {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-path", parse(from_os_str))]
+        target_filepath: PathBuf,
+        #[structopt(flatten)]
+        cube: CubeOpts,
+        /// write one weighted row per grant and cell instead of aggregating
+        #[structopt(long="rows")]
+        rows: bool,
+    },
+    /// rca, eci/pci, proximity and density per year, as csv tables in the target directory
+    #[structopt(name="complexity")]
+    Complexity {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-dir", parse(from_os_str))]
+        target_dir: PathBuf,
+        #[structopt(flatten)]
+        cube: CubeOpts,
+        #[structopt(long="rca-threshold", default_value="1.0")]
+        rca_threshold: f64,
+        /// iterations of the method of reflections
+        #[structopt(long="reflections", default_value="18")]
+        reflections: usize,
+    },
+    /// technology space proximity network
+    #[structopt(name="techspace")]
+    Techspace {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-path", parse(from_os_str))]
+        target_filepath: PathBuf,
+        /// edge-list, graphml, or gexf
+        #[structopt(long="format", default_value="graphml")]
+        format: NetworkFormat,
+        /// co-classification (codes on the same grant) or rca (countries specializing in both,
+        /// over all years pooled)
+        #[structopt(long="source", default_value="co-classification")]
+        source: ProximitySource,
+        /// only --technology is used for co-classification
+        #[structopt(flatten)]
+        cube: CubeOpts,
+        /// count field of classification search codes as co-classified (cpc and uspc only)
+        #[structopt(long="include-search")]
+        include_search: bool,
+        /// leave out co-classified pairs seen on fewer grants
+        #[structopt(long="min-cooccurrence", default_value="1")]
+        min_cooccurrence: u64,
+        #[structopt(long="rca-threshold", default_value="1.0")]
+        rca_threshold: f64,
+    },
+    /// assignee organization ids: raw_name,normalized_name,org_id,org_name as csv
+    #[structopt(name="assignees")]
+    Assignees {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-path", parse(from_os_str))]
+        target_filepath: PathBuf,
+        /// csv of alias,name for names that normalization can't match, e.g. IBM
+        #[structopt(long="aliases", parse(from_os_str))]
+        aliases: Option<PathBuf>,
+        /// Jaro-Winkler similarity at which normalized names are clustered
+        #[structopt(long="threshold", default_value="0.95")]
+        threshold: f64,
+    },
+    /// inventor ids: doc_number,sequence,inventor_id,first_name,last_name,city,state,country as csv
+    #[structopt(name="inventors")]
+    Inventors {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-path", parse(from_os_str))]
+        target_filepath: PathBuf,
+        /// pairwise score (0 to 1) at which mentions are linked
+        #[structopt(long="threshold", default_value="0.55")]
+        threshold: f64,
+    },
+    /// inventor, country, city or organization collaboration networks, one file per time window
+    #[structopt(name="collaboration")]
+    Collaboration {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-dir", parse(from_os_str))]
+        target_dir: PathBuf,
+        /// inventor, country (of inventors), city (of inventors), or organization (assignees)
+        #[structopt(long="actor", default_value="country")]
+        actor: Actor,
+        /// edge-list, graphml, or gexf
+        #[structopt(long="format", default_value="graphml")]
+        format: NetworkFormat,
+        /// whole (one per grant) or fractional (1 / (n - 1) per pair of n actors)
+        #[structopt(long="weighting", default_value="whole")]
+        weighting: Weighting,
+        /// grant or application
+        #[structopt(long="year", default_value="grant")]
+        year: YearOf,
+        /// years per window; one network over all years if not given
+        #[structopt(long="window-length")]
+        window_length: Option<u16>,
+        /// years between window starts; the window length if not given
+        #[structopt(long="window-step")]
+        window_step: Option<u16>,
+        /// first year of the first window
+        #[structopt(long="window-start", default_value="1976")]
+        window_start: u16,
+        /// inventor ids as written by the inventors command
+        #[structopt(long="inventor-ids", parse(from_os_str))]
+        inventor_ids: Option<PathBuf>,
+        /// organization ids as written by the assignees command
+        #[structopt(long="organization-ids", parse(from_os_str))]
+        organization_ids: Option<PathBuf>,
+    },
+    /// forward and backward citation counts, self-citations, originality and generality of
+    /// each grant, as csv
+    #[structopt(name="citations")]
+    Citations {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-path", parse(from_os_str))]
+        target_filepath: PathBuf,
+        /// also write the citations: citing,cited,category,self_citation
+        #[structopt(long="edges-path", parse(from_os_str))]
+        edges: Option<PathBuf>,
+        /// years after grant to count forward citations in, comma separated
+        #[structopt(long="windows", use_delimiter=true, default_value="3,5,10")]
+        windows: Vec<u32>,
+        /// cpc level for originality and generality: section, class, subclass, main-group,
+        /// or subgroup
+        #[structopt(long="level", default_value="subclass")]
+        level: Level,
+    },
+    /// counties, cbsas, msas and commuting zones of US inventors and assignees as csv; needs
+    /// --places or --places-gazetteer
+    #[structopt(name="regions")]
+    Regions {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-path", parse(from_os_str))]
+        target_filepath: PathBuf,
+        #[structopt(flatten)]
+        crosswalks: RegionOpts,
+    },
+    /// inventor and assignee locations from a GeoNames dump, one csv row per party
+    #[structopt(name="geocode")]
+    Geocode {
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        #[structopt(long="target-path", parse(from_os_str))]
+        target_filepath: PathBuf,
+        /// GeoNames table, e.g. allCountries.txt or cities15000.txt
+        #[structopt(long="gazetteer", parse(from_os_str))]
+        gazetteer: PathBuf,
+        /// GeoNames admin1CodesASCII.txt, for state/region names
+        #[structopt(long="admin1", parse(from_os_str))]
+        admin1: Option<PathBuf>,
+        /// GeoNames admin2Codes.txt, for county/district names
+        #[structopt(long="admin2", parse(from_os_str))]
+        admin2: Option<PathBuf>,
+        /// Jaro-Winkler similarity a fuzzy city match needs
+        #[structopt(long="min-similarity", default_value="0.9")]
+        min_similarity: f64,
+    },
+    #[structopt(name="export")]
+    Export {
+        /// weekly grant files; each is loaded in its own transaction
+        #[structopt(parse(from_os_str), required=true)]
+        data_filepaths: Vec<PathBuf>,
+        /// sqlite database to create or update (requires the sqlite feature)
+        #[structopt(long="sqlite", parse(from_os_str))]
+        sqlite: Option<PathBuf>,
+        /// directory for postgres schema.sql and per-table copy files
+        #[structopt(long="postgres", parse(from_os_str))]
+        postgres: Option<PathBuf>,
+        /// text (psql scripts with inline COPY data) or binary
+        #[structopt(long="postgres-format", default_value="text")]
+        postgres_format: PgCopyFormatArg,
+    },
+}
+
+#[derive(Debug)]
+enum ProximitySource {
+    CoClassification,
+    Rca,
+}
+
+impl FromStr for ProximitySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "co-classification" => Ok(ProximitySource::CoClassification),
+            "rca" => Ok(ProximitySource::Rca),
+            _ => Err(format!("unknown proximity source {:?}, expected co-classification or rca", s)),
+        }
+    }
+}
+
+/// Axes and counting of the country × technology × year cube
+#[derive(Debug, StructOpt)]
+struct CubeOpts {
+    /// cpc-section, cpc-class, cpc-subclass, cpc-group, ipc, uspc, locarno, locarno-subclass,
+    /// or wipo-field
+    #[structopt(long="technology", default_value="cpc-subclass")]
+    technology: Technology,
+    /// inventor or assignee
+    #[structopt(long="country", default_value="inventor")]
+    country: CountryOf,
+    /// grant or application
+    #[structopt(long="year", default_value="grant")]
+    year: YearOf,
+    /// how a grant is shared among countries: whole, fractional (per inventor or assignee),
+    /// fractional-country, or first (first inventor or assignee only)
+    #[structopt(long="counting", default_value="whole")]
+    counting: Counting,
+    /// how a grant is shared among its classes: whole or fractional
+    #[structopt(long="class-counting", default_value="whole")]
+    class_counting: ClassCounting,
+    /// normalize countries to ISO 3166 alpha2 or alpha3; raw grant codes if not given
+    #[structopt(long="iso")]
+    iso: Option<CountryCode>,
+    /// with --iso, how to map dissolved states (SU, YU, CS, AN): successor, historical, or drop
+    #[structopt(long="successors", default_value="successor")]
+    successors: SuccessorPolicy,
+    /// with a locarno technology, map codes to this edition so grants classified under
+    /// different editions are counted together
+    #[structopt(long="locarno-edition")]
+    locarno_edition: Option<u8>,
+    /// csv of locarno subclasses moved between editions (edition,code,next_code), needed by
+    /// --locarno-edition; codes not listed are taken as unchanged
+    #[structopt(long="locarno-concordance", parse(from_os_str))]
+    locarno_concordance: Option<PathBuf>,
+    /// csv of uspc to cpc (uspc,cpc), for --technology wipo-field at uspc subclass level
+    /// rather than the bundled class level
+    #[structopt(long="uspc-concordance", parse(from_os_str))]
+    uspc_concordance: Option<PathBuf>,
+    /// count US regions instead of countries: county, cbsa, msa, or cz (commuting zone);
+    /// needs --places or --places-gazetteer, and --cbsas or --commuting-zones above county
+    #[structopt(long="region")]
+    region: Option<RegionLevel>,
+    #[structopt(flatten)]
+    crosswalks: RegionOpts,
+}
+
+impl CubeOpts {
+    fn cube(&self) -> Result<Cube, Error> {
+        let mut cube = Cube::new(self.technology, self.country, self.year)
+            .with_counting(self.counting, self.class_counting);
+
+        if let Some(code) = self.iso {
+            cube = cube.with_normalizer(CountryNormalizer::new(code, self.successors));
+        }
+        if let Some(edition) = self.locarno_edition {
+            // without a list of moves every code would map to itself
+            let path = self.locarno_concordance.as_ref()
+                .ok_or(Error::CliNoLocarnoConcordance)?;
+            let f = fs::File::open(path)
+                .context(OpenDataFile)?;
+            let concordance = EditionConcordance::from_reader(BufReader::new(f))
+                .context(UsPto)?;
+            cube = cube.with_locarno_edition(concordance, edition);
+        }
+        if let Some(ref path) = self.uspc_concordance {
+            let f = fs::File::open(path)
+                .context(OpenDataFile)?;
+            let concordance = UspcConcordance::from_reader(BufReader::new(f))
+                .context(UsPto)?;
+            cube = cube.with_uspc_concordance(concordance);
+        }
+        if let Some(level) = self.region {
+            cube = cube.with_regions(self.crosswalks.regions(Some(level))?, level);
+        }
+
+        Ok(cube)
+    }
+}
+
+// crosswalks from US cities to counties, and counties to regions; a plain comment, since a
+// flattened struct's doc comment replaces the subcommand's about text
+#[derive(Debug, StructOpt)]
+struct RegionOpts {
+    /// csv of US cities' counties: state,city,county_fips[,weight]
+    #[structopt(long="places", parse(from_os_str))]
+    places: Option<PathBuf>,
+    /// GeoNames table (e.g. US.txt) for the counties of cities not in --places
+    #[structopt(long="places-gazetteer", parse(from_os_str))]
+    places_gazetteer: Option<PathBuf>,
+    /// csv of the OMB delineation: county_fips,cbsa_code,cbsa_title,cbsa_type
+    #[structopt(long="cbsas", parse(from_os_str))]
+    cbsas: Option<PathBuf>,
+    /// csv of county_fips,commuting_zone
+    #[structopt(long="commuting-zones", parse(from_os_str))]
+    commuting_zones: Option<PathBuf>,
+}
+
+impl RegionOpts {
+    /// errors without the crosswalks `level` needs; every level, or None for all of them,
+    /// needs counties
+    fn regions(&self, level: Option<RegionLevel>) -> Result<Regions, Error> {
+        let missing = if self.places.is_none() && self.places_gazetteer.is_none() {
+            Some("--places or --places-gazetteer")
+        } else {
+            match level {
+                Some(RegionLevel::Cbsa) | Some(RegionLevel::Msa) if self.cbsas.is_none() => Some("--cbsas"),
+                Some(RegionLevel::CommutingZone) if self.commuting_zones.is_none() => Some("--commuting-zones"),
+                _ => None,
+            }
+        };
+        if let Some(needs) = missing {
+            return Err(Error::CliNoCrosswalk { needs });
+        }
+
+        let mut regions = Regions::new();
+
+        if let Some(ref path) = self.places {
+            let f = fs::File::open(path)
+                .context(OpenDataFile)?;
+            regions = regions.with_places(BufReader::new(f)).context(UsPto)?;
+        }
+        if let Some(ref path) = self.places_gazetteer {
+            let f = fs::File::open(path)
+                .context(OpenDataFile)?;
+            let gazetteer = Gazetteer::from_geonames(BufReader::new(f))
+                .context(UsPto)?;
+            regions = regions.with_gazetteer(&gazetteer);
+        }
+        if let Some(ref path) = self.cbsas {
+            let f = fs::File::open(path)
+                .context(OpenDataFile)?;
+            regions = regions.with_cbsas(BufReader::new(f)).context(UsPto)?;
+        }
+        if let Some(ref path) = self.commuting_zones {
+            let f = fs::File::open(path)
+                .context(OpenDataFile)?;
+            regions = regions.with_commuting_zones(BufReader::new(f)).context(UsPto)?;
+        }
+
+        Ok(regions)
+    }
 }
 
 #[derive(Debug, Snafu)]
 enum Error {
     #[snafu(display("Cli Error: missing filepath"))]
     CliNoPath,
+    #[snafu(display("Cli Error: export needs a target: --sqlite and/or --postgres"))]
+    CliNoExportTarget,
+    #[snafu(display("Cli Error: --columns only applies to csv output"))]
+    CliColumnsNotCsv,
+    #[snafu(display("Cli Error: --locarno-edition needs --locarno-concordance"))]
+    CliNoLocarnoConcordance,
+    #[snafu(display("Cli Error: --window-length and --window-step must be at least 1"))]
+    CliEmptyWindow,
+    #[snafu(display("Cli Error: US regions need {}", needs))]
+    CliNoCrosswalk { needs: &'static str },
+    #[snafu(display("Cli Error: built without the {} feature", feature))]
+    #[cfg_attr(feature = "sqlite", allow(dead_code))]
+    FeatureDisabled { feature: &'static str },
     #[snafu(display("Open Datafile Error: {}", source))]
     OpenDataFile { source: std::io::Error },
     #[snafu(display("Read Datafile Error: {}", source))]
@@ -135,6 +1205,8 @@ enum Error {
     UsPto { source: uspto::Error },
     #[snafu(display("Write Csv Error: {}", source))]
     WriteCsv { source: csv::Error },
+    #[snafu(display("Write Json Error: {}", source))]
+    WriteJson { source: serde_json::Error },
     #[snafu(display("Serialize Output Error: {}", source))]
     WriteOutput { source: std::io::Error },
 }
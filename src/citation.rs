@@ -0,0 +1,351 @@
+//! Citation graph over a corpus of grants, and the citation measures of each grant.
+//!
+//! Cited US patents are resolved to `PatentNumber`s, so "5241599", "05241599" and
+//! "US 5,241,599" are one node; citations of pre-grant publications and foreign documents
+//! are left out. For every grant in the corpus:
+//! - forward citations: grants in the corpus citing it, in all and within N years of its
+//!   grant date (by the citing grant's date). Counts near the end of the corpus are
+//!   truncated, so the corpus should run N years past the grants measured.
+//! - self-citations: citations between grants sharing an assignee, by normalized name.
+//! - originality (over cited patents) and generality (over citing grants): one minus the
+//!   Herfindahl index of their technology classes. Each patent counts once, split equally
+//!   among its distinct classes at the graph's level. Cited patents outside the corpus are
+//!   classified by the cpc given in the citation, if any.
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::assignee::normalize_name;
+use crate::data::{BibliographicDataGrant, PatentGrant, UsCitation};
+use crate::number::PatentNumber;
+use crate::scheme::{roll_up, Level};
+
+/// The US patents a grant cites, resolved and without repeats, in citation order
+pub fn cited_patents(dg: &BibliographicDataGrant) -> Vec<(PatentNumber, &UsCitation)> {
+    let mut seen = BTreeSet::new();
+
+    dg.us_references_cited.iter()
+        .filter_map(|citation| {
+            let patcit = citation.patcit.as_ref()?;
+            let country = patcit.country.trim();
+            if !(country.is_empty() || country.eq_ignore_ascii_case("US")) {
+                return None;
+            }
+            // publications (A1, A2, ...) aren't patents
+            if patcit.kind.is_some() && patcit.kind_code().and_then(|kind| kind.patent_type()).is_none() {
+                return None;
+            }
+            patcit.patent_number().ok().map(|number| (number, citation))
+        })
+        .filter(|(number, _)| seen.insert(*number))
+        .collect()
+}
+
+fn distinct(symbols: impl Iterator<Item = String>, level: Level) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    symbols.filter_map(|symbol| roll_up(&symbol, level))
+        .filter(|class| seen.insert(class.clone()))
+        .collect()
+}
+
+/// one minus the Herfindahl index of class shares; each patent's classes share one count
+fn concentration<'a, I>(patents: I) -> Option<f64>
+    where I: IntoIterator<Item = &'a [String]>,
+{
+    let mut shares: BTreeMap<&str, f64> = BTreeMap::new();
+    let mut total = 0.0;
+    for classes in patents.into_iter().filter(|classes| !classes.is_empty()) {
+        for class in classes {
+            *shares.entry(class).or_insert(0.0) += 1.0 / classes.len() as f64;
+        }
+        total += 1.0;
+    }
+
+    if total == 0.0 {
+        return None;
+    }
+    Some(1.0 - shares.values().map(|share| (share / total).powi(2)).sum::<f64>())
+}
+
+/// the same day `years` later; Feb 29 goes to Feb 28
+fn add_years(date: NaiveDate, years: u32) -> NaiveDate {
+    let year = date.year() + years as i32;
+    date.with_year(year)
+        .or_else(|| (date - Duration::days(1)).with_year(year))
+        .unwrap_or(date)
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    grant_date: Option<NaiveDate>,
+    assignees: BTreeSet<String>,
+    classes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Cited {
+    number: PatentNumber,
+    category: Option<String>,
+    // from the citation, for patents outside the corpus
+    classes: Vec<String>,
+}
+
+/// One citation; the edge list form of the graph
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CitationEdge<'a> {
+    pub citing: String,
+    pub cited: String,
+    /// e.g. "cited by examiner"
+    pub category: Option<&'a str>,
+    /// None when the cited patent isn't in the corpus
+    pub self_citation: Option<bool>,
+}
+
+/// The citation measures of one grant
+#[derive(Debug, Clone, PartialEq)]
+pub struct CitationMeasures {
+    pub doc_number: String,
+    pub grant_date: Option<NaiveDate>,
+    pub backward_citations: usize,
+    /// backward citations of corpus grants sharing an assignee
+    pub backward_self_citations: usize,
+    pub forward_citations: usize,
+    pub forward_self_citations: usize,
+    /// (years, forward citations within them); None without a grant date
+    pub forward_within: Vec<(u32, Option<usize>)>,
+    pub originality: Option<f64>,
+    pub generality: Option<f64>,
+}
+
+impl CitationMeasures {
+    /// csv header, with a forward_<N>y column per window
+    pub fn header(windows: &[u32]) -> Vec<String> {
+        let mut header: Vec<String> = vec![
+            "doc_number", "grant_date", "backward_citations", "backward_self_citations",
+            "forward_citations", "forward_self_citations",
+        ].into_iter().map(|s| s.to_owned()).collect();
+        header.extend(windows.iter().map(|years| format!("forward_{}y", years)));
+        header.push("originality".to_owned());
+        header.push("generality".to_owned());
+        header
+    }
+
+    /// csv record matching `header`
+    pub fn record(&self) -> Vec<String> {
+        let optional = |value: Option<String>| value.unwrap_or_default();
+
+        let mut record = vec![
+            self.doc_number.clone(),
+            optional(self.grant_date.map(|date| date.format("%Y%m%d").to_string())),
+            self.backward_citations.to_string(),
+            self.backward_self_citations.to_string(),
+            self.forward_citations.to_string(),
+            self.forward_self_citations.to_string(),
+        ];
+        record.extend(self.forward_within.iter().map(|(_, count)| optional(count.map(|c| c.to_string()))));
+        record.push(optional(self.originality.map(|o| o.to_string())));
+        record.push(optional(self.generality.map(|g| g.to_string())));
+        record
+    }
+}
+
+/// Directed citation graph; only the citations and a few fields of each grant are kept
+pub struct CitationGraph {
+    level: Level,
+    nodes: BTreeMap<PatentNumber, Node>,
+    // citing to cited
+    backward: BTreeMap<PatentNumber, Vec<Cited>>,
+    // cited to citing
+    forward: HashMap<PatentNumber, Vec<PatentNumber>>,
+}
+
+impl CitationGraph {
+    /// Originality and generality are over cpc classes at `level`, usually subclass.
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            nodes: BTreeMap::new(),
+            backward: BTreeMap::new(),
+            forward: HashMap::new(),
+        }
+    }
+
+    /// Adds a grant and its citations; grants whose number doesn't parse, or that were
+    /// already added, are skipped.
+    pub fn add(&mut self, pg: &PatentGrant) {
+        let dg = &pg.us_bibliographic_data_grant;
+        let number = match dg.publication_reference.patent_number() {
+            Ok(number) if !self.nodes.contains_key(&number) => number,
+            _ => return,
+        };
+
+        let cpcs = dg.classifications_cpc.main_cpc.iter().chain(dg.classifications_cpc.further_cpc.iter());
+        self.nodes.insert(number, Node {
+            grant_date: dg.grant_date().ok(),
+            assignees: dg.assignees.iter()
+                .filter_map(|a| a.orgname.as_deref().or(a.addressbook.orgname.as_deref()))
+                .map(normalize_name)
+                .collect(),
+            classes: distinct(cpcs.map(|cpc| cpc.symbol()), self.level),
+        });
+
+        let cited: Vec<Cited> = cited_patents(dg).into_iter()
+            .filter(|(cited, _)| *cited != number)
+            .map(|(cited, citation)| Cited {
+                number: cited,
+                category: citation.category.clone(),
+                classes: distinct(citation.classification_cpc_text.iter().cloned(), self.level),
+            })
+            .collect();
+        for c in &cited {
+            self.forward.entry(c.number).or_default().push(number);
+        }
+        self.backward.insert(number, cited);
+    }
+
+    /// grants in the corpus
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// the patents a corpus grant cites
+    pub fn backward(&self, number: &PatentNumber) -> Vec<PatentNumber> {
+        self.backward.get(number)
+            .map(|cited| cited.iter().map(|c| c.number).collect())
+            .unwrap_or_default()
+    }
+
+    /// the corpus grants citing a patent
+    pub fn forward(&self, number: &PatentNumber) -> &[PatentNumber] {
+        self.forward.get(number).map(|citing| citing.as_slice()).unwrap_or(&[])
+    }
+
+    /// Do the two share an assignee; None unless both are in the corpus
+    pub fn is_self_citation(&self, citing: &PatentNumber, cited: &PatentNumber) -> Option<bool> {
+        let (a, b) = (self.nodes.get(citing)?, self.nodes.get(cited)?);
+        Some(a.assignees.intersection(&b.assignees).next().is_some())
+    }
+
+    /// Forward citations by grants dated within `years` of the patent's grant date; None if
+    /// the patent isn't in the corpus or has no grant date
+    pub fn forward_within(&self, number: &PatentNumber, years: u32) -> Option<usize> {
+        let end = add_years(self.nodes.get(number)?.grant_date?, years);
+
+        Some(self.forward(number).iter()
+            .filter(|citing| matches!(self.nodes[*citing].grant_date, Some(date) if date <= end))
+            .count())
+    }
+
+    fn cited_classes<'a>(&'a self, cited: &'a Cited) -> &'a [String] {
+        match self.nodes.get(&cited.number) {
+            Some(node) => &node.classes,
+            None => &cited.classes,
+        }
+    }
+
+    /// None if no cited patent has a class
+    pub fn originality(&self, number: &PatentNumber) -> Option<f64> {
+        let cited = self.backward.get(number)?;
+        concentration(cited.iter().map(|c| self.cited_classes(c)))
+    }
+
+    /// None if no citing grant has a class
+    pub fn generality(&self, number: &PatentNumber) -> Option<f64> {
+        concentration(self.forward(number).iter().map(|citing| self.nodes[citing].classes.as_slice()))
+    }
+
+    /// measures of every corpus grant, by patent number
+    pub fn measures(&self, windows: &[u32]) -> Vec<CitationMeasures> {
+        self.nodes.iter()
+            .map(|(number, node)| {
+                let is_self = |citing: &PatentNumber, cited: &PatentNumber| self.is_self_citation(citing, cited) == Some(true);
+                let backward = self.backward(number);
+                let forward = self.forward(number);
+
+                CitationMeasures {
+                    doc_number: number.padded(),
+                    grant_date: node.grant_date,
+                    backward_citations: backward.len(),
+                    backward_self_citations: backward.iter().filter(|cited| is_self(number, cited)).count(),
+                    forward_citations: forward.len(),
+                    forward_self_citations: forward.iter().filter(|citing| is_self(citing, number)).count(),
+                    forward_within: windows.iter().map(|years| (*years, self.forward_within(number, *years))).collect(),
+                    originality: self.originality(number),
+                    generality: self.generality(number),
+                }
+            })
+            .collect()
+    }
+
+    /// every citation of the corpus, by citing patent
+    pub fn edges(&self) -> Vec<CitationEdge<'_>> {
+        self.backward.iter()
+            .flat_map(|(citing, cited)| cited.iter().map(move |c| CitationEdge {
+                citing: citing.padded(),
+                cited: c.number.padded(),
+                category: c.category.as_deref(),
+                self_citation: self.is_self_citation(citing, &c.number),
+            }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DocumentId;
+    use crate::testing::TestGrant;
+
+    fn grant(doc_number: &str, date: &str, assignee: &str, cpc: &str, cites: &[&str]) -> PatentGrant {
+        let mut pg = TestGrant::new()
+            .with_doc_number(doc_number)
+            .with_date(date)
+            .with_assignee(assignee)
+            .with_cpc(&[cpc])
+            .build();
+        pg.us_bibliographic_data_grant.us_references_cited = cites.iter()
+            .map(|cited| UsCitation {
+                patcit: Some(DocumentId {
+                    country: "US".to_owned(),
+                    doc_number: cited.to_string(),
+                    kind: Some(if cited.len() == 11 { "A1" } else { "B2" }.to_owned()),
+                    ..Default::default()
+                }),
+                classification_cpc_text: Some("A61K 31/00".to_owned()),
+                ..Default::default()
+            })
+            .collect();
+        pg
+    }
+
+    #[test]
+    fn citation_measures() {
+        let mut graph = CitationGraph::new(Level::Subclass);
+        graph.add(&grant("09000001", "20150106", "Acme Corp.", "H04L", &["5241599", "20040123456"]));
+        graph.add(&grant("09500000", "20170103", "ACME Corporation", "H04L", &["9000001", "5241599"]));
+        graph.add(&grant("10000000", "20180605", "Other Inc", "G06F", &["US 9,000,001", "09500000"]));
+        graph.add(&grant("10500000", "20221206", "Other Inc", "G06F", &["09000001"]));
+
+        let first: PatentNumber = "9000001".parse().unwrap();
+        assert_eq!(graph.backward(&first), vec!["5241599".parse().unwrap()]);
+        assert_eq!(graph.forward(&first).len(), 3);
+        assert_eq!(graph.forward_within(&first, 3), Some(1));
+        assert_eq!(graph.forward_within(&first, 5), Some(2));
+        assert_eq!(graph.is_self_citation(&"9500000".parse().unwrap(), &first), Some(true));
+        // citing grants in H04L, G06F and G06F
+        assert!((graph.generality(&first).unwrap() - (1.0 - (1.0 / 9.0 + 4.0 / 9.0))).abs() < 1e-9);
+        // the cited patent outside the corpus is classified by its citation
+        assert_eq!(graph.originality(&first), Some(0.0));
+
+        let measures = graph.measures(&[3, 5]);
+        assert_eq!(measures[0].doc_number, "09000001");
+        assert_eq!(measures[0].forward_self_citations, 1);
+        assert_eq!(measures[0].record()[6..8], ["1".to_owned(), "2".to_owned()]);
+        assert_eq!(graph.edges().len(), 6);
+    }
+}
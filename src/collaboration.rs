@@ -0,0 +1,330 @@
+//! Collaboration networks: inventors, countries, cities or organizations linked by the grants
+//! they share.
+//!
+//! Each grant links every pair of its distinct actors, and adds one to each actor's node.
+//! With whole weighting every pair gets one; with fractional weighting each pair gets
+//! 1 / (n - 1), so each actor's links from one grant add up to one whatever the team size.
+//! Country and city networks are built from inventor addresses, so a country–country edge
+//! is international co-invention.
+//!
+//! Inventors are their names unless ids from `inventor` disambiguation are given, and
+//! organizations their normalized names unless ids from `assignee` harmonization are.
+//!
+//! Grants are counted in every time window their year falls in; windows of `length` years
+//! start every `step` years from `start`, so a step shorter than the length gives sliding
+//! windows.
+
+use chrono::Datelike;
+use serde::Deserialize;
+use snafu::ResultExt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::assignee::normalize_name;
+use crate::country::{CountryCode, CountryNormalizer, SuccessorPolicy};
+use crate::cube::YearOf;
+use crate::data::{Address, BibliographicDataGrant, PatentGrant};
+use crate::error::{Error, ReadTable};
+use crate::geocode::normalize;
+use crate::network::Graph;
+
+/// What the nodes are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Actor {
+    Inventor,
+    /// of inventors
+    Country,
+    /// of inventors
+    City,
+    /// assignee organizations
+    Organization,
+}
+
+impl FromStr for Actor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inventor" => Ok(Actor::Inventor),
+            "country" => Ok(Actor::Country),
+            "city" => Ok(Actor::City),
+            "organization" => Ok(Actor::Organization),
+            _ => Err(format!("unknown actor {:?}, expected one of: inventor, country, city, organization", s)),
+        }
+    }
+}
+
+/// How much a grant adds to an edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weighting {
+    /// one per grant
+    Whole,
+    /// 1 / (n - 1) for a grant with n actors
+    Fractional,
+}
+
+impl FromStr for Weighting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "whole" => Ok(Weighting::Whole),
+            "fractional" => Ok(Weighting::Fractional),
+            _ => Err(format!("unknown weighting {:?}, expected whole or fractional", s)),
+        }
+    }
+}
+
+/// Time windows of `length` years, starting every `step` years from `start`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Windows {
+    pub start: u16,
+    pub length: u16,
+    pub step: u16,
+}
+
+impl Windows {
+    /// first and last year of each window containing `year`, earliest first
+    pub fn containing(&self, year: u16) -> Vec<(u16, u16)> {
+        let mut windows = Vec::new();
+        if self.length == 0 || self.step == 0 {
+            return windows;
+        }
+
+        // windows running past the last year end on it
+        let mut first = self.start;
+        while first <= year {
+            let last = first.saturating_add(self.length - 1);
+            if year <= last {
+                windows.push((first, last));
+            }
+            first = match first.checked_add(self.step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        windows
+    }
+}
+
+#[derive(Deserialize)]
+struct InventorIdRecord {
+    doc_number: String,
+    sequence: String,
+    inventor_id: String,
+}
+
+#[derive(Deserialize)]
+struct OrganizationIdRecord {
+    raw_name: String,
+    org_id: String,
+}
+
+/// Streaming collaboration networks, one per time window
+pub struct Collaboration {
+    actor: Actor,
+    year_of: YearOf,
+    weighting: Weighting,
+    windows: Option<Windows>,
+    countries: CountryNormalizer,
+    // (doc number, sequence) to inventor id
+    inventor_ids: HashMap<(String, String), String>,
+    // raw assignee name to organization id
+    organization_ids: HashMap<String, String>,
+    graphs: BTreeMap<Option<(u16, u16)>, Graph>,
+    grants: u64,
+    skipped: u64,
+}
+
+impl Collaboration {
+    /// with whole weighting and one network over all years
+    pub fn new(actor: Actor, year_of: YearOf) -> Self {
+        Self {
+            actor,
+            year_of,
+            weighting: Weighting::Whole,
+            windows: None,
+            countries: CountryNormalizer::new(CountryCode::Alpha2, SuccessorPolicy::Successor),
+            inventor_ids: HashMap::new(),
+            organization_ids: HashMap::new(),
+            graphs: BTreeMap::new(),
+            grants: 0,
+            skipped: 0,
+        }
+    }
+
+    pub fn with_weighting(mut self, weighting: Weighting) -> Self {
+        self.weighting = weighting;
+        self
+    }
+
+    pub fn with_windows(mut self, windows: Windows) -> Self {
+        self.windows = Some(windows);
+        self
+    }
+
+    /// Countries are normalized to alpha-2 codes, dissolved states to their successors,
+    /// unless another normalizer is given.
+    pub fn with_normalizer(mut self, normalizer: CountryNormalizer) -> Self {
+        self.countries = normalizer;
+        self
+    }
+
+    /// csv as written by the inventors command: doc_number,sequence,inventor_id,...
+    pub fn with_inventor_ids<R: Read>(mut self, r: R) -> Result<Self, Error> {
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: InventorIdRecord = record.context(ReadTable)?;
+            self.inventor_ids.insert((record.doc_number, record.sequence), record.inventor_id);
+        }
+        Ok(self)
+    }
+
+    /// csv as written by the assignees command: raw_name,...,org_id,...
+    pub fn with_organization_ids<R: Read>(mut self, r: R) -> Result<Self, Error> {
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: OrganizationIdRecord = record.context(ReadTable)?;
+            self.organization_ids.insert(record.raw_name.trim().to_owned(), record.org_id);
+        }
+        Ok(self)
+    }
+
+    fn city(&self, address: &Address) -> Option<String> {
+        let country = self.countries.address_code(address)?;
+        let city = normalize(address.city.as_deref()?)?;
+
+        Some(match address.state.as_deref().and_then(normalize) {
+            Some(state) => format!("{}, {}, {}", city, state, country),
+            None => format!("{}, {}", city, country),
+        })
+    }
+
+    /// the grant's distinct actors, in document order
+    pub fn actors(&self, dg: &BibliographicDataGrant) -> Vec<String> {
+        let doc_number = &dg.publication_reference.doc_number;
+        let actors: Vec<String> = match self.actor {
+            Actor::Inventor => dg.inventors.iter()
+                .filter_map(|inventor| {
+                    let key = (doc_number.clone(), inventor.sequence.clone());
+                    if let Some(id) = self.inventor_ids.get(&key) {
+                        return Some(id.clone());
+                    }
+                    let ab = &inventor.addressbook;
+                    let name = [ab.last_name.as_deref(), ab.first_name.as_deref()].iter()
+                        .flatten()
+                        .map(|name| name.trim())
+                        .filter(|name| !name.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    if name.is_empty() { None } else { Some(name) }
+                })
+                .collect(),
+            Actor::Country => dg.inventors.iter()
+                .filter_map(|inventor| self.countries.address_code(&inventor.addressbook.address))
+                .map(|code| code.to_owned())
+                .collect(),
+            Actor::City => dg.inventors.iter()
+                .filter_map(|inventor| self.city(&inventor.addressbook.address))
+                .collect(),
+            Actor::Organization => dg.assignees.iter()
+                .filter_map(|a| a.orgname.as_deref().or(a.addressbook.orgname.as_deref()))
+                .map(|name| match self.organization_ids.get(name.trim()) {
+                    Some(id) => id.clone(),
+                    None => normalize_name(name),
+                })
+                .filter(|name| !name.is_empty())
+                .collect(),
+        };
+
+        let mut seen = BTreeSet::new();
+        actors.into_iter().filter(|actor| seen.insert(actor.clone())).collect()
+    }
+
+    /// Counts one grant. Grants without actors or a year (or outside every window) are only
+    /// counted in `skipped`.
+    pub fn add(&mut self, pg: &PatentGrant) {
+        self.grants += 1;
+        let dg = &pg.us_bibliographic_data_grant;
+
+        let actors = self.actors(dg);
+        let date = match self.year_of {
+            YearOf::Grant => dg.grant_date(),
+            YearOf::Application => dg.application_date(),
+        };
+        let windows: Vec<Option<(u16, u16)>> = match (self.windows, date) {
+            (None, _) => vec![None],
+            (Some(windows), Ok(date)) => windows.containing(date.year() as u16).into_iter().map(Some).collect(),
+            (Some(_), Err(_)) => Vec::new(),
+        };
+        if actors.is_empty() || windows.is_empty() {
+            self.skipped += 1;
+            return;
+        }
+
+        let weight = match self.weighting {
+            Weighting::Whole => 1.0,
+            Weighting::Fractional => 1.0 / (actors.len() as f64 - 1.0).max(1.0),
+        };
+        for window in windows {
+            let graph = self.graphs.entry(window).or_default();
+            for (i, a) in actors.iter().enumerate() {
+                graph.add_node(a, 1.0);
+                for b in &actors[i + 1..] {
+                    graph.add_edge(a, b, weight);
+                }
+            }
+        }
+    }
+
+    /// One network per window, by first year; one keyed None without windows. Nodes are
+    /// weighted by grants.
+    pub fn graphs(&self) -> &BTreeMap<Option<(u16, u16)>, Graph> {
+        &self.graphs
+    }
+
+    pub fn grants(&self) -> u64 {
+        self.grants
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestGrant;
+
+    fn grant(date: &str, countries: &[&str]) -> PatentGrant {
+        TestGrant::new().with_date(date).with_inventor_countries(countries).build()
+    }
+
+    #[test]
+    fn country_windows() {
+        let windows = Windows { start: 2000, length: 5, step: 5 };
+        assert_eq!(windows.containing(2004), vec![(2000, 2004)]);
+        assert_eq!(windows.containing(1999), vec![]);
+        let sliding = Windows { start: 2000, length: 3, step: 1 };
+        assert_eq!(sliding.containing(2003), vec![(2001, 2003), (2002, 2004), (2003, 2005)]);
+        let late = Windows { start: u16::MAX - 5, length: 10, step: 3 };
+        assert_eq!(late.containing(u16::MAX), vec![(u16::MAX - 5, u16::MAX), (u16::MAX - 2, u16::MAX)]);
+
+        let mut collaboration = Collaboration::new(Actor::Country, YearOf::Grant)
+            .with_weighting(Weighting::Fractional)
+            .with_windows(windows);
+        collaboration.add(&grant("20010102", &["US", "DE", "UK", "US"]));
+        collaboration.add(&grant("20030102", &["DE", "DD"]));
+        collaboration.add(&grant("20060102", &["US", "DE"]));
+        collaboration.add(&grant("19990102", &["US", "DE"]));
+
+        let graphs = collaboration.graphs();
+        assert_eq!(graphs.len(), 2);
+        let first = &graphs[&Some((2000, 2004))];
+        assert_eq!(first.edge("US", "DE"), Some(0.5));
+        assert_eq!(first.edge("GB", "US"), Some(0.5));
+        assert_eq!(first.nodes().collect::<Vec<_>>(), vec![("DE", 2.0), ("GB", 1.0), ("US", 1.0)]);
+        assert_eq!(graphs[&Some((2005, 2009))].edge("DE", "US"), Some(1.0));
+        assert_eq!(collaboration.skipped(), 1);
+    }
+}
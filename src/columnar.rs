@@ -0,0 +1,303 @@
+//! columnar output (parquet and arrow ipc) for patent grants.
+//!
+//! Only built with the `columnar` feature.
+//!
+//! One row per grant. Bibliographic fields are scalar utf8 columns; parties, cpc/ipc symbols,
+//! and the field of classification search are list columns (parties as lists of structs). The schema is
+//! defined once in `schema`, and `record_batch` fills it in the same column order.
+//!
+//! Weekly files are meant to be written as one batch each, so a parquet file covering many
+//! weeks has one row group per week.
+
+use arrow::array::{ArrayRef, ListArray, StringArray, StructArray};
+use arrow::buffer::OffsetBuffer;
+use arrow::datatypes::{DataType, Field, Fields, Schema, SchemaRef};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::data::{AddressBook, BibliographicDataGrant, PatentGrant};
+use crate::error::Error;
+
+const APPLICANT_FIELDS: [&str; 9] = [
+    "sequence", "app_type", "orgname", "first_name", "last_name", "city", "state", "country", "residence",
+];
+const INVENTOR_FIELDS: [&str; 7] = [
+    "sequence", "orgname", "first_name", "last_name", "city", "state", "country",
+];
+const ASSIGNEE_FIELDS: [&str; 5] = [
+    "orgname", "role", "city", "state", "country",
+];
+
+/// Scalar columns, in order. Only `kind` and `classification_national_further` are nullable.
+const SCALAR_FIELDS: [(&str, bool); 15] = [
+    ("doc_number", false),
+    ("kind", true),
+    ("date", false),
+    ("country", false),
+    ("application_doc_number", false),
+    ("application_date", false),
+    ("us_application_series_code", false),
+    ("us_term_of_grant", false),
+    ("invention_title", false),
+    ("number_of_claims", false),
+    ("us_exemplary_claim", false),
+    ("classification_locarno_edition", false),
+    ("classification_locarno", false),
+    ("classification_national", false),
+    ("classification_national_further", true),
+];
+
+/// arrow schema shared by the parquet and ipc writers
+pub fn schema() -> SchemaRef {
+    let mut fields: Vec<Field> = SCALAR_FIELDS.iter()
+        .map(|(name, nullable)| Field::new(*name, DataType::Utf8, *nullable))
+        .collect();
+
+    fields.push(Field::new("classifications_cpc", list_of(DataType::Utf8), false));
+    fields.push(Field::new("classifications_ipc", list_of(DataType::Utf8), false));
+    fields.push(Field::new("classification_national_search", list_of(DataType::Utf8), false));
+    fields.push(Field::new("classification_cpc_search", list_of(DataType::Utf8), false));
+    fields.push(Field::new("us_applicants", list_of(DataType::Struct(utf8_fields(&APPLICANT_FIELDS))), false));
+    fields.push(Field::new("inventors", list_of(DataType::Struct(utf8_fields(&INVENTOR_FIELDS))), false));
+    fields.push(Field::new("assignees", list_of(DataType::Struct(utf8_fields(&ASSIGNEE_FIELDS))), false));
+
+    Arc::new(Schema::new(fields))
+}
+
+/// one record batch from a slice of grants (usually one weekly file)
+pub fn record_batch(grants: &[PatentGrant]) -> Result<RecordBatch, Error> {
+    let biblios: Vec<&BibliographicDataGrant> = grants.iter()
+        .map(|pg| &pg.us_bibliographic_data_grant)
+        .collect();
+
+    let scalar = |f: &dyn Fn(&BibliographicDataGrant) -> Option<&str>| -> ArrayRef {
+        Arc::new(biblios.iter().map(|dg| f(dg)).collect::<StringArray>())
+    };
+
+    let columns: Vec<ArrayRef> = vec![
+        scalar(&|dg| Some(&dg.publication_reference.doc_number)),
+        scalar(&|dg| dg.publication_reference.kind.as_deref()),
+        scalar(&|dg| Some(dg.publication_reference.date.raw())),
+        scalar(&|dg| Some(&dg.publication_reference.country)),
+        scalar(&|dg| Some(&dg.application_reference.doc_number)),
+        scalar(&|dg| Some(dg.application_reference.date.raw())),
+        scalar(&|dg| Some(&dg.us_application_series_code)),
+        scalar(&|dg| Some(&dg.us_term_of_grant)),
+        scalar(&|dg| Some(&dg.invention_title)),
+        scalar(&|dg| Some(&dg.number_of_claims)),
+        scalar(&|dg| Some(&dg.us_exemplary_claim)),
+        scalar(&|dg| Some(&dg.classification_locarno.edition)),
+        scalar(&|dg| Some(&dg.classification_locarno.main_classification)),
+        scalar(&|dg| Some(&dg.classification_national.main_classification)),
+        scalar(&|dg| dg.classification_national.further_classification.as_deref()),
+        // main cpc first
+        owned_string_list_column(biblios.iter()
+            .map(|dg| dg.classifications_cpc.main_cpc.iter()
+                .chain(dg.classifications_cpc.further_cpc.iter())
+                .map(|cpc| cpc.symbol())
+                .collect())
+            .collect()),
+        owned_string_list_column(biblios.iter()
+            .map(|dg| dg.classifications_ipcr.iter()
+                .map(|ipcr| ipcr.symbol())
+                .collect())
+            .collect()),
+        string_list_column(biblios.iter()
+            .map(|dg| dg.us_field_of_classification_search.classification_nationals.iter()
+                .map(|cn| cn.main_classification.as_str())
+                .collect())
+            .collect()),
+        string_list_column(biblios.iter()
+            .map(|dg| dg.us_field_of_classification_search.classification_cpc_texts.iter()
+                .map(|s| s.as_str())
+                .collect())
+            .collect()),
+        struct_list_column(&APPLICANT_FIELDS, biblios.iter()
+            .map(|dg| dg.us_applicants.iter()
+                .map(|a| {
+                    let mut row = vec![Some(a.sequence.as_str()), Some(a.app_type.as_str())];
+                    row.extend_from_slice(&addressbook_row(&a.addressbook));
+                    row.push(a.residence.as_deref());
+                    row
+                })
+                .collect())
+            .collect()),
+        struct_list_column(&INVENTOR_FIELDS, biblios.iter()
+            .map(|dg| dg.inventors.iter()
+                .map(|i| {
+                    let mut row = vec![Some(i.sequence.as_str())];
+                    row.extend_from_slice(&addressbook_row(&i.addressbook));
+                    row
+                })
+                .collect())
+            .collect()),
+        struct_list_column(&ASSIGNEE_FIELDS, biblios.iter()
+            .map(|dg| dg.assignees.iter()
+                .map(|a| {
+                    let ab = &a.addressbook;
+                    vec![
+                        a.orgname.as_deref().or(ab.orgname.as_deref()),
+                        a.role.as_deref().or(ab.role.as_deref()),
+                        ab.address.city.as_deref(),
+                        ab.address.state.as_deref(),
+                        ab.address.country.as_deref(),
+                    ]
+                })
+                .collect())
+            .collect()),
+    ];
+
+    RecordBatch::try_new(schema(), columns)
+        .map_err(|err| Error::Columnar { src: err.to_string() })
+}
+
+/// Parquet output. Each call to `write_row_group` becomes exactly one row group.
+pub struct ParquetWriter<W: Write + Send> {
+    wtr: ArrowWriter<W>,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn new(w: W) -> Result<Self, Error> {
+        let wtr = ArrowWriter::try_new(w, schema(), None)
+            .map_err(|err| Error::Columnar { src: err.to_string() })?;
+
+        Ok(Self { wtr })
+    }
+
+    pub fn write_row_group(&mut self, grants: &[PatentGrant]) -> Result<(), Error> {
+        let batch = record_batch(grants)?;
+
+        self.wtr.write(&batch)
+            .and_then(|_| self.wtr.flush())
+            .map_err(|err| Error::Columnar { src: err.to_string() })
+    }
+
+    /// writes the footer; the file is not readable until this is called.
+    pub fn close(self) -> Result<(), Error> {
+        self.wtr.close()
+            .map(|_| ())
+            .map_err(|err| Error::Columnar { src: err.to_string() })
+    }
+}
+
+/// Arrow IPC file output. Each call to `write_batch` becomes one record batch.
+pub struct IpcWriter<W: Write> {
+    wtr: FileWriter<W>,
+}
+
+impl<W: Write> IpcWriter<W> {
+    pub fn new(w: W) -> Result<Self, Error> {
+        let wtr = FileWriter::try_new(w, &schema())
+            .map_err(|err| Error::Columnar { src: err.to_string() })?;
+
+        Ok(Self { wtr })
+    }
+
+    pub fn write_batch(&mut self, grants: &[PatentGrant]) -> Result<(), Error> {
+        let batch = record_batch(grants)?;
+
+        self.wtr.write(&batch)
+            .map_err(|err| Error::Columnar { src: err.to_string() })
+    }
+
+    /// writes the footer; the file is not readable until this is called.
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.wtr.finish()
+            .map_err(|err| Error::Columnar { src: err.to_string() })
+    }
+}
+
+// orgname, first_name, last_name, city, state, country
+fn addressbook_row(ab: &AddressBook) -> [Option<&str>; 6] {
+    [
+        ab.orgname.as_deref(),
+        ab.first_name.as_deref(),
+        ab.last_name.as_deref(),
+        ab.address.city.as_deref(),
+        ab.address.state.as_deref(),
+        ab.address.country.as_deref(),
+    ]
+}
+
+fn utf8_fields(names: &[&str]) -> Fields {
+    names.iter()
+        .map(|name| Field::new(*name, DataType::Utf8, true))
+        .collect()
+}
+
+fn list_of(item: DataType) -> DataType {
+    DataType::List(Arc::new(Field::new("item", item, true)))
+}
+
+fn string_list_column(lists: Vec<Vec<&str>>) -> ArrayRef {
+    let offsets = OffsetBuffer::from_lengths(lists.iter().map(|l| l.len()));
+    let values: StringArray = lists.into_iter().flatten().map(Some).collect();
+
+    Arc::new(ListArray::new(
+        Arc::new(Field::new("item", DataType::Utf8, true)),
+        offsets,
+        Arc::new(values),
+        None,
+    ))
+}
+
+fn owned_string_list_column(lists: Vec<Vec<String>>) -> ArrayRef {
+    string_list_column(lists.iter()
+        .map(|l| l.iter().map(|s| s.as_str()).collect())
+        .collect())
+}
+
+/// `rows` is, per grant, a list of rows with one value per name in `names`
+fn struct_list_column(names: &[&str], rows: Vec<Vec<Vec<Option<&str>>>>) -> ArrayRef {
+    let fields = utf8_fields(names);
+    let offsets = OffsetBuffer::from_lengths(rows.iter().map(|r| r.len()));
+    let rows: Vec<Vec<Option<&str>>> = rows.into_iter().flatten().collect();
+
+    let children: Vec<ArrayRef> = (0..names.len())
+        .map(|i| Arc::new(rows.iter().map(|row| row[i]).collect::<StringArray>()) as ArrayRef)
+        .collect();
+    let values = StructArray::new(fields.clone(), children, None);
+
+    Arc::new(ListArray::new(
+        Arc::new(Field::new("item", DataType::Struct(fields), true)),
+        offsets,
+        Arc::new(values),
+        None,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Address, Inventor};
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    #[test]
+    fn parquet_row_group_per_batch() {
+        let mut grant = PatentGrant::default();
+        grant.us_bibliographic_data_grant.publication_reference.doc_number = "10123456".to_owned();
+        grant.us_bibliographic_data_grant.inventors.push(Inventor {
+            addressbook: AddressBook {
+                address: Address { country: Some("DE".to_owned()), ..Address::default() },
+                ..AddressBook::default()
+            },
+            ..Inventor::default()
+        });
+        let week = vec![grant.clone(), grant];
+
+        let path = std::env::temp_dir().join("uspto_parquet_row_group_per_batch.parquet");
+        let mut wtr = ParquetWriter::new(std::fs::File::create(&path).unwrap()).unwrap();
+        wtr.write_row_group(&week).unwrap();
+        wtr.write_row_group(&week[..1]).unwrap();
+        wtr.close().unwrap();
+
+        let rdr = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let meta = rdr.metadata();
+        assert_eq!(meta.num_row_groups(), 2);
+        assert_eq!(meta.row_group(0).num_rows(), 2);
+        assert_eq!(meta.row_group(1).num_rows(), 1);
+    }
+}
@@ -0,0 +1,531 @@
+//! Economic complexity measures over the country × technology matrix.
+//!
+//! Takes one year of a `Cube` (or all years pooled) as a `CountMatrix` and computes revealed comparative advantage,
+//! the binary Mcp matrix, diversity and ubiquity, the method of reflections, eigenvector ECI and
+//! PCI, and proximity and density over the technology space (Hidalgo & Hausmann 2009,
+//! Hidalgo et al. 2007). Every result comes out as rows of a serializable table tagged with the
+//! year.
+//!
+//! Matrices are dense, so this is meant for subclass-level technologies (hundreds of codes),
+//! not cpc groups.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cube::Cube;
+
+/// power iteration stops after this many steps if it hasn't converged
+const MAX_ITERATIONS: usize = 10_000;
+const TOLERANCE: f64 = 1e-12;
+
+/// Country × technology counts, dense and row-major by country
+#[derive(Debug, Clone, PartialEq)]
+pub struct CountMatrix {
+    /// None when pooled over all years
+    pub year: Option<u16>,
+    pub countries: Vec<String>,
+    pub technologies: Vec<String>,
+    pub counts: Vec<f64>,
+}
+
+impl CountMatrix {
+    /// One year's cells of the cube, or with `year` None, all years summed.
+    /// Countries and technologies are sorted.
+    pub fn from_cube(cube: &Cube, year: Option<u16>) -> Self {
+        let cells: Vec<_> = cube.cells()
+            .filter(|cell| year.is_none() || year == Some(cell.year))
+            .collect();
+
+        let countries: Vec<String> = cells.iter()
+            .map(|cell| cell.country)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|c| c.to_owned())
+            .collect();
+        let technologies: Vec<String> = cells.iter()
+            .map(|cell| cell.technology)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|t| t.to_owned())
+            .collect();
+
+        let country_idx: BTreeMap<&str, usize> = countries.iter().enumerate().map(|(i, c)| (c.as_str(), i)).collect();
+        let technology_idx: BTreeMap<&str, usize> = technologies.iter().enumerate().map(|(i, t)| (t.as_str(), i)).collect();
+
+        let mut counts = vec![0.0; countries.len() * technologies.len()];
+        for cell in &cells {
+            counts[country_idx[cell.country] * technologies.len() + technology_idx[cell.technology]] += cell.count;
+        }
+
+        Self { year, countries, technologies, counts }
+    }
+
+    fn get(&self, c: usize, p: usize) -> f64 {
+        self.counts[c * self.technologies.len() + p]
+    }
+}
+
+/// one row per country and technology
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RcaRow<'a> {
+    pub year: Option<u16>,
+    pub country: &'a str,
+    pub technology: &'a str,
+    pub count: f64,
+    pub rca: f64,
+    /// 1 if rca is at or above the threshold
+    pub mcp: u8,
+    pub density: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CountryRow<'a> {
+    pub year: Option<u16>,
+    pub country: &'a str,
+    pub diversity: u32,
+    /// standardized; empty when the country has no technology with mcp = 1
+    pub eci: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TechnologyRow<'a> {
+    pub year: Option<u16>,
+    pub technology: &'a str,
+    pub ubiquity: u32,
+    /// standardized; empty when no country has mcp = 1 for the technology
+    pub pci: Option<f64>,
+}
+
+/// one row per pair of technologies with nonzero proximity, `technology_a < technology_b`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ProximityRow<'a> {
+    pub year: Option<u16>,
+    pub technology_a: &'a str,
+    pub technology_b: &'a str,
+    pub proximity: f64,
+}
+
+/// k_{c,n} from the method of reflections
+#[derive(Debug, PartialEq, Serialize)]
+pub struct CountryReflectionRow<'a> {
+    pub year: Option<u16>,
+    pub country: &'a str,
+    pub iteration: usize,
+    pub value: f64,
+}
+
+/// k_{p,n} from the method of reflections
+#[derive(Debug, PartialEq, Serialize)]
+pub struct TechnologyReflectionRow<'a> {
+    pub year: Option<u16>,
+    pub technology: &'a str,
+    pub iteration: usize,
+    pub value: f64,
+}
+
+/// All measures for one year, or all years pooled
+pub struct Complexity<'a> {
+    matrix: &'a CountMatrix,
+    rca: Vec<f64>,
+    mcp: Vec<bool>,
+    diversity: Vec<u32>,
+    ubiquity: Vec<u32>,
+    eci: Vec<Option<f64>>,
+    pci: Vec<Option<f64>>,
+    proximity: Vec<f64>,
+    density: Vec<f64>,
+}
+
+impl<'a> Complexity<'a> {
+    /// `rca_threshold` is usually 1.0
+    pub fn new(matrix: &'a CountMatrix, rca_threshold: f64) -> Self {
+        let n_c = matrix.countries.len();
+        let n_p = matrix.technologies.len();
+
+        let rca = rca(matrix);
+        let mcp: Vec<bool> = rca.iter().map(|r| *r >= rca_threshold).collect();
+        let m = |c: usize, p: usize| if mcp[c * n_p + p] { 1.0 } else { 0.0 };
+
+        let diversity: Vec<u32> = (0..n_c).map(|c| (0..n_p).filter(|&p| mcp[c * n_p + p]).count() as u32).collect();
+        let ubiquity: Vec<u32> = (0..n_p).map(|p| (0..n_c).filter(|&c| mcp[c * n_p + p]).count() as u32).collect();
+
+        let (eci, pci) = eci_pci(n_c, n_p, &m, &diversity, &ubiquity);
+
+        // φ_pp' = Σ_c M_cp M_cp' / max(k_p, k_p')
+        let mut proximity = vec![0.0; n_p * n_p];
+        for p in 0..n_p {
+            for q in 0..n_p {
+                let k = ubiquity[p].max(ubiquity[q]);
+                if k > 0 {
+                    let shared: f64 = (0..n_c).map(|c| m(c, p) * m(c, q)).sum();
+                    proximity[p * n_p + q] = shared / f64::from(k);
+                }
+            }
+        }
+
+        // ω_cp = Σ_p' M_cp' φ_pp' / Σ_p' φ_pp'
+        let mut density = vec![0.0; n_c * n_p];
+        for p in 0..n_p {
+            let total: f64 = (0..n_p).map(|q| proximity[p * n_p + q]).sum();
+            if total == 0.0 {
+                continue;
+            }
+            for c in 0..n_c {
+                let near: f64 = (0..n_p).map(|q| m(c, q) * proximity[p * n_p + q]).sum();
+                density[c * n_p + p] = near / total;
+            }
+        }
+
+        Self { matrix, rca, mcp, diversity, ubiquity, eci, pci, proximity, density }
+    }
+
+    pub fn rca_rows(&self) -> Vec<RcaRow<'_>> {
+        let n_p = self.matrix.technologies.len();
+        let mut rows = Vec::with_capacity(self.rca.len());
+
+        for (c, country) in self.matrix.countries.iter().enumerate() {
+            for (p, technology) in self.matrix.technologies.iter().enumerate() {
+                rows.push(RcaRow {
+                    year: self.matrix.year,
+                    country,
+                    technology,
+                    count: self.matrix.get(c, p),
+                    rca: self.rca[c * n_p + p],
+                    mcp: self.mcp[c * n_p + p] as u8,
+                    density: self.density[c * n_p + p],
+                });
+            }
+        }
+        rows
+    }
+
+    pub fn country_rows(&self) -> Vec<CountryRow<'_>> {
+        self.matrix.countries.iter()
+            .enumerate()
+            .map(|(c, country)| CountryRow {
+                year: self.matrix.year,
+                country,
+                diversity: self.diversity[c],
+                eci: self.eci[c],
+            })
+            .collect()
+    }
+
+    pub fn technology_rows(&self) -> Vec<TechnologyRow<'_>> {
+        self.matrix.technologies.iter()
+            .enumerate()
+            .map(|(p, technology)| TechnologyRow {
+                year: self.matrix.year,
+                technology,
+                ubiquity: self.ubiquity[p],
+                pci: self.pci[p],
+            })
+            .collect()
+    }
+
+    pub fn proximity_rows(&self) -> Vec<ProximityRow<'_>> {
+        let technologies = &self.matrix.technologies;
+        let n_p = technologies.len();
+        let mut rows = Vec::new();
+
+        for p in 0..n_p {
+            for q in (p + 1)..n_p {
+                let proximity = self.proximity[p * n_p + q];
+                if proximity > 0.0 {
+                    rows.push(ProximityRow {
+                        year: self.matrix.year,
+                        technology_a: &technologies[p],
+                        technology_b: &technologies[q],
+                        proximity,
+                    });
+                }
+            }
+        }
+        rows
+    }
+
+    /// Proximity between two technologies, if both are in the matrix
+    pub fn proximity(&self, technology_a: &str, technology_b: &str) -> Option<f64> {
+        let technologies = &self.matrix.technologies;
+        let p = technologies.iter().position(|t| t == technology_a)?;
+        let q = technologies.iter().position(|t| t == technology_b)?;
+
+        Some(self.proximity[p * technologies.len() + q])
+    }
+
+    /// Method of reflections, iterations 0 (diversity and ubiquity) through `iterations`.
+    ///
+    /// Countries and technologies with no mcp = 1 are left out, since every reflection
+    /// divides by diversity or ubiquity.
+    pub fn reflections(&self, iterations: usize) -> (Vec<CountryReflectionRow<'_>>, Vec<TechnologyReflectionRow<'_>>) {
+        let n_c = self.matrix.countries.len();
+        let n_p = self.matrix.technologies.len();
+        let mcp = |c: usize, p: usize| self.mcp[c * n_p + p];
+
+        let mut kc: Vec<f64> = self.diversity.iter().map(|&k| f64::from(k)).collect();
+        let mut kp: Vec<f64> = self.ubiquity.iter().map(|&k| f64::from(k)).collect();
+        let mut country_rows = Vec::new();
+        let mut technology_rows = Vec::new();
+
+        for iteration in 0..=iterations {
+            if iteration > 0 {
+                // zero-degree nodes stay at zero and drop out of the sums, or their 0/0 would
+                // spread to every neighbour in the next step
+                let next_kc: Vec<f64> = (0..n_c)
+                    .map(|c| match self.diversity[c] {
+                        0 => 0.0,
+                        k => (0..n_p).filter(|&p| mcp(c, p)).map(|p| kp[p]).sum::<f64>() / f64::from(k),
+                    })
+                    .collect();
+                let next_kp: Vec<f64> = (0..n_p)
+                    .map(|p| match self.ubiquity[p] {
+                        0 => 0.0,
+                        k => (0..n_c).filter(|&c| mcp(c, p)).map(|c| kc[c]).sum::<f64>() / f64::from(k),
+                    })
+                    .collect();
+                kc = next_kc;
+                kp = next_kp;
+            }
+
+            for (c, country) in self.matrix.countries.iter().enumerate() {
+                if self.diversity[c] > 0 {
+                    country_rows.push(CountryReflectionRow { year: self.matrix.year, country, iteration, value: kc[c] });
+                }
+            }
+            for (p, technology) in self.matrix.technologies.iter().enumerate() {
+                if self.ubiquity[p] > 0 {
+                    technology_rows.push(TechnologyReflectionRow { year: self.matrix.year, technology, iteration, value: kp[p] });
+                }
+            }
+        }
+
+        (country_rows, technology_rows)
+    }
+}
+
+/// RCA_cp = (X_cp / X_c) / (X_p / X); zero where the country or technology has no counts
+fn rca(matrix: &CountMatrix) -> Vec<f64> {
+    let n_c = matrix.countries.len();
+    let n_p = matrix.technologies.len();
+
+    let x_c: Vec<f64> = (0..n_c).map(|c| (0..n_p).map(|p| matrix.get(c, p)).sum()).collect();
+    let x_p: Vec<f64> = (0..n_p).map(|p| (0..n_c).map(|c| matrix.get(c, p)).sum()).collect();
+    let x: f64 = x_c.iter().sum();
+
+    let mut rca = vec![0.0; n_c * n_p];
+    for c in 0..n_c {
+        for p in 0..n_p {
+            if x_c[c] > 0.0 && x_p[p] > 0.0 {
+                rca[c * n_p + p] = (matrix.get(c, p) / x_c[c]) / (x_p[p] / x);
+            }
+        }
+    }
+    rca
+}
+
+/// ECI and PCI over the countries and technologies with nonzero diversity and ubiquity.
+///
+/// ECI is the eigenvector of the second largest eigenvalue of M̃_cc' = Σ_p M_cp M_c'p / (k_c k_p).
+/// M̃ is similar to the symmetric positive semidefinite S = K_c^½ M̃ K_c^-½, whose top
+/// eigenvector is √k_c with eigenvalue 1, so power iteration on S with that vector projected
+/// out converges to the one we want. PCI is the same on the technology side.
+fn eci_pci<F>(n_c: usize, n_p: usize, m: &F, diversity: &[u32], ubiquity: &[u32]) -> (Vec<Option<f64>>, Vec<Option<f64>>)
+    where F: Fn(usize, usize) -> f64,
+{
+    let cs: Vec<usize> = (0..n_c).filter(|&c| diversity[c] > 0).collect();
+    let ps: Vec<usize> = (0..n_p).filter(|&p| ubiquity[p] > 0).collect();
+    let kc: Vec<f64> = cs.iter().map(|&c| f64::from(diversity[c])).collect();
+    let kp: Vec<f64> = ps.iter().map(|&p| f64::from(ubiquity[p])).collect();
+
+    // S_cc' = Σ_p M_cp M_c'p / (√(k_c k_c') k_p)
+    let s_c = symmetric(cs.len(), &kc, |i, j| {
+        ps.iter().zip(&kp).map(|(&p, k)| m(cs[i], p) * m(cs[j], p) / k).sum()
+    });
+    let s_p = symmetric(ps.len(), &kp, |i, j| {
+        cs.iter().zip(&kc).map(|(&c, k)| m(c, ps[i]) * m(c, ps[j]) / k).sum()
+    });
+
+    let eci_sub = second_eigenvector(&s_c, &kc).map(|v| {
+        let mut eci = standardize(&v.iter().zip(&kc).map(|(v, k)| v / k.sqrt()).collect::<Vec<_>>());
+        // more diverse countries have higher eci
+        if covariance(&eci, &kc) < 0.0 {
+            eci.iter_mut().for_each(|e| *e = -*e);
+        }
+        eci
+    });
+    let pci_sub = second_eigenvector(&s_p, &kp).map(|v| {
+        let mut pci = standardize(&v.iter().zip(&kp).map(|(v, k)| v / k.sqrt()).collect::<Vec<_>>());
+        // sign agrees with the mean eci of the countries specialized in each technology,
+        // or failing that, less ubiquitous technologies have higher pci
+        let reference: Vec<f64> = match eci_sub {
+            Some(ref eci) => ps.iter().zip(&kp)
+                .map(|(&p, k)| cs.iter().zip(eci).map(|(&c, e)| m(c, p) * e).sum::<f64>() / k)
+                .collect(),
+            None => kp.iter().map(|k| -k).collect(),
+        };
+        if covariance(&pci, &reference) < 0.0 {
+            pci.iter_mut().for_each(|e| *e = -*e);
+        }
+        pci
+    });
+
+    let mut eci = vec![None; n_c];
+    if let Some(values) = eci_sub {
+        for (&c, value) in cs.iter().zip(values) {
+            eci[c] = Some(value);
+        }
+    }
+    let mut pci = vec![None; n_p];
+    if let Some(values) = pci_sub {
+        for (&p, value) in ps.iter().zip(values) {
+            pci[p] = Some(value);
+        }
+    }
+
+    (eci, pci)
+}
+
+/// n × n matrix of `f(i, j) / √(k_i k_j)`
+fn symmetric<F: Fn(usize, usize) -> f64>(n: usize, k: &[f64], f: F) -> Vec<f64> {
+    let mut s = vec![0.0; n * n];
+    for i in 0..n {
+        for j in i..n {
+            let v = f(i, j) / (k[i] * k[j]).sqrt();
+            s[i * n + j] = v;
+            s[j * n + i] = v;
+        }
+    }
+    s
+}
+
+/// Second eigenvector of `s`, whose top eigenvector is known to be √k.
+/// None if there are fewer than two rows or `s` has rank one.
+fn second_eigenvector(s: &[f64], k: &[f64]) -> Option<Vec<f64>> {
+    let n = k.len();
+    if n < 2 {
+        return None;
+    }
+
+    let top = normalize(k.iter().map(|k| k.sqrt()).collect())?;
+    let deflate = |v: &mut Vec<f64>| {
+        let dot: f64 = v.iter().zip(&top).map(|(a, b)| a * b).sum();
+        v.iter_mut().zip(&top).for_each(|(a, b)| *a -= dot * b);
+    };
+
+    // any start that isn't parallel to the top eigenvector
+    let mut v: Vec<f64> = (0..n).map(|i| (i + 1) as f64).collect();
+    deflate(&mut v);
+    let mut v = normalize(v)?;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut w: Vec<f64> = (0..n)
+            .map(|i| (0..n).map(|j| s[i * n + j] * v[j]).sum())
+            .collect();
+        deflate(&mut w);
+        let w = normalize(w)?;
+
+        let change: f64 = w.iter().zip(&v).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+        v = w;
+        if change < TOLERANCE {
+            break;
+        }
+    }
+
+    Some(v)
+}
+
+fn normalize(v: Vec<f64>) -> Option<Vec<f64>> {
+    let norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm < TOLERANCE {
+        return None;
+    }
+    Some(v.into_iter().map(|x| x / norm).collect())
+}
+
+fn mean(v: &[f64]) -> f64 {
+    v.iter().sum::<f64>() / v.len() as f64
+}
+
+/// (v - mean) / standard deviation
+fn standardize(v: &[f64]) -> Vec<f64> {
+    let mean = mean(v);
+    let sd = (v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / v.len() as f64).sqrt();
+    v.iter().map(|x| (x - mean) / sd).collect()
+}
+
+/// unnormalized, since only its sign is used
+fn covariance(a: &[f64], b: &[f64]) -> f64 {
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    a.iter().zip(b).map(|(x, y)| (x - mean_a) * (y - mean_b)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round(x: f64) -> f64 {
+        (x * 1000.0).round() / 1000.0
+    }
+
+    #[test]
+    fn nested_matrix() {
+        // with a threshold of 0.5, mcp is nested: A has all three, B the first two, C only the first
+        let matrix = CountMatrix {
+            year: Some(2019),
+            countries: vec!["A".to_owned(), "B".to_owned(), "C".to_owned()],
+            technologies: vec!["P1".to_owned(), "P2".to_owned(), "P3".to_owned()],
+            counts: vec![
+                10.0, 10.0, 10.0,
+                10.0, 10.0, 0.0,
+                10.0, 0.0, 0.0,
+            ],
+        };
+        let complexity = Complexity::new(&matrix, 0.5);
+
+        let rca: Vec<f64> = complexity.rca_rows().iter().map(|r| round(r.rca)).collect();
+        assert_eq!(rca, vec![0.667, 1.0, 2.0, 1.0, 1.5, 0.0, 2.0, 0.0, 0.0]);
+
+        let countries = complexity.country_rows();
+        let diversity: Vec<u32> = countries.iter().map(|r| r.diversity).collect();
+        assert_eq!(diversity, vec![3, 2, 1]);
+        let eci: Vec<f64> = countries.iter().map(|r| r.eci.unwrap()).collect();
+        assert!(eci[0] > eci[1] && eci[1] > eci[2]);
+        assert!(round(mean(&eci)).abs() < 1e-9);
+
+        let pci: Vec<f64> = complexity.technology_rows().iter().map(|r| r.pci.unwrap()).collect();
+        assert!(pci[2] > pci[1] && pci[1] > pci[0]);
+
+        // P1 and P2 share two countries, max ubiquity 3
+        assert_eq!(round(complexity.proximity("P1", "P2").unwrap()), 0.667);
+        assert_eq!(complexity.proximity_rows().len(), 3);
+
+        let (kc, _) = complexity.reflections(1);
+        let kc1: Vec<f64> = kc.iter().filter(|r| r.iteration == 1).map(|r| round(r.value)).collect();
+        // A: (3 + 2 + 1) / 3, B: (3 + 2) / 2, C: 3 / 1
+        assert_eq!(kc1, vec![2.0, 2.5, 3.0]);
+    }
+
+    #[test]
+    fn zero_diversity_country() {
+        // C is spread evenly, so no rca reaches 1.5
+        let matrix = CountMatrix {
+            year: Some(2019),
+            countries: vec!["A".to_owned(), "B".to_owned(), "C".to_owned(), "D".to_owned()],
+            technologies: vec!["P1".to_owned(), "P2".to_owned(), "P3".to_owned()],
+            counts: vec![
+                10.0, 1.0, 1.0,
+                1.0, 10.0, 1.0,
+                1.0, 1.0, 1.0,
+                1.0, 1.0, 10.0,
+            ],
+        };
+        let complexity = Complexity::new(&matrix, 1.5);
+        assert_eq!(complexity.country_rows()[2].diversity, 0);
+
+        let (kc, kp) = complexity.reflections(3);
+        assert!(kc.iter().all(|r| r.value.is_finite()));
+        assert!(kp.iter().all(|r| r.value.is_finite()));
+        assert!(kc.iter().all(|r| r.country != "C"));
+        assert_eq!(kp.iter().filter(|r| r.iteration == 3).count(), 3);
+    }
+}
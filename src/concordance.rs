@@ -0,0 +1,443 @@
+//! Concordances between classifications, for a technology dimension that covers 1976 to now.
+//!
+//! - IPC to the 35 WIPO technology fields (Schmoch 2008, as revised by WIPO), bundled.
+//! - CPC to IPC, by rule: CPC shares IPC's subclasses and main groups, except for the Y section
+//!   and the 2000-series indexing groups, which have no IPC counterpart.
+//! - USPC to CPC: each USPC class to the CPC subclass most of it went to, bundled; subclass
+//!   level from the USPTO's published concordance, loaded, since that's far too large to bundle.
+//!
+//! `derive` applies them in that order of preference, so a grant with only a USPC
+//! classification still gets CPC, IPC, and technology fields.
+
+use serde::Deserialize;
+use snafu::ResultExt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+
+use lazy_static::lazy_static;
+
+use crate::data::BibliographicDataGrant;
+use crate::error::{Error, ReadTable};
+use crate::uspc::UspcCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TechnologyField {
+    /// 1 to 35
+    pub id: u8,
+    pub name: &'static str,
+    pub sector: &'static str,
+}
+
+const ELECTRICAL: &str = "Electrical engineering";
+const INSTRUMENTS: &str = "Instruments";
+const CHEMISTRY: &str = "Chemistry";
+const MECHANICAL: &str = "Mechanical engineering";
+const OTHER: &str = "Other fields";
+
+pub const FIELDS: [TechnologyField; 35] = [
+    TechnologyField { id: 1, name: "Electrical machinery, apparatus, energy", sector: ELECTRICAL },
+    TechnologyField { id: 2, name: "Audio-visual technology", sector: ELECTRICAL },
+    TechnologyField { id: 3, name: "Telecommunications", sector: ELECTRICAL },
+    TechnologyField { id: 4, name: "Digital communication", sector: ELECTRICAL },
+    TechnologyField { id: 5, name: "Basic communication processes", sector: ELECTRICAL },
+    TechnologyField { id: 6, name: "Computer technology", sector: ELECTRICAL },
+    TechnologyField { id: 7, name: "IT methods for management", sector: ELECTRICAL },
+    TechnologyField { id: 8, name: "Semiconductors", sector: ELECTRICAL },
+    TechnologyField { id: 9, name: "Optics", sector: INSTRUMENTS },
+    TechnologyField { id: 10, name: "Measurement", sector: INSTRUMENTS },
+    TechnologyField { id: 11, name: "Analysis of biological materials", sector: INSTRUMENTS },
+    TechnologyField { id: 12, name: "Control", sector: INSTRUMENTS },
+    TechnologyField { id: 13, name: "Medical technology", sector: INSTRUMENTS },
+    TechnologyField { id: 14, name: "Organic fine chemistry", sector: CHEMISTRY },
+    TechnologyField { id: 15, name: "Biotechnology", sector: CHEMISTRY },
+    TechnologyField { id: 16, name: "Pharmaceuticals", sector: CHEMISTRY },
+    TechnologyField { id: 17, name: "Macromolecular chemistry, polymers", sector: CHEMISTRY },
+    TechnologyField { id: 18, name: "Food chemistry", sector: CHEMISTRY },
+    TechnologyField { id: 19, name: "Basic materials chemistry", sector: CHEMISTRY },
+    TechnologyField { id: 20, name: "Materials, metallurgy", sector: CHEMISTRY },
+    TechnologyField { id: 21, name: "Surface technology, coating", sector: CHEMISTRY },
+    TechnologyField { id: 22, name: "Micro-structural and nano-technology", sector: CHEMISTRY },
+    TechnologyField { id: 23, name: "Chemical engineering", sector: CHEMISTRY },
+    TechnologyField { id: 24, name: "Environmental technology", sector: CHEMISTRY },
+    TechnologyField { id: 25, name: "Handling", sector: MECHANICAL },
+    TechnologyField { id: 26, name: "Machine tools", sector: MECHANICAL },
+    TechnologyField { id: 27, name: "Engines, pumps, turbines", sector: MECHANICAL },
+    TechnologyField { id: 28, name: "Textile and paper machines", sector: MECHANICAL },
+    TechnologyField { id: 29, name: "Other special machines", sector: MECHANICAL },
+    TechnologyField { id: 30, name: "Thermal processes and apparatus", sector: MECHANICAL },
+    TechnologyField { id: 31, name: "Mechanical elements", sector: MECHANICAL },
+    TechnologyField { id: 32, name: "Transport", sector: MECHANICAL },
+    TechnologyField { id: 33, name: "Furniture, games", sector: OTHER },
+    TechnologyField { id: 34, name: "Other consumer goods", sector: OTHER },
+    TechnologyField { id: 35, name: "Civil engineering", sector: OTHER },
+];
+
+/// IPC subclasses, or subclass and main group, of each field. A main group entry takes
+/// precedence over its subclass's entry, e.g. G01N 33 is field 11 and the rest of G01N field 10.
+const IPC_FIELDS: [(u8, &str); 35] = [
+    (1, "F21H F21K F21L F21S F21V F21W F21Y H01B H01C H01F H01G H01H H01J H01K H01M H01R H01T \
+         H02B H02G H02H H02J H02K H02M H02N H02P H02S H05B H05C H05F H99Z"),
+    (2, "G09F G09G G11B H04N H04R H04S H05K"),
+    (3, "G08C H01P H01Q H04B H04H H04J H04K H04M H04N-1 H04Q"),
+    (4, "H04L H04N-21 H04W"),
+    (5, "H03B H03C H03D H03F H03G H03H H03J H03K H03L H03M"),
+    (6, "G06C G06D G06E G06F G06G G06J G06K G06M G06N G06T G10L G11C"),
+    (7, "G06Q"),
+    (8, "H01L H10B H10K H10N"),
+    (9, "G02B G02C G02F G03B G03C G03D G03F G03G G03H H01S"),
+    (10, "G01B G01C G01D G01F G01G G01H G01J G01K G01L G01M G01N G01P G01Q G01R G01S G01V G01W \
+          G04B G04C G04D G04F G04G G04R G12B G99Z"),
+    (11, "G01N-33"),
+    (12, "G05B G05D G05F G07B G07C G07D G07F G07G G08B G08G G09B G09C G09D"),
+    (13, "A61B A61C A61D A61F A61G A61H A61J A61L A61M A61N H05G"),
+    (14, "A61K-8 A61Q C07B C07C C07D C07F C07H C07J C40B"),
+    (15, "C07G C07K C12M C12N C12P C12Q C12R C12S"),
+    (16, "A61K A61P"),
+    (17, "C08B C08C C08F C08G C08H C08K C08L"),
+    (18, "A01H A21D A23B A23C A23D A23F A23G A23J A23K A23L C12C C12F C12G C12H C12J C13B C13D \
+          C13F C13J C13K"),
+    (19, "A01N A01P C05B C05C C05D C05F C05G C06B C06C C06D C06F C09B C09C C09D C09F C09G C09H \
+          C09J C09K C10B C10C C10F C10G C10H C10J C10K C10L C10M C10N C11B C11C C11D C99Z"),
+    (20, "B22C B22D B22F C01B C01C C01D C01F C01G C03C C04B C21B C21C C21D C22B C22C C22F"),
+    (21, "B05C B05D B32B C23C C23D C23F C23G C25B C25C C25D C25F C30B"),
+    (22, "B81B B81C B82B B82Y"),
+    (23, "B01B B01D B01F B01J B01L B02C B03B B03C B03D B04B B04C B05B B06B B07B B07C B08B C14C \
+          D06B D06C D06L F25J F26B H05H"),
+    (24, "A62D B01D-45 B01D-46 B01D-47 B01D-49 B01D-50 B01D-51 B01D-52 B01D-53 B09B B09C B65F \
+          C02F E01F-8 F01N F23G F23J G01T"),
+    (25, "B25J B65B B65C B65D B65G B65H B66B B66C B66D B66F B67B B67C B67D"),
+    (26, "B21B B21C B21D B21F B21G B21H B21J B21K B21L B23B B23C B23D B23F B23G B23H B23K B23P \
+          B23Q B24B B24C B24D B25B B25C B25D B25F B25G B25H B26B B26D B26F B27B B27C B27D B27F \
+          B27G B27H B27J B27K B27L B27M B27N B30B"),
+    (27, "F01B F01C F01D F01K F01L F01M F01P F02B F02C F02D F02F F02G F02K F02M F02N F02P F03B \
+          F03C F03D F03G F03H F04B F04C F04D F04F F23R F99Z G21B G21C G21D G21F G21G G21H G21J \
+          G21K"),
+    (28, "A41H A43D A46D B31B B31C B31D B31F B41B B41C B41D B41F B41G B41J B41K B41L B41M B41N \
+          C14B D01B D01C D01D D01F D01G D01H D02G D02H D02J D03C D03D D03J D04B D04C D04G D04H \
+          D05B D05C D06G D06H D06J D06M D06P D06Q D21B D21C D21D D21F D21G D21H D21J D99Z"),
+    (29, "A01B A01C A01D A01F A01G A01J A01K A01L A01M A21B A21C A22B A22C A23N A23P B02B B28B \
+          B28C B28D B29B B29C B29D B29K B29L B33Y B99Z C03B C08J C12L C13C C13G C13H F41A F41B \
+          F41C F41F F41G F41H F41J F42B F42C F42D"),
+    (30, "F22B F22D F22G F23B F23C F23D F23H F23K F23L F23M F23N F23Q F24B F24C F24D F24F F24H \
+          F24J F24S F24T F25B F25C F27B F27D F28B F28C F28D F28F F28G"),
+    (31, "F15B F15C F15D F16B F16C F16D F16F F16G F16H F16J F16K F16L F16M F16N F16P F16S F16T \
+          F17B F17C F17D G05G"),
+    (32, "B60B B60C B60D B60F B60G B60H B60J B60K B60L B60M B60N B60P B60Q B60R B60S B60T B60V \
+          B60W B61B B61C B61D B61F B61G B61H B61J B61K B61L B62B B62C B62D B62H B62J B62K B62L \
+          B62M B63B B63C B63G B63H B63J B64B B64C B64D B64F B64G B64U"),
+    (33, "A47B A47C A47D A47F A47G A47H A47J A47K A47L A63B A63C A63D A63F A63G A63H A63J A63K"),
+    (34, "A24B A24C A24D A24F A41B A41C A41D A41F A41G A42B A42C A43B A43C A44B A44C A45B A45C \
+          A45D A45F A46B A62B A62C A99Z B42B B42C B42D B42F B43K B43L B43M B44B B44C B44D B44F \
+          B68B B68C B68F B68G D04D D06F D06N D07B F25D G10B G10C G10D G10F G10G G10H G10K"),
+    (35, "E01B E01C E01D E01F E01H E02B E02C E02D E02F E03B E03C E03D E03F E04B E04C E04D E04F \
+          E04G E04H E05B E05C E05D E05F E05G E06B E06C E21B E21C E21D E21F E99Z"),
+];
+
+lazy_static! {
+    // (subclass, main group) to field id
+    static ref IPC_FIELD_INDEX: HashMap<(String, Option<u32>), u8> = {
+        let mut index = HashMap::new();
+        for (field, entries) in IPC_FIELDS.iter() {
+            for entry in entries.split_whitespace() {
+                let mut parts = entry.splitn(2, '-');
+                let subclass = parts.next().unwrap_or("").to_owned();
+                let main_group = parts.next().and_then(|group| group.parse().ok());
+                index.insert((subclass, main_group), *field);
+            }
+        }
+        index
+    };
+}
+
+/// Subclass and main group of an IPC or CPC symbol: "H04L 9/32", "H04L9/32", "H04L 9", or
+/// just "H04L". None if it doesn't start with a subclass.
+fn split_symbol(symbol: &str) -> Option<(String, Option<u32>)> {
+    let compact: String = symbol.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+
+    let subclass = compact.get(..4)?;
+    let b = subclass.as_bytes();
+    if !(b[0].is_ascii_alphabetic() && b[1].is_ascii_digit() && b[2].is_ascii_digit() && b[3].is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let main_group = compact[4..].split('/').next().and_then(|group| group.parse().ok());
+    Some((subclass.to_owned(), main_group))
+}
+
+pub fn field(id: u8) -> Option<&'static TechnologyField> {
+    FIELDS.iter().find(|field| field.id == id)
+}
+
+/// WIPO technology field of an IPC symbol, e.g. "G01N 33/53" is field 11,
+/// "Analysis of biological materials".
+pub fn ipc_field(symbol: &str) -> Option<&'static TechnologyField> {
+    let (subclass, main_group) = split_symbol(symbol)?;
+
+    main_group.and_then(|group| IPC_FIELD_INDEX.get(&(subclass.clone(), Some(group))))
+        .or_else(|| IPC_FIELD_INDEX.get(&(subclass, None)))
+        .and_then(|id| field(*id))
+}
+
+/// The IPC main group a CPC symbol falls in, e.g. "H04L 9/3226" to "H04L 9/00"; a subclass
+/// alone stays a subclass. None for the Y section and 2000-series indexing groups.
+pub fn cpc_to_ipc(symbol: &str) -> Option<String> {
+    let (subclass, main_group) = split_symbol(symbol)?;
+    if subclass.starts_with('Y') {
+        return None;
+    }
+
+    match main_group {
+        Some(group) if group >= 2000 => None,
+        Some(group) => Some(format!("{} {}/00", subclass, group)),
+        None => Some(subclass),
+    }
+}
+
+/// USPC class to the CPC subclass(es) most of its subclasses went to in the reclassification.
+/// Design classes are left out: designs are classified by Locarno.
+const USPC_CLASSES: [(&str, &str); 433] = [
+    ("2", "A41D"), ("4", "A47K"), ("5", "A47C"), ("7", "B25F"), ("8", "D06P"), ("12", "A43D"),
+    ("14", "E01D"), ("15", "A47L"), ("16", "E05D"), ("19", "D01G"), ("23", "B01D"), ("24", "A44B"),
+    ("26", "D06C"), ("27", "A61G"), ("28", "D04H"), ("29", "B23P"), ("30", "B26B"), ("33", "G01B"),
+    ("34", "F26B"), ("36", "A43B"), ("37", "E02F"), ("38", "D06F"), ("40", "G09F"), ("42", "F41A"),
+    ("43", "A01K"), ("44", "C10L"), ("47", "A01G"), ("48", "C10J"), ("49", "E06B"), ("51", "B24D"),
+    ("52", "E04B"), ("53", "B65B"), ("54", "B68B"), ("55", "B01D"), ("56", "A01D"), ("57", "D01H"),
+    ("59", "B21L"), ("60", "F01K"), ("62", "F25B"), ("63", "A44C"), ("65", "C03B"), ("66", "D04B"),
+    ("68", "D06B"), ("69", "C14B"), ("70", "E05B"), ("71", "C05G"), ("72", "B21D"), ("73", "G01N"),
+    ("74", "F16H"), ("75", "C22B"), ("76", "B21K"), ("79", "A44B"), ("81", "B25B"), ("82", "B23B"),
+    ("83", "B26D"), ("84", "G10H"), ("86", "F42B"), ("87", "D04C"), ("89", "F41A"), ("91", "F15B"),
+    ("92", "F15B"), ("95", "B01D"), ("96", "B01D"), ("99", "A47J"), ("100", "B30B"), ("101", "B41F"),
+    ("102", "F42B"), ("104", "B61B"), ("105", "B61D"), ("106", "C09D"), ("108", "A47B"), ("109", "E05G"),
+    ("110", "F23G"), ("111", "A01C"), ("112", "D05B"), ("114", "B63B"), ("116", "G08B"), ("117", "C30B"),
+    ("118", "B05C"), ("119", "A01K"), ("122", "F22B"), ("123", "F02B"), ("124", "F41B"), ("125", "B28D"),
+    ("126", "F24C"), ("127", "C13K"), ("128", "A61B"), ("131", "A24F"), ("132", "A45D"), ("134", "B08B"),
+    ("135", "A45B"), ("136", "H01L"), ("137", "F16K"), ("138", "F16L"), ("139", "D03D"), ("140", "B21F"),
+    ("141", "B67C"), ("142", "B27C"), ("144", "B27C"), ("147", "B27H"), ("148", "C21D"), ("149", "C06B"),
+    ("150", "A45C"), ("152", "B60C"), ("156", "B32B"), ("157", "B60B"), ("159", "B01D"), ("160", "E06B"),
+    ("162", "D21H"), ("163", "B21G"), ("164", "B22D"), ("165", "F28F"), ("166", "E21B"), ("168", "A01L"),
+    ("169", "A62C"), ("171", "A01D"), ("172", "A01B"), ("173", "B25D"), ("174", "H01B"), ("175", "E21B"),
+    ("177", "G01G"), ("178", "H04L"), ("180", "B60K"), ("181", "G10K"), ("182", "E06C"), ("184", "F16N"),
+    ("185", "F03G"), ("186", "A47F"), ("187", "B66B"), ("188", "F16D"), ("190", "A45C"), ("191", "B60M"),
+    ("192", "F16D"), ("193", "B65G"), ("194", "G07F"), ("196", "C10G"), ("198", "B65G"), ("199", "B41B"),
+    ("200", "H01H"), ("201", "C10B"), ("202", "B01D"), ("203", "B01D"), ("204", "C25B"), ("205", "C25D"),
+    ("206", "B65D"), ("208", "C10G"), ("209", "B07B"), ("210", "B01D C02F"), ("211", "A47B"), ("212", "B66C"),
+    ("213", "B61G"), ("215", "B65D"), ("216", "C23F"), ("217", "B65D"), ("218", "H01H"), ("219", "H05B"),
+    ("220", "B65D"), ("221", "G07F"), ("222", "B67D"), ("223", "A41H"), ("224", "A45F"), ("225", "B26F"),
+    ("226", "B65H"), ("227", "B25C"), ("228", "B23K"), ("229", "B65D"), ("232", "A47G"), ("234", "B26F"),
+    ("235", "G06K"), ("236", "G05D"), ("237", "F24D"), ("238", "E01B"), ("239", "B05B"), ("241", "B02C"),
+    ("242", "B65H"), ("244", "B64C"), ("245", "B21F"), ("246", "B61L"), ("248", "F16M"), ("249", "B28B"),
+    ("250", "G01T"), ("251", "F16K"), ("252", "C09K"), ("254", "B66F"), ("256", "E04H"), ("257", "H01L"),
+    ("260", "C07C"), ("261", "B01F"), ("264", "B29C"), ("266", "C21B"), ("267", "F16F"), ("269", "B25B"),
+    ("270", "B65H"), ("271", "B65H"), ("273", "A63F"), ("276", "B41B"), ("277", "F16J"), ("278", "B62C"),
+    ("279", "B23B"), ("280", "B62D"), ("281", "B42D"), ("283", "B42D"), ("285", "F16L"), ("290", "H02K"),
+    ("292", "E05C"), ("293", "B60R"), ("294", "B66C"), ("295", "B60B"), ("296", "B62D"), ("297", "A47C"),
+    ("298", "B60P"), ("299", "E21C"), ("300", "A46D"), ("301", "B60B"), ("303", "B60T"), ("305", "B62D"),
+    ("307", "H02J"), ("310", "H02K"), ("312", "A47B"), ("313", "H01J"), ("314", "H05B"), ("315", "H05B"),
+    ("318", "H02P"), ("320", "H02J"), ("322", "H02P"), ("323", "G05F"), ("324", "G01R"), ("326", "H03K"),
+    ("327", "H03K"), ("329", "H03D"), ("330", "H03F"), ("331", "H03B"), ("332", "H03C"), ("333", "H01P"),
+    ("334", "H03J"), ("335", "H01H"), ("336", "H01F"), ("337", "H01H"), ("338", "H01C"), ("340", "G08B"),
+    ("341", "H03M"), ("342", "G01S"), ("343", "H01Q"), ("345", "G09G"), ("346", "G01D"), ("347", "B41J"),
+    ("348", "H04N"), ("349", "G02F"), ("351", "A61B"), ("352", "G03B"), ("353", "G03B"), ("355", "G03B"),
+    ("356", "G01J"), ("358", "H04N"), ("359", "G02B"), ("360", "G11B"), ("361", "H05K"), ("362", "F21V"),
+    ("363", "H02M"), ("365", "G11C"), ("366", "B01F"), ("367", "G01S"), ("368", "G04G"), ("369", "G11B"),
+    ("370", "H04L"), ("372", "H01S"), ("373", "H05B"), ("374", "G01K"), ("375", "H04L"), ("376", "G21C"),
+    ("377", "H03K"), ("378", "H05G"), ("379", "H04M"), ("380", "H04L"), ("381", "H04R"), ("382", "G06T"),
+    ("383", "B65D"), ("384", "F16C"), ("385", "G02B"), ("386", "H04N"), ("388", "H02P"), ("392", "H05B"),
+    ("396", "G03B"), ("398", "H04B"), ("399", "G03G"), ("400", "B41J"), ("401", "B43K"), ("402", "B42F"),
+    ("403", "F16B"), ("404", "E01C"), ("405", "E02D"), ("406", "B65G"), ("407", "B23C"), ("408", "B23B"),
+    ("409", "B23C"), ("410", "B60P"), ("411", "F16B"), ("412", "B42C"), ("413", "B21D"), ("414", "B65G"),
+    ("415", "F04D"), ("416", "F01D"), ("417", "F04B"), ("418", "F04C"), ("419", "B22F"), ("420", "C22C"),
+    ("422", "B01J"), ("423", "C01B"), ("424", "A61K"), ("425", "B29C"), ("426", "A23L"), ("427", "B05D"),
+    ("428", "B32B"), ("429", "H01M"), ("430", "G03F"), ("431", "F23D"), ("432", "F27B"), ("433", "A61C"),
+    ("434", "G09B"), ("435", "C12N"), ("436", "G01N"), ("438", "H01L"), ("439", "H01R"), ("440", "B63H"),
+    ("441", "B63B"), ("442", "D03D"), ("445", "H01J"), ("446", "A63H"), ("449", "A01K"), ("450", "A41C"),
+    ("451", "B24B"), ("452", "A22C"), ("453", "G07D"), ("454", "F24F"), ("455", "H04W"), ("460", "A01F"),
+    ("462", "B41L"), ("463", "A63F"), ("464", "F16D"), ("470", "B21K"), ("472", "A63G"), ("473", "A63B"),
+    ("474", "F16H"), ("475", "F16H"), ("476", "F16H"), ("477", "B60W"), ("482", "A63B"), ("483", "B23Q"),
+    ("492", "F16C"), ("493", "B31B"), ("494", "B04B"), ("501", "C04B"), ("502", "B01J"), ("503", "B41M"),
+    ("504", "A01N"), ("505", "H10N"), ("506", "C40B"), ("507", "C09K"), ("508", "C10M"), ("510", "C11D"),
+    ("512", "C11B"), ("514", "A61K"), ("516", "B01F"), ("518", "C10G"), ("520", "C08F"), ("521", "C08G"),
+    ("522", "C08F"), ("523", "C08K"), ("524", "C08K"), ("525", "C08L"), ("526", "C08F"), ("527", "C08H"),
+    ("528", "C08G"), ("530", "C07K"), ("532", "C07C"), ("536", "C07H"), ("540", "C07D"), ("544", "C07D"),
+    ("546", "C07D"), ("548", "C07D"), ("549", "C07D"), ("552", "C07J"), ("554", "C07C"), ("556", "C07F"),
+    ("558", "C07C"), ("560", "C07C"), ("562", "C07C"), ("564", "C07C"), ("568", "C07C"), ("570", "C07C"),
+    ("585", "C07C"), ("588", "A62D"), ("600", "A61B"), ("601", "A61H"), ("602", "A61F"), ("604", "A61M"),
+    ("606", "A61B"), ("607", "A61N"), ("623", "A61F"), ("700", "G05B"), ("701", "G01C"), ("702", "G01D"),
+    ("703", "G06F"), ("704", "G10L"), ("705", "G06Q"), ("706", "G06N"), ("707", "G06F"), ("708", "G06F"),
+    ("709", "G06F"), ("710", "G06F"), ("711", "G06F"), ("712", "G06F"), ("713", "G06F"), ("714", "G06F"),
+    ("715", "G06F"), ("716", "G06F"), ("717", "G06F"), ("718", "G06F"), ("719", "G06F"), ("720", "G11B"),
+    ("725", "H04N"), ("726", "G06F"), ("800", "A01H"), ("850", "G01Q"), ("901", "B25J"), ("902", "G06Q"),
+    ("903", "B60W"), ("930", "C07K"), ("976", "G21C"), ("977", "B82Y"), ("984", "G10H"), ("987", "C07F"),
+    ("PLT", "A01H"),
+];
+
+lazy_static! {
+    static ref USPC_CLASS_INDEX: HashMap<&'static str, &'static str> = USPC_CLASSES.iter().cloned().collect();
+}
+
+#[derive(Deserialize)]
+struct UspcRecord {
+    uspc: String,
+    cpc: String,
+}
+
+/// USPC to CPC, at subclass level where the source has it and class level otherwise. The
+/// default has nothing loaded, so only the bundled class-level table applies.
+#[derive(Debug, Default, Clone)]
+pub struct UspcConcordance {
+    cpc: BTreeMap<UspcCode, Vec<String>>,
+}
+
+impl UspcConcordance {
+    /// csv with a header row: uspc,cpc. One row per pair; uspc is in slashed form ("424/130.1")
+    /// or a class alone ("424") for class-level mappings.
+    pub fn from_reader<R: Read>(r: R) -> Result<Self, Error> {
+        let mut concordance = Self::default();
+
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: UspcRecord = record.context(ReadTable)?;
+            concordance.insert(record.uspc.parse()?, record.cpc.trim());
+        }
+
+        Ok(concordance)
+    }
+
+    pub fn insert(&mut self, uspc: UspcCode, cpc: &str) {
+        let symbols = self.cpc.entry(uspc).or_default();
+        if !symbols.iter().any(|symbol| symbol == cpc) {
+            symbols.push(cpc.to_owned());
+        }
+    }
+
+    /// CPC symbols for the code, falling back to its class if the subclass isn't listed, then
+    /// to the bundled class-level table
+    pub fn cpc(&self, uspc: &UspcCode) -> Vec<&str> {
+        let class = UspcCode { class: uspc.class.clone(), subclass: None, digest: None };
+
+        match self.cpc.get(uspc).or_else(|| self.cpc.get(&class)) {
+            Some(symbols) => symbols.iter().map(|symbol| symbol.as_str()).collect(),
+            None => USPC_CLASS_INDEX.get(uspc.class.as_str())
+                .map(|symbols| symbols.split_whitespace().collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Which of the grant's classifications the derived technology came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Ipc,
+    Cpc,
+    Uspc,
+    None,
+}
+
+/// A grant's technology in each classification, given or derived
+#[derive(Debug, Clone, PartialEq)]
+pub struct DerivedTechnology {
+    pub source: Source,
+    /// as given, or from uspc
+    pub cpc: Vec<String>,
+    /// as given, or from cpc
+    pub ipc: Vec<String>,
+    /// from ipc, in order and without repeats
+    pub fields: Vec<&'static TechnologyField>,
+}
+
+/// The grant's technology fields, from IPC if it has any, else from CPC, else from its
+/// main and further USPC classifications through `uspc`.
+pub fn derive(dg: &BibliographicDataGrant, uspc: &UspcConcordance) -> DerivedTechnology {
+    let cpcs = &dg.classifications_cpc;
+    let given_cpc: Vec<String> = cpcs.main_cpc.iter().chain(cpcs.further_cpc.iter()).map(|c| c.symbol()).collect();
+    let given_ipc: Vec<String> = dg.classifications_ipcr.iter().map(|c| c.symbol()).collect();
+
+    let (source, cpc, ipc) = if !given_ipc.is_empty() {
+        (Source::Ipc, given_cpc, given_ipc)
+    } else if !given_cpc.is_empty() {
+        let ipc = given_cpc.iter().filter_map(|symbol| cpc_to_ipc(symbol)).collect();
+        (Source::Cpc, given_cpc, ipc)
+    } else {
+        let national = &dg.classification_national;
+        let codes: Vec<UspcCode> = national.main_uspc().ok().into_iter()
+            .chain(national.further_uspc().and_then(|code| code.ok()))
+            .collect();
+        let mut cpc: Vec<String> = Vec::new();
+        for symbol in codes.iter().flat_map(|code| uspc.cpc(code)) {
+            if !cpc.iter().any(|seen| seen == symbol) {
+                cpc.push(symbol.to_owned());
+            }
+        }
+        let ipc = cpc.iter().filter_map(|symbol| cpc_to_ipc(symbol)).collect();
+        let source = if cpc.is_empty() { Source::None } else { Source::Uspc };
+        (source, cpc, ipc)
+    };
+
+    let mut seen = BTreeSet::new();
+    let fields = ipc.iter()
+        .filter_map(|symbol| ipc_field(symbol))
+        .filter(|field| seen.insert(field.id))
+        .collect();
+
+    DerivedTechnology { source, cpc, ipc, fields }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::ClassificationCpc;
+
+    #[test]
+    fn ipc_fields() {
+        assert_eq!(ipc_field("H04L 9/32").unwrap().name, "Digital communication");
+        assert_eq!(ipc_field("G01N 33/53").unwrap().id, 11);
+        assert_eq!(ipc_field("G01N 21/00").unwrap().id, 10);
+        assert_eq!(ipc_field("A61K8/02").unwrap().id, 14);
+        assert_eq!(ipc_field("A61K 31/00").unwrap().id, 16);
+        assert_eq!(ipc_field("H04N 1/00").unwrap().id, 3);
+        assert_eq!(ipc_field("H04N 5/225").unwrap().id, 2);
+        assert_eq!(ipc_field("B01D 53/94").unwrap().sector, CHEMISTRY);
+        assert!(ipc_field("Y02E 10/50").is_none());
+
+        assert_eq!(cpc_to_ipc("H04L 9/3226").unwrap(), "H04L 9/00");
+        assert!(cpc_to_ipc("A61K 2300/00").is_none());
+        assert!(cpc_to_ipc("Y02E 10/50").is_none());
+    }
+
+    #[test]
+    fn derive_from_uspc() {
+        let csv = "uspc,cpc\n713/168,H04L 9/3226\n380,H04L 9/00\n";
+        let concordance = UspcConcordance::from_reader(csv.as_bytes()).unwrap();
+
+        let mut dg = BibliographicDataGrant::default();
+        dg.classification_national.main_classification = "713168".to_owned();
+        dg.classification_national.further_classification = Some("380 30".to_owned());
+
+        let derived = derive(&dg, &concordance);
+        assert_eq!(derived.source, Source::Uspc);
+        assert_eq!(derived.cpc, vec!["H04L 9/3226", "H04L 9/00"]);
+        assert_eq!(derived.fields.iter().map(|f| f.id).collect::<Vec<_>>(), vec![4]);
+
+        // bundled classes only
+        let derived = derive(&dg, &UspcConcordance::default());
+        assert_eq!(derived.source, Source::Uspc);
+        assert_eq!(derived.cpc, vec!["G06F", "H04L"]);
+        assert_eq!(derived.fields.iter().map(|f| f.id).collect::<Vec<_>>(), vec![6, 4]);
+
+        dg.classification_national.main_classification = "D14138".to_owned();
+        dg.classification_national.further_classification = None;
+        assert_eq!(derive(&dg, &UspcConcordance::default()).source, Source::None);
+
+        dg.classifications_cpc.main_cpc = Some(ClassificationCpc {
+            section: "G".to_owned(),
+            class: "06".to_owned(),
+            subclass: "F".to_owned(),
+            main_group: "21".to_owned(),
+            subgroup: "62".to_owned(),
+            ..ClassificationCpc::default()
+        });
+        let derived = derive(&dg, &concordance);
+        assert_eq!(derived.source, Source::Cpc);
+        assert_eq!(derived.ipc, vec!["G06F 21/00"]);
+        assert_eq!(derived.fields[0].name, "Computer technology");
+    }
+}
@@ -0,0 +1,295 @@
+//! Country code normalization to ISO 3166.
+//!
+//! Grants carry WIPO ST.3 codes, which are ISO 3166-1 alpha-2 plus some history: codes for
+//! states that no longer exist (SU, DD, YU, CS, ...), a few aliases (UK, EL, DT), and US
+//! addresses with a state but no country.
+//!
+//! Historical codes fall in two groups:
+//! - renamed or merged states (DD, ZR, BU, TP, YD, RH) always map to their one successor.
+//! - dissolved states (SU, YU, CS, AN) follow `SuccessorPolicy`, since no single successor
+//!   is right for every use. Individual codes can be pointed elsewhere with `with_successor`.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use crate::data::Address;
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Country {
+    pub alpha2: &'static str,
+    pub alpha3: &'static str,
+}
+
+impl Country {
+    pub fn code(&self, code: CountryCode) -> &'static str {
+        match code {
+            CountryCode::Alpha2 => self.alpha2,
+            CountryCode::Alpha3 => self.alpha3,
+        }
+    }
+}
+
+const fn country(alpha2: &'static str, alpha3: &'static str) -> Country {
+    Country { alpha2, alpha3 }
+}
+
+/// ISO 3166-1 current codes
+const COUNTRIES: [Country; 249] = [
+    country("AD", "AND"), country("AE", "ARE"), country("AF", "AFG"), country("AG", "ATG"),
+    country("AI", "AIA"), country("AL", "ALB"), country("AM", "ARM"), country("AO", "AGO"),
+    country("AQ", "ATA"), country("AR", "ARG"), country("AS", "ASM"), country("AT", "AUT"),
+    country("AU", "AUS"), country("AW", "ABW"), country("AX", "ALA"), country("AZ", "AZE"),
+    country("BA", "BIH"), country("BB", "BRB"), country("BD", "BGD"), country("BE", "BEL"),
+    country("BF", "BFA"), country("BG", "BGR"), country("BH", "BHR"), country("BI", "BDI"),
+    country("BJ", "BEN"), country("BL", "BLM"), country("BM", "BMU"), country("BN", "BRN"),
+    country("BO", "BOL"), country("BQ", "BES"), country("BR", "BRA"), country("BS", "BHS"),
+    country("BT", "BTN"), country("BV", "BVT"), country("BW", "BWA"), country("BY", "BLR"),
+    country("BZ", "BLZ"), country("CA", "CAN"), country("CC", "CCK"), country("CD", "COD"),
+    country("CF", "CAF"), country("CG", "COG"), country("CH", "CHE"), country("CI", "CIV"),
+    country("CK", "COK"), country("CL", "CHL"), country("CM", "CMR"), country("CN", "CHN"),
+    country("CO", "COL"), country("CR", "CRI"), country("CU", "CUB"), country("CV", "CPV"),
+    country("CW", "CUW"), country("CX", "CXR"), country("CY", "CYP"), country("CZ", "CZE"),
+    country("DE", "DEU"), country("DJ", "DJI"), country("DK", "DNK"), country("DM", "DMA"),
+    country("DO", "DOM"), country("DZ", "DZA"), country("EC", "ECU"), country("EE", "EST"),
+    country("EG", "EGY"), country("EH", "ESH"), country("ER", "ERI"), country("ES", "ESP"),
+    country("ET", "ETH"), country("FI", "FIN"), country("FJ", "FJI"), country("FK", "FLK"),
+    country("FM", "FSM"), country("FO", "FRO"), country("FR", "FRA"), country("GA", "GAB"),
+    country("GB", "GBR"), country("GD", "GRD"), country("GE", "GEO"), country("GF", "GUF"),
+    country("GG", "GGY"), country("GH", "GHA"), country("GI", "GIB"), country("GL", "GRL"),
+    country("GM", "GMB"), country("GN", "GIN"), country("GP", "GLP"), country("GQ", "GNQ"),
+    country("GR", "GRC"), country("GS", "SGS"), country("GT", "GTM"), country("GU", "GUM"),
+    country("GW", "GNB"), country("GY", "GUY"), country("HK", "HKG"), country("HM", "HMD"),
+    country("HN", "HND"), country("HR", "HRV"), country("HT", "HTI"), country("HU", "HUN"),
+    country("ID", "IDN"), country("IE", "IRL"), country("IL", "ISR"), country("IM", "IMN"),
+    country("IN", "IND"), country("IO", "IOT"), country("IQ", "IRQ"), country("IR", "IRN"),
+    country("IS", "ISL"), country("IT", "ITA"), country("JE", "JEY"), country("JM", "JAM"),
+    country("JO", "JOR"), country("JP", "JPN"), country("KE", "KEN"), country("KG", "KGZ"),
+    country("KH", "KHM"), country("KI", "KIR"), country("KM", "COM"), country("KN", "KNA"),
+    country("KP", "PRK"), country("KR", "KOR"), country("KW", "KWT"), country("KY", "CYM"),
+    country("KZ", "KAZ"), country("LA", "LAO"), country("LB", "LBN"), country("LC", "LCA"),
+    country("LI", "LIE"), country("LK", "LKA"), country("LR", "LBR"), country("LS", "LSO"),
+    country("LT", "LTU"), country("LU", "LUX"), country("LV", "LVA"), country("LY", "LBY"),
+    country("MA", "MAR"), country("MC", "MCO"), country("MD", "MDA"), country("ME", "MNE"),
+    country("MF", "MAF"), country("MG", "MDG"), country("MH", "MHL"), country("MK", "MKD"),
+    country("ML", "MLI"), country("MM", "MMR"), country("MN", "MNG"), country("MO", "MAC"),
+    country("MP", "MNP"), country("MQ", "MTQ"), country("MR", "MRT"), country("MS", "MSR"),
+    country("MT", "MLT"), country("MU", "MUS"), country("MV", "MDV"), country("MW", "MWI"),
+    country("MX", "MEX"), country("MY", "MYS"), country("MZ", "MOZ"), country("NA", "NAM"),
+    country("NC", "NCL"), country("NE", "NER"), country("NF", "NFK"), country("NG", "NGA"),
+    country("NI", "NIC"), country("NL", "NLD"), country("NO", "NOR"), country("NP", "NPL"),
+    country("NR", "NRU"), country("NU", "NIU"), country("NZ", "NZL"), country("OM", "OMN"),
+    country("PA", "PAN"), country("PE", "PER"), country("PF", "PYF"), country("PG", "PNG"),
+    country("PH", "PHL"), country("PK", "PAK"), country("PL", "POL"), country("PM", "SPM"),
+    country("PN", "PCN"), country("PR", "PRI"), country("PS", "PSE"), country("PT", "PRT"),
+    country("PW", "PLW"), country("PY", "PRY"), country("QA", "QAT"), country("RE", "REU"),
+    country("RO", "ROU"), country("RS", "SRB"), country("RU", "RUS"), country("RW", "RWA"),
+    country("SA", "SAU"), country("SB", "SLB"), country("SC", "SYC"), country("SD", "SDN"),
+    country("SE", "SWE"), country("SG", "SGP"), country("SH", "SHN"), country("SI", "SVN"),
+    country("SJ", "SJM"), country("SK", "SVK"), country("SL", "SLE"), country("SM", "SMR"),
+    country("SN", "SEN"), country("SO", "SOM"), country("SR", "SUR"), country("SS", "SSD"),
+    country("ST", "STP"), country("SV", "SLV"), country("SX", "SXM"), country("SY", "SYR"),
+    country("SZ", "SWZ"), country("TC", "TCA"), country("TD", "TCD"), country("TF", "ATF"),
+    country("TG", "TGO"), country("TH", "THA"), country("TJ", "TJK"), country("TK", "TKL"),
+    country("TL", "TLS"), country("TM", "TKM"), country("TN", "TUN"), country("TO", "TON"),
+    country("TR", "TUR"), country("TT", "TTO"), country("TV", "TUV"), country("TW", "TWN"),
+    country("TZ", "TZA"), country("UA", "UKR"), country("UG", "UGA"), country("UM", "UMI"),
+    country("US", "USA"), country("UY", "URY"), country("UZ", "UZB"), country("VA", "VAT"),
+    country("VC", "VCT"), country("VE", "VEN"), country("VG", "VGB"), country("VI", "VIR"),
+    country("VN", "VNM"), country("VU", "VUT"), country("WF", "WLF"), country("WS", "WSM"),
+    country("YE", "YEM"), country("YT", "MYT"), country("ZA", "ZAF"), country("ZM", "ZMB"),
+    country("ZW", "ZWE"),
+];
+
+/// ISO 3166-3 codes of dissolved states, with the successor used by `SuccessorPolicy::Successor`
+const DISSOLVED: [(Country, &str); 4] = [
+    // Soviet Union
+    (country("SU", "SUN"), "RU"),
+    // Yugoslavia
+    (country("YU", "YUG"), "RS"),
+    // Czechoslovakia. CS was reused for Serbia and Montenegro (2003-2006), which USPTO data
+    // rarely uses; point it elsewhere with `with_successor` if needed.
+    (country("CS", "CSK"), "CZ"),
+    // Netherlands Antilles
+    (country("AN", "ANT"), "CW"),
+];
+
+/// renamed or merged states, and non-ISO aliases seen in ST.3 data
+const RENAMED: [(&str, &str); 9] = [
+    ("DD", "DE"), // East Germany
+    ("DT", "DE"), // pre-1978 code for West Germany
+    ("ZR", "CD"), // Zaire
+    ("BU", "MM"), // Burma
+    ("TP", "TL"), // East Timor
+    ("YD", "YE"), // South Yemen
+    ("RH", "ZW"), // Southern Rhodesia
+    ("UK", "GB"),
+    ("EL", "GR"),
+];
+
+/// US states, DC, and territories USPTO lists as states
+const US_STATES: [&str; 56] = [
+    "AL", "AK", "AZ", "AR", "CA", "CO", "CT", "DE", "FL", "GA", "HI", "ID", "IL", "IN", "IA",
+    "KS", "KY", "LA", "ME", "MD", "MA", "MI", "MN", "MS", "MO", "MT", "NE", "NV", "NH", "NJ",
+    "NM", "NY", "NC", "ND", "OH", "OK", "OR", "PA", "RI", "SC", "SD", "TN", "TX", "UT", "VT",
+    "VA", "WA", "WV", "WI", "WY", "DC", "PR", "GU", "VI", "AS", "MP",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CountryCode {
+    Alpha2,
+    Alpha3,
+}
+
+impl FromStr for CountryCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alpha2" => Ok(CountryCode::Alpha2),
+            "alpha3" => Ok(CountryCode::Alpha3),
+            _ => Err(format!("unknown country code {:?}, expected alpha2 or alpha3", s)),
+        }
+    }
+}
+
+/// What to do with codes of dissolved states
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SuccessorPolicy {
+    /// map to the largest successor: SU to RU, YU to RS, CS to CZ, AN to CW
+    Successor,
+    /// keep the ISO 3166-3 code: SU/SUN, YU/YUG, CS/CSK, AN/ANT
+    Historical,
+    /// treat as unknown
+    Drop,
+}
+
+impl FromStr for SuccessorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "successor" => Ok(SuccessorPolicy::Successor),
+            "historical" => Ok(SuccessorPolicy::Historical),
+            "drop" => Ok(SuccessorPolicy::Drop),
+            _ => Err(format!("unknown successor policy {:?}, expected one of: successor, historical, drop", s)),
+        }
+    }
+}
+
+/// ISO 3166-1 country by alpha-2 or alpha-3 code, case-insensitive
+pub fn lookup(code: &str) -> Option<Country> {
+    let code = code.trim().to_ascii_uppercase();
+
+    COUNTRIES.iter()
+        .find(|c| c.alpha2 == code || c.alpha3 == code)
+        .cloned()
+}
+
+/// Is this a US state (or DC, or a territory listed as a state) code
+pub fn is_us_state(state: &str) -> bool {
+    let state = state.trim().to_ascii_uppercase();
+    US_STATES.iter().any(|s| *s == state)
+}
+
+#[derive(Debug, Clone)]
+pub struct CountryNormalizer {
+    code: CountryCode,
+    policy: SuccessorPolicy,
+    // dissolved alpha-2 code to chosen successor, overriding the policy
+    overrides: BTreeMap<&'static str, Country>,
+}
+
+impl CountryNormalizer {
+    pub fn new(code: CountryCode, policy: SuccessorPolicy) -> Self {
+        Self {
+            code,
+            policy,
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Maps one dissolved state's code to `successor`, regardless of the policy,
+    /// e.g. YU to HR. Errors if either code is unknown.
+    pub fn with_successor(mut self, dissolved: &str, successor: &str) -> Result<Self, Error> {
+        let dissolved = dissolved.trim().to_ascii_uppercase();
+        let (historical, _) = DISSOLVED.iter()
+            .find(|(c, _)| c.alpha2 == dissolved || c.alpha3 == dissolved)
+            .ok_or_else(|| Error::UnknownCountry { code: dissolved.clone() })?;
+        let successor = lookup(successor)
+            .ok_or_else(|| Error::UnknownCountry { code: successor.to_owned() })?;
+
+        self.overrides.insert(historical.alpha2, successor);
+        Ok(self)
+    }
+
+    /// The ISO country for a raw code, falling back to US when there's no code but `state`
+    /// is a US state. None for codes that aren't countries (e.g. EP, WO, XX).
+    pub fn country(&self, country: Option<&str>, state: Option<&str>) -> Option<Country> {
+        let raw = match country.map(|c| c.trim().to_ascii_uppercase()) {
+            Some(ref c) if !c.is_empty() => c.clone(),
+            _ => {
+                return match state {
+                    Some(state) if is_us_state(state) => lookup("US"),
+                    _ => None,
+                };
+            },
+        };
+
+        if let Some((_, successor)) = RENAMED.iter().find(|(code, _)| *code == raw) {
+            return lookup(successor);
+        }
+        if let Some((historical, successor)) = DISSOLVED.iter().find(|(c, _)| c.alpha2 == raw || c.alpha3 == raw) {
+            if let Some(successor) = self.overrides.get(historical.alpha2) {
+                return Some(*successor);
+            }
+            return match self.policy {
+                SuccessorPolicy::Successor => lookup(successor),
+                SuccessorPolicy::Historical => Some(*historical),
+                SuccessorPolicy::Drop => None,
+            };
+        }
+
+        lookup(&raw)
+    }
+
+    /// the configured code (alpha-2 or alpha-3) for a raw code and state
+    pub fn code(&self, country: Option<&str>, state: Option<&str>) -> Option<&'static str> {
+        self.country(country, state).map(|c| c.code(self.code))
+    }
+
+    pub fn address_code(&self, address: &Address) -> Option<&'static str> {
+        self.code(address.country.as_deref(), address.state.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_codes() {
+        let iso3 = CountryNormalizer::new(CountryCode::Alpha3, SuccessorPolicy::Successor);
+
+        assert_eq!(iso3.code(Some("de"), None), Some("DEU"));
+        assert_eq!(iso3.code(Some("DD"), None), Some("DEU"));
+        assert_eq!(iso3.code(Some("UK"), None), Some("GBR"));
+        assert_eq!(iso3.code(Some("SU"), None), Some("RUS"));
+        assert_eq!(iso3.code(None, Some("CA")), Some("USA"));
+        assert_eq!(iso3.code(Some(""), Some("ON")), None);
+        assert_eq!(iso3.code(Some("EP"), None), None);
+
+        let historical = CountryNormalizer::new(CountryCode::Alpha2, SuccessorPolicy::Historical)
+            .with_successor("YU", "HR")
+            .unwrap();
+        assert_eq!(historical.code(Some("SU"), None), Some("SU"));
+        assert_eq!(historical.code(Some("YU"), None), Some("HR"));
+        // renames don't depend on the policy
+        assert_eq!(historical.code(Some("ZR"), None), Some("CD"));
+
+        let drop = CountryNormalizer::new(CountryCode::Alpha2, SuccessorPolicy::Drop);
+        assert_eq!(drop.code(Some("CS"), None), None);
+
+        assert!(drop.with_successor("DE", "FR").is_err());
+    }
+}
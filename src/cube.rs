@@ -0,0 +1,545 @@
+//! Country × technology × year aggregation.
+//!
+//! `Cube` is built in a single pass over parsed grants, so only the cells are held in memory
+//! and never the grants themselves.
+//!
+//! How a grant is spread over cells is set by `Counting` for countries and `ClassCounting` for
+//! technologies; a cell gets the product of the two weights. With whole counting (the default)
+//! a grant with inventors in US, DE, DE and two CPC subclasses adds one to four cells. With
+//! fractional counting on both axes the grant adds up to exactly one: US gets 1/3 and DE 2/3,
+//! each split in half across the subclasses.
+//!
+//! With `with_regions` the country axis holds US regions (counties, CBSAs, MSAs or commuting
+//! zones) instead, counted the same way; see `region`.
+
+use chrono::Datelike;
+use serde::Serialize;
+use snafu::ResultExt;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::concordance::{derive, DerivedTechnology, UspcConcordance};
+use crate::country::CountryNormalizer;
+use crate::locarno::{edition_at, EditionConcordance, LocarnoCode};
+use crate::region::{RegionLevel, Regions};
+use crate::data::{Address, BibliographicDataGrant, PatentGrant};
+use crate::error::{Error, WriteCube};
+
+/// The classification used for the technology axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Technology {
+    /// e.g. H, from main and further cpc
+    CpcSection,
+    /// e.g. H04, from main and further cpc
+    CpcClass,
+    /// e.g. H04L, from main and further cpc
+    CpcSubclass,
+    /// e.g. H04L 9/00, from main and further cpc
+    CpcGroup,
+    /// ipc subclass, e.g. H04L
+    Ipc,
+    /// uspc class of the main classification, e.g. 713
+    UspcClass,
+    /// locarno class, e.g. 02 (design patents only)
+    LocarnoClass,
+    /// locarno subclass, e.g. 02-02 (design patents only)
+    LocarnoSubclass,
+    /// WIPO technology field, 1 to 35, from ipc, or cpc where there's no ipc
+    WipoField,
+}
+
+impl FromStr for Technology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cpc-section" => Ok(Technology::CpcSection),
+            "cpc-class" => Ok(Technology::CpcClass),
+            "cpc-subclass" => Ok(Technology::CpcSubclass),
+            "cpc-group" => Ok(Technology::CpcGroup),
+            "ipc" => Ok(Technology::Ipc),
+            "uspc" => Ok(Technology::UspcClass),
+            "locarno" => Ok(Technology::LocarnoClass),
+            "locarno-subclass" => Ok(Technology::LocarnoSubclass),
+            "wipo-field" => Ok(Technology::WipoField),
+            _ => Err(format!(
+                "unknown technology {:?}, expected one of: cpc-section, cpc-class, cpc-subclass, cpc-group, ipc, uspc, locarno, locarno-subclass, wipo-field",
+                s,
+            )),
+        }
+    }
+}
+
+/// Whose address gives the country axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CountryOf {
+    Inventor,
+    Assignee,
+}
+
+impl FromStr for CountryOf {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inventor" => Ok(CountryOf::Inventor),
+            "assignee" => Ok(CountryOf::Assignee),
+            _ => Err(format!("unknown country source {:?}, expected inventor or assignee", s)),
+        }
+    }
+}
+
+/// Which date gives the year axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YearOf {
+    Grant,
+    Application,
+}
+
+impl FromStr for YearOf {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grant" => Ok(YearOf::Grant),
+            "application" => Ok(YearOf::Application),
+            _ => Err(format!("unknown year source {:?}, expected grant or application", s)),
+        }
+    }
+}
+
+/// How a grant is shared among the countries of its inventors (or assignees)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Counting {
+    /// every country listed gets the whole grant
+    Whole,
+    /// each inventor gets an equal share, summed per country
+    Fractional,
+    /// each distinct country gets an equal share, however many inventors it has
+    FractionalCountry,
+    /// the first listed inventor's country gets the whole grant
+    First,
+}
+
+impl FromStr for Counting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "whole" => Ok(Counting::Whole),
+            "fractional" => Ok(Counting::Fractional),
+            "fractional-country" => Ok(Counting::FractionalCountry),
+            "first" => Ok(Counting::First),
+            _ => Err(format!("unknown counting {:?}, expected one of: whole, fractional, fractional-country, first", s)),
+        }
+    }
+}
+
+/// How a grant is shared among its technology classes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClassCounting {
+    /// every class gets the whole grant
+    Whole,
+    /// each distinct class gets an equal share
+    Fractional,
+}
+
+impl FromStr for ClassCounting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "whole" => Ok(ClassCounting::Whole),
+            "fractional" => Ok(ClassCounting::Fractional),
+            _ => Err(format!("unknown class counting {:?}, expected whole or fractional", s)),
+        }
+    }
+}
+
+/// Technology codes of a grant, in document order and without repeats
+pub fn technologies(dg: &BibliographicDataGrant, technology: Technology) -> Vec<String> {
+    let cpcs = || dg.classifications_cpc.main_cpc.iter().chain(dg.classifications_cpc.further_cpc.iter());
+
+    let codes: Vec<String> = match technology {
+        Technology::CpcSection => cpcs()
+            .map(|c| c.section.clone())
+            .collect(),
+        Technology::CpcClass => cpcs()
+            .map(|c| format!("{}{}", c.section, c.class))
+            .collect(),
+        Technology::CpcSubclass => cpcs()
+            .map(|c| format!("{}{}{}", c.section, c.class, c.subclass))
+            .collect(),
+        Technology::CpcGroup => cpcs()
+            .map(|c| format!("{}{}{} {}/00", c.section, c.class, c.subclass, c.main_group))
+            .collect(),
+        Technology::Ipc => dg.classifications_ipcr.iter()
+            .map(|c| format!("{}{}{}", c.section, c.class, c.subclass))
+            .collect(),
+        // e.g. "713168" is class 713, "D 2860" class D2
+        Technology::UspcClass => dg.classification_national.main_uspc().ok()
+            .map(|code| code.class)
+            .into_iter()
+            .collect(),
+        // e.g. "0202" is class 02, subclass 02
+        Technology::LocarnoClass | Technology::LocarnoSubclass => dg.classification_locarno.code().ok()
+            .map(|code| locarno_technology(code, technology))
+            .into_iter()
+            .collect(),
+        Technology::WipoField => field_ids(&derive(dg, &UspcConcordance::default())),
+    };
+
+    unique(codes.into_iter().filter(|code| !code.trim().is_empty()))
+}
+
+/// The grant's locarno code as it is in `edition`. The grant's own edition is used if it
+/// gives one, otherwise the edition in force on the grant date.
+pub fn locarno_code(dg: &BibliographicDataGrant, concordance: &EditionConcordance, edition: u8) -> Option<LocarnoCode> {
+    let code = dg.classification_locarno.code().ok()?;
+    let from = dg.classification_locarno.parsed_edition()
+        .or_else(|| dg.grant_date().ok().and_then(edition_at));
+
+    Some(match from {
+        Some(from) => concordance.map(code, from, edition),
+        None => code,
+    })
+}
+
+fn field_ids(derived: &DerivedTechnology) -> Vec<String> {
+    derived.fields.iter().map(|field| field.id.to_string()).collect()
+}
+
+fn locarno_technology(code: LocarnoCode, technology: Technology) -> String {
+    match technology {
+        Technology::LocarnoClass => code.class_code(),
+        _ => code.to_string(),
+    }
+}
+
+/// Countries of a grant's inventors or assignees, in document order and without repeats.
+/// Raw codes, unless a normalizer is given.
+pub fn countries(dg: &BibliographicDataGrant, country_of: CountryOf, normalizer: Option<&CountryNormalizer>) -> Vec<String> {
+    unique(party_countries(dg, country_of, normalizer).into_iter().flatten())
+}
+
+/// one entry per inventor or assignee, in document order
+fn party_countries(dg: &BibliographicDataGrant, country_of: CountryOf, normalizer: Option<&CountryNormalizer>) -> Vec<Option<String>> {
+    let addresses: Vec<&Address> = match country_of {
+        CountryOf::Inventor => dg.inventors.iter().map(|i| &i.addressbook.address).collect(),
+        CountryOf::Assignee => dg.assignees.iter().map(|a| &a.addressbook.address).collect(),
+    };
+
+    addresses.into_iter()
+        .map(|address| match normalizer {
+            Some(normalizer) => normalizer.address_code(address).map(|code| code.to_owned()),
+            None => address.country.clone(),
+        })
+        .collect()
+}
+
+/// Each country's share of a grant, in document order. Inventors (or assignees) without a
+/// country are left out, so fractional shares are among those with one.
+pub fn country_weights(
+    dg: &BibliographicDataGrant,
+    country_of: CountryOf,
+    counting: Counting,
+    normalizer: Option<&CountryNormalizer>,
+    ) -> Vec<(String, f64)>
+{
+    let parties = party_countries(dg, country_of, normalizer);
+
+    match counting {
+        Counting::Whole => countries(dg, country_of, normalizer).into_iter()
+            .map(|country| (country, 1.0))
+            .collect(),
+        Counting::Fractional => {
+            let located: Vec<String> = parties.into_iter().flatten().collect();
+            let share = 1.0 / located.len() as f64;
+
+            unique(located.iter().cloned()).into_iter()
+                .map(|country| {
+                    let n = located.iter().filter(|c| **c == country).count();
+                    (country, n as f64 * share)
+                })
+                .collect()
+        },
+        Counting::FractionalCountry => shares(countries(dg, country_of, normalizer)),
+        Counting::First => parties.into_iter()
+            .next()
+            .and_then(|country| country)
+            .map(|country| (country, 1.0))
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Each technology's share of a grant, in document order
+pub fn technology_weights(dg: &BibliographicDataGrant, technology: Technology, class_counting: ClassCounting) -> Vec<(String, f64)> {
+    counted(technologies(dg, technology), class_counting)
+}
+
+fn counted(technologies: Vec<String>, class_counting: ClassCounting) -> Vec<(String, f64)> {
+    match class_counting {
+        ClassCounting::Whole => technologies.into_iter().map(|t| (t, 1.0)).collect(),
+        ClassCounting::Fractional => shares(technologies),
+    }
+}
+
+/// equal shares adding up to one
+fn shares(values: Vec<String>) -> Vec<(String, f64)> {
+    let share = 1.0 / values.len() as f64;
+    values.into_iter().map(|v| (v, share)).collect()
+}
+
+fn unique<I: Iterator<Item = String>>(values: I) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    values.filter(|v| seen.insert(v.clone())).collect()
+}
+
+fn year(dg: &BibliographicDataGrant, year_of: YearOf) -> Option<u16> {
+    let date = match year_of {
+        YearOf::Grant => dg.grant_date(),
+        YearOf::Application => dg.application_date(),
+    };
+
+    date.ok().map(|date| date.year() as u16)
+}
+
+/// One row of cube output
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Cell<'a> {
+    pub country: &'a str,
+    pub technology: &'a str,
+    pub year: u16,
+    pub count: f64,
+}
+
+/// A grant's share of one cell; the row-level form of the cube
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Weight {
+    pub doc_number: String,
+    pub country: String,
+    pub technology: String,
+    pub year: u16,
+    pub weight: f64,
+}
+
+/// Streaming country × technology × year counts
+pub struct Cube {
+    technology: Technology,
+    country_of: CountryOf,
+    year_of: YearOf,
+    counting: Counting,
+    class_counting: ClassCounting,
+    normalizer: Option<CountryNormalizer>,
+    locarno: Option<(EditionConcordance, u8)>,
+    uspc: UspcConcordance,
+    regions: Option<(Regions, RegionLevel)>,
+    cells: BTreeMap<(String, String, u16), f64>,
+    grants: u64,
+    skipped: u64,
+}
+
+impl Cube {
+    /// with whole counting; see `with_counting`
+    pub fn new(technology: Technology, country_of: CountryOf, year_of: YearOf) -> Self {
+        Self {
+            technology,
+            country_of,
+            year_of,
+            counting: Counting::Whole,
+            class_counting: ClassCounting::Whole,
+            normalizer: None,
+            locarno: None,
+            uspc: UspcConcordance::default(),
+            regions: None,
+            cells: BTreeMap::new(),
+            grants: 0,
+            skipped: 0,
+        }
+    }
+
+    pub fn with_counting(mut self, counting: Counting, class_counting: ClassCounting) -> Self {
+        self.counting = counting;
+        self.class_counting = class_counting;
+        self
+    }
+
+    /// Countries are normalized to ISO codes instead of kept as they appear in the grant.
+    pub fn with_normalizer(mut self, normalizer: CountryNormalizer) -> Self {
+        self.normalizer = Some(normalizer);
+        self
+    }
+
+    /// Locarno codes are mapped to `edition`, so grants classified under different editions
+    /// are counted together. Only applies to the locarno technologies.
+    pub fn with_locarno_edition(mut self, concordance: EditionConcordance, edition: u8) -> Self {
+        self.locarno = Some((concordance, edition));
+        self
+    }
+
+    /// WIPO fields of grants with neither ipc nor cpc are derived from their uspc
+    /// classification. Only applies to `Technology::WipoField`.
+    pub fn with_uspc_concordance(mut self, concordance: UspcConcordance) -> Self {
+        self.uspc = concordance;
+        self
+    }
+
+    /// The country axis holds the US regions at `level` of inventors (or assignees); grants
+    /// with none in a region aren't counted. The normalizer doesn't apply.
+    pub fn with_regions(mut self, regions: Regions, level: RegionLevel) -> Self {
+        self.regions = Some((regions, level));
+        self
+    }
+
+    /// The cells a grant would add to, with its weight in each. Empty if the grant has
+    /// no country, technology, or year.
+    pub fn weights(&self, pg: &PatentGrant) -> Vec<Weight> {
+        let dg = &pg.us_bibliographic_data_grant;
+
+        let year = match year(dg, self.year_of) {
+            Some(year) => year,
+            None => return Vec::new(),
+        };
+        let technologies = match (self.technology, &self.locarno) {
+            (Technology::LocarnoClass, Some((concordance, edition)))
+            | (Technology::LocarnoSubclass, Some((concordance, edition))) => locarno_code(dg, concordance, *edition)
+                .map(|code| (locarno_technology(code, self.technology), 1.0))
+                .into_iter()
+                .collect(),
+            (Technology::WipoField, _) => counted(field_ids(&derive(dg, &self.uspc)), self.class_counting),
+            _ => technology_weights(dg, self.technology, self.class_counting),
+        };
+
+        let countries = match &self.regions {
+            Some((regions, level)) => regions.weights(dg, self.country_of, self.counting, *level),
+            None => country_weights(dg, self.country_of, self.counting, self.normalizer.as_ref()),
+        };
+
+        countries.into_iter()
+            .flat_map(|(country, country_weight)| {
+                technologies.iter().map(move |(technology, technology_weight)| Weight {
+                    doc_number: dg.publication_reference.doc_number.clone(),
+                    country: country.clone(),
+                    technology: technology.clone(),
+                    year,
+                    weight: country_weight * technology_weight,
+                })
+            })
+            .collect()
+    }
+
+    /// Counts one grant. Grants with no country, technology, or year can't be placed
+    /// in any cell and are only counted in `skipped`.
+    pub fn add(&mut self, pg: &PatentGrant) {
+        self.grants += 1;
+
+        let weights = self.weights(pg);
+        if weights.is_empty() {
+            self.skipped += 1;
+        }
+
+        for w in weights {
+            *self.cells.entry((w.country, w.technology, w.year)).or_insert(0.0) += w.weight;
+        }
+    }
+
+    /// grants seen by `add`, including skipped ones
+    pub fn grants(&self) -> u64 {
+        self.grants
+    }
+
+    /// grants that weren't counted in any cell
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// years with at least one cell, ascending
+    pub fn years(&self) -> Vec<u16> {
+        let years: BTreeSet<u16> = self.cells.keys().map(|(_, _, year)| *year).collect();
+        years.into_iter().collect()
+    }
+
+    /// non-empty cells, sorted by country, technology, year
+    pub fn cells(&self) -> impl Iterator<Item = Cell<'_>> {
+        self.cells.iter().map(|((country, technology, year), count)| Cell {
+            country,
+            technology,
+            year: *year,
+            count: *count,
+        })
+    }
+
+    /// csv with a header row: country,technology,year,count
+    pub fn write_csv<W: Write>(&self, w: W) -> Result<(), Error> {
+        let mut wtr = csv::Writer::from_writer(w);
+
+        for cell in self.cells() {
+            wtr.serialize(cell)
+                .context(WriteCube)?;
+        }
+
+        wtr.flush()
+            .map_err(|err| Error::WriteCube { source: err.into() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestGrant;
+
+    fn grant(date: &str, inventor_countries: &[&str], cpc_subclasses: &[&str]) -> PatentGrant {
+        TestGrant::new().with_date(date).with_inventor_countries(inventor_countries).with_cpc(cpc_subclasses).build()
+    }
+
+    #[test]
+    fn whole_counts() {
+        let mut cube = Cube::new(Technology::CpcSubclass, CountryOf::Inventor, YearOf::Grant);
+        cube.add(&grant("20190101", &["US", "DE", "DE"], &["H04L", "G06F", "H04L"]));
+        cube.add(&grant("20190108", &["DE"], &["H04L"]));
+        cube.add(&grant("20190108", &[], &["H04L"]));
+
+        let cells: Vec<_> = cube.cells().map(|c| (c.country, c.technology, c.year, c.count)).collect();
+        assert_eq!(cells, vec![
+            ("DE", "G06F", 2019, 1.0),
+            ("DE", "H04L", 2019, 2.0),
+            ("US", "G06F", 2019, 1.0),
+            ("US", "H04L", 2019, 1.0),
+        ]);
+        assert_eq!(cube.grants(), 3);
+        assert_eq!(cube.skipped(), 1);
+    }
+
+    #[test]
+    fn fractional_weights() {
+        let pg = grant("20190101", &["US", "DE", "DE"], &["H04L", "G06F"]);
+        let weights = |counting, class_counting| -> Vec<(String, String, f64)> {
+            Cube::new(Technology::CpcSubclass, CountryOf::Inventor, YearOf::Grant)
+                .with_counting(counting, class_counting)
+                .weights(&pg)
+                .into_iter()
+                .map(|w| (w.country, w.technology, (w.weight * 1000.0).round() / 1000.0))
+                .collect()
+        };
+        let row = |country: &str, technology: &str, weight| (country.to_owned(), technology.to_owned(), weight);
+
+        assert_eq!(weights(Counting::Fractional, ClassCounting::Fractional), vec![
+            row("US", "H04L", 0.167),
+            row("US", "G06F", 0.167),
+            row("DE", "H04L", 0.333),
+            row("DE", "G06F", 0.333),
+        ]);
+        assert_eq!(weights(Counting::FractionalCountry, ClassCounting::Whole), vec![
+            row("US", "H04L", 0.5),
+            row("US", "G06F", 0.5),
+            row("DE", "H04L", 0.5),
+            row("DE", "G06F", 0.5),
+        ]);
+        assert_eq!(weights(Counting::First, ClassCounting::Fractional), vec![
+            row("US", "H04L", 0.5),
+            row("US", "G06F", 0.5),
+        ]);
+    }
+}
@@ -1,8 +1,23 @@
 //! data struct definitions for xml data
+//!
+//! All types implement serde `Serialize` and `Deserialize`, so full grants can be dumped to
+//! and reloaded from any serde format without re-parsing the xml. Serialized field names are
+//! the rust field names, and are kept stable.
+//!
+//! Dates are `dates::Date`s, validated when read and serialized as the raw "YYYYMMDD"
+//! strings; the `*_date` methods give the parsed `NaiveDate`s.
 
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Default)]
+use crate::dates::{parse_date, Date};
+use crate::error::Error;
+use crate::locarno::{parse_edition, LocarnoCode};
+use crate::number::{KindCode, PatentNumber};
+use crate::uspc::UspcCode;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PatentGrant {
     pub us_bibliographic_data_grant: BibliographicDataGrant,
 
@@ -20,18 +35,20 @@ pub struct PatentGrant {
     pub claims: Vec<String>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BibliographicDataGrant {
     pub publication_reference: DocumentId,
     pub application_reference: DocumentId,
     pub us_application_series_code: String,
     // TODO: handle disclaimer
     pub us_term_of_grant: String,
+    pub classifications_ipcr: Vec<ClassificationIpcr>,
+    pub classifications_cpc: ClassificationsCpc,
     pub classification_locarno: ClassificationLocarno,
     pub classification_national: ClassificationNational,
     // TODO: handle ID
     pub invention_title: String,
-//    pub us_references_cited: Vec<UsCitation>,
+    pub us_references_cited: Vec<UsCitation>,
     pub number_of_claims: String,
     pub us_exemplary_claim: String,
     pub us_field_of_classification_search: UsFieldOfClassificationSearch,
@@ -47,21 +64,154 @@ pub struct BibliographicDataGrant {
     pub examiners: Examiners,
 }
 
-#[derive(Debug, Default)]
+impl BibliographicDataGrant {
+    pub fn grant_date(&self) -> Result<NaiveDate, Error> {
+        self.publication_reference.parsed_date()
+    }
+
+    pub fn application_date(&self) -> Result<NaiveDate, Error> {
+        self.application_reference.parsed_date()
+    }
+
+    pub fn grant_year(&self) -> Result<i32, Error> {
+        self.grant_date().map(|date| date.year())
+    }
+
+    /// ISO 8601 (year, week) of the grant date. Grants issue on Tuesdays, so this
+    /// identifies the weekly file.
+    pub fn grant_iso_week(&self) -> Result<(i32, u32), Error> {
+        self.grant_date().map(|date| (date.iso_week().year(), date.iso_week().week()))
+    }
+
+    /// days from application filing to grant
+    pub fn pendency_days(&self) -> Result<i64, Error> {
+        Ok((self.grant_date()? - self.application_date()?).num_days())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DocumentId {
     pub country: String,
     pub doc_number: String,
     pub kind: Option<String>,
-    pub date: String,
+    // only in citations, e.g. name of first inventor
+    pub name: Option<String>,
+    pub date: Date,
+}
+
+impl DocumentId {
+    pub fn parsed_date(&self) -> Result<NaiveDate, Error> {
+        self.date.parsed()
+    }
+
+    /// Parsed `doc_number`. Only meaningful for US documents; citations of foreign
+    /// documents will usually fail to parse.
+    pub fn patent_number(&self) -> Result<PatentNumber, Error> {
+        self.doc_number.parse()
+    }
+
+    pub fn kind_code(&self) -> Option<KindCode> {
+        self.kind.as_ref().and_then(|kind| kind.parse().ok())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationIpcr {
+    pub ipc_version_indicator: String, // date
+    pub classification_level: Option<String>,
+    pub section: String,
+    pub class: String,
+    pub subclass: String,
+    pub main_group: Option<String>,
+    pub subgroup: Option<String>,
+    pub symbol_position: Option<String>,
+    pub classification_value: Option<String>,
+    pub action_date: Option<Date>,
+    pub generating_office: Option<String>, // country
+    pub classification_status: Option<String>,
+    pub classification_data_source: Option<String>,
+}
+
+impl ClassificationIpcr {
+    pub fn version_date(&self) -> Result<NaiveDate, Error> {
+        parse_date(&self.ipc_version_indicator)
+    }
+
+    /// None if there's no action date
+    pub fn parsed_action_date(&self) -> Option<Result<NaiveDate, Error>> {
+        self.action_date.as_ref().map(|date| date.parsed())
+    }
+
+    /// e.g. "H04L 9/32"; just the subclass if there's no group
+    pub fn symbol(&self) -> String {
+        class_symbol(&self.section, &self.class, &self.subclass, self.main_group.as_deref(), self.subgroup.as_deref())
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationsCpc {
+    pub main_cpc: Option<ClassificationCpc>,
+    // TODO: handle combination-set
+    pub further_cpc: Vec<ClassificationCpc>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClassificationCpc {
+    pub cpc_version_indicator: String, // date
+    pub section: String,
+    pub class: String,
+    pub subclass: String,
+    pub main_group: String,
+    pub subgroup: String,
+    pub symbol_position: String,
+    pub classification_value: String,
+    pub action_date: Date,
+    pub generating_office: String, // country
+    pub classification_status: Option<String>,
+    pub classification_data_source: Option<String>,
+    pub scheme_origination_code: Option<String>,
+}
+
+impl ClassificationCpc {
+    pub fn version_date(&self) -> Result<NaiveDate, Error> {
+        parse_date(&self.cpc_version_indicator)
+    }
+
+    pub fn parsed_action_date(&self) -> Result<NaiveDate, Error> {
+        self.action_date.parsed()
+    }
+
+    /// e.g. "H04L 9/32"
+    pub fn symbol(&self) -> String {
+        class_symbol(&self.section, &self.class, &self.subclass, Some(&self.main_group), Some(&self.subgroup))
+    }
 }
 
-#[derive(Debug, Default)]
+fn class_symbol(section: &str, class: &str, subclass: &str, main_group: Option<&str>, subgroup: Option<&str>) -> String {
+    match (main_group, subgroup) {
+        (Some(main_group), Some(subgroup)) => format!("{}{}{} {}/{}", section, class, subclass, main_group, subgroup),
+        (Some(main_group), None) => format!("{}{}{} {}", section, class, subclass, main_group),
+        _ => format!("{}{}{}", section, class, subclass),
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ClassificationLocarno {
     pub edition: String,
     pub main_classification: String,
 }
 
-#[derive(Debug, Default)]
+impl ClassificationLocarno {
+    pub fn code(&self) -> Result<LocarnoCode, Error> {
+        self.main_classification.parse()
+    }
+
+    pub fn parsed_edition(&self) -> Option<u8> {
+        parse_edition(&self.edition)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ClassificationNational {
     pub country: String,
     pub additional_info: String,
@@ -69,20 +219,41 @@ pub struct ClassificationNational {
     pub further_classification: Option<String>,
 }
 
+impl ClassificationNational {
+    pub fn main_uspc(&self) -> Result<UspcCode, Error> {
+        UspcCode::from_fixed_width(&self.main_classification)
+    }
+
+    pub fn further_uspc(&self) -> Option<Result<UspcCode, Error>> {
+        self.further_classification.as_ref().map(|raw| UspcCode::from_fixed_width(raw))
+    }
+}
+
+/// One entry in us-references-cited; either a patent or a non-patent literature citation.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsCitation {
+    pub sequence: String, // num attr of patcit or nplcit
+    pub patcit: Option<DocumentId>,
+    pub nplcit: Option<String>,
+    pub category: Option<String>,
+    pub classification_cpc_text: Option<String>,
+    pub classification_national: Option<ClassificationNational>,
+}
+
 //#[derive(Debug, Default)]
 //pub struct InventionTitle {
 //    pub id: String,
 //    pub title: String,
 //}
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UsFieldOfClassificationSearch {
     pub classification_nationals: Vec<ClassificationNational>,
     pub classification_cpc_texts: Vec<String>,
     pub classification_cpc_combination_texts: Vec<String>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UsApplicant {
     pub sequence: String,
     pub app_type: String,
@@ -92,7 +263,7 @@ pub struct UsApplicant {
     pub residence: Option<String>, // Country
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AddressBook {
     pub orgname: Option<String>,
     pub first_name: Option<String>,
@@ -101,44 +272,78 @@ pub struct AddressBook {
     pub address: Address,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Address {
     pub city: Option<String>,
     pub state: Option<String>,
     pub country: Option<String>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Inventor {
     pub sequence: String,
     pub designation: String,
     pub addressbook: AddressBook,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Agent {
     pub sequence: String,
     pub rep_type: String,
     pub addressbook: AddressBook,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Assignee {
     pub orgname: Option<String>,
     pub role: Option<String>,
     pub addressbook: AddressBook,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Examiners {
     pub primary_examiner: Examiner,
     pub assistant_examiner: Examiner,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Examiner {
     pub first_name: String,
     pub last_name: String,
     pub department: Option<String>,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let mut grant = PatentGrant::default();
+        grant.us_bibliographic_data_grant.publication_reference = DocumentId {
+            country: "US".to_owned(),
+            doc_number: "D0845432".to_owned(),
+            kind: Some("S1".to_owned()),
+            name: None,
+            date: "20190409".into(),
+        };
+        grant.us_bibliographic_data_grant.inventors.push(Inventor {
+            sequence: "001".to_owned(),
+            designation: "us-only".to_owned(),
+            addressbook: AddressBook {
+                first_name: Some("Rene".to_owned()),
+                address: Address { country: Some("FR".to_owned()), ..Address::default() },
+                ..AddressBook::default()
+            },
+        });
+        grant.descriptions.insert("BRFSUM".to_owned(), "summary".to_owned());
+        grant.claims.push("1. A claim.".to_owned());
+
+        let json = serde_json::to_string(&grant).unwrap();
+        assert!(json.contains(r#""doc_number":"D0845432""#));
+
+        let reloaded: PatentGrant = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded, grant);
+    }
+}
@@ -0,0 +1,114 @@
+//! Parsing of "YYYYMMDD" dates from grant xml.
+//!
+//! `Date` keeps the raw string, so nothing is lost on serialization, next to its value parsed
+//! once when the grant is read. Older data sometimes has "00" for an unknown day or month
+//! (e.g. "19870500"); those parse to the first day of the month, or January, so they still
+//! sort and bucket by year correctly.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::error::Error;
+
+/// A date as published, and its parsed value if it's valid.
+///
+/// Dates compare by parsed value; invalid ones sort first, among themselves by raw string.
+/// Serialized as the raw string.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct Date {
+    raw: String,
+    parsed: Option<NaiveDate>,
+}
+
+impl Date {
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+
+    /// None if the raw date is empty or invalid
+    pub fn naive(&self) -> Option<NaiveDate> {
+        self.parsed
+    }
+
+    pub fn parsed(&self) -> Result<NaiveDate, Error> {
+        self.parsed.ok_or_else(|| Error::InvalidDate { raw: self.raw.clone() })
+    }
+}
+
+impl From<String> for Date {
+    fn from(raw: String) -> Self {
+        let parsed = parse_date(&raw).ok();
+        Self { raw, parsed }
+    }
+}
+
+impl From<&str> for Date {
+    fn from(raw: &str) -> Self {
+        Self::from(raw.to_owned())
+    }
+}
+
+impl From<Date> for String {
+    fn from(date: Date) -> Self {
+        date.raw
+    }
+}
+
+impl Ord for Date {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.parsed, &self.raw).cmp(&(other.parsed, &other.raw))
+    }
+}
+
+impl PartialOrd for Date {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+pub fn parse_date(raw: &str) -> Result<NaiveDate, Error> {
+    let invalid = || Error::InvalidDate { raw: raw.to_owned() };
+
+    let raw_trimmed = raw.trim();
+    if raw_trimmed.len() != 8 || !raw_trimmed.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    let year: i32 = raw_trimmed[..4].parse().map_err(|_| invalid())?;
+    let month: u32 = raw_trimmed[4..6].parse().map_err(|_| invalid())?;
+    let day: u32 = raw_trimmed[6..].parse().map_err(|_| invalid())?;
+
+    NaiveDate::from_ymd_opt(year, month.max(1), day.max(1))
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dates() {
+        assert_eq!(parse_date("20190101").unwrap(), NaiveDate::from_ymd_opt(2019, 1, 1).unwrap());
+        assert_eq!(parse_date("19870500").unwrap(), NaiveDate::from_ymd_opt(1987, 5, 1).unwrap());
+        assert_eq!(parse_date("19870000").unwrap(), NaiveDate::from_ymd_opt(1987, 1, 1).unwrap());
+
+        assert!(parse_date("20190230").is_err());
+        assert!(parse_date("2019011").is_err());
+        assert!(parse_date("2019-01-01").is_err());
+        assert!(parse_date("").is_err());
+
+        let mut dates: Vec<Date> = vec!["20190101".into(), "19870500".into(), "2019011".into()];
+        dates.sort();
+        assert_eq!(dates.iter().map(|d| d.raw()).collect::<Vec<_>>(), vec!["2019011", "19870500", "20190101"]);
+        assert!(dates[0].parsed().is_err());
+    }
+}
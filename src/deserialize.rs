@@ -1,5 +1,6 @@
 use quick_xml::{self, Reader};
 use quick_xml::events::{Event, BytesText};
+use quick_xml::events::attributes::Attribute;
 use snafu::OptionExt;
 use std::io::BufRead;
 
@@ -8,7 +9,7 @@ use crate::error::Error;
 use crate::error::Deser;
 // helper macros
 use crate::{try_some, parse_struct_update, parse_struct_update_from};
-use crate::util::{consume_start, skip_to_tag_within};
+use crate::util::{consume_start, decode_latin1_value, skip_to_tag_within};
 
 pub struct PatentGrants<B: BufRead> {
     rdr: quick_xml::Reader<B>,
@@ -234,12 +235,10 @@ fn deser_biblio<B: BufRead>(
                         }
                     },
                     b"classifications-ipcr" => {
-                        // TODO skip for now
-                        skip_to_tag_within(b"", b"classifications-ipcr", rdr, buf)?;
+                        deser_classes_ipcr(rdr, buf, &mut biblio.classifications_ipcr)?;
                     },
                     b"classifications-cpc" => {
-                        // TODO skip for now
-                        skip_to_tag_within(b"", b"classifications-cpc", rdr, buf)?;
+                        deser_classes_cpc(rdr, buf, &mut biblio.classifications_cpc)?;
                     },
                     b"classification-locarno" => {
                         deser_class_locarno(rdr, buf, &mut biblio.classification_locarno)?;
@@ -250,6 +249,9 @@ fn deser_biblio<B: BufRead>(
                     b"invention-title" => {
                         biblio.invention_title = deser_text_with_tags_from(e.name(), rdr)?;
                     },
+                    b"us-references-cited" => {
+                        deser_us_references_cited(rdr, buf, &mut biblio.us_references_cited)?;
+                    },
                     b"number-of-claims" => {
                         biblio.number_of_claims = deser_text_from(e.name(), rdr)?;
                     },
@@ -300,7 +302,8 @@ fn deser_biblio<B: BufRead>(
 ///     pub country: String,
 ///     pub doc_number: String,
 ///     pub kind: Option<String>,
-///     pub date: String,
+///     pub name: Option<String>,
+///     pub date: Date,
 /// }
 fn deser_doc_id<B: BufRead>(rdr: &mut quick_xml::Reader<B>, buf: &mut Vec<u8>, doc_id: &mut DocumentId) -> Result<(), Error> {
     parse_struct_update!(
@@ -317,12 +320,354 @@ fn deser_doc_id<B: BufRead>(rdr: &mut quick_xml::Reader<B>, buf: &mut Vec<u8>, d
         // Option
         {
             b"kind" => kind,
+            b"name" => name,
         }
     );
 
     Ok(())
 }
 
+/// Deserializes a Vec of ClassificationIpcr
+///
+/// called after tag classifications-ipcr is already hit
+fn deser_classes_ipcr<B: BufRead>(
+    rdr: &mut quick_xml::Reader<B>,
+    buf: &mut Vec<u8>,
+    classes_ipcr: &mut Vec<ClassificationIpcr>,
+    ) -> Result<(), Error>
+{
+    loop {
+        match rdr.read_event(buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"classification-ipcr" => {
+                        let mut class_ipcr = ClassificationIpcr::default();
+
+                        deser_class_ipcr(rdr, buf, &mut class_ipcr)?;
+                        classes_ipcr.push(class_ipcr);
+                    },
+                    _ => return Err(Error::Deser { src: format!("found element {:?}, not classification-ipcr", std::str::from_utf8(e.name())) }),
+                }
+            },
+            Ok(Event::End(e)) => {
+                if e.name() == "classifications-ipcr".as_bytes() {
+                    break;
+                } else {
+                    continue;
+                }
+            },
+            Ok(_) => return Err(Error::Deser { src: "found non-start-element besides classifications-ipcr".to_string() }),
+
+            Err(err) => return Err(Error::Deser { src: err.to_string() }),
+        }
+    }
+
+    Ok(())
+}
+
+/// pub struct ClassificationIpcr {
+///     pub ipc_version_indicator: String,
+///     pub section: String,
+///     pub class: String,
+///     pub subclass: String,
+///     ...
+/// }
+///
+/// version indicator, action date, and generating office are unwrapped
+/// from their nested date and country elements.
+///
+/// called after tag classification-ipcr is already hit
+fn deser_class_ipcr<B: BufRead>(
+    rdr: &mut quick_xml::Reader<B>,
+    buf: &mut Vec<u8>,
+    class_ipcr: &mut ClassificationIpcr,
+    ) -> Result<(), Error>
+{
+    loop {
+        match rdr.read_event(buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"ipc-version-indicator" => class_ipcr.ipc_version_indicator = deser_text(b"date", rdr)?,
+                    b"classification-level" => class_ipcr.classification_level = Some(deser_text_from(e.name(), rdr)?),
+                    b"section" => class_ipcr.section = deser_text_from(e.name(), rdr)?,
+                    b"class" => class_ipcr.class = deser_text_from(e.name(), rdr)?,
+                    b"subclass" => class_ipcr.subclass = deser_text_from(e.name(), rdr)?,
+                    b"main-group" => class_ipcr.main_group = Some(deser_text_from(e.name(), rdr)?),
+                    b"subgroup" => class_ipcr.subgroup = Some(deser_text_from(e.name(), rdr)?),
+                    b"symbol-position" => class_ipcr.symbol_position = Some(deser_text_from(e.name(), rdr)?),
+                    b"classification-value" => class_ipcr.classification_value = Some(deser_text_from(e.name(), rdr)?),
+                    b"action-date" => class_ipcr.action_date = Some(deser_text(b"date", rdr)?.into()),
+                    b"generating-office" => class_ipcr.generating_office = Some(deser_text(b"country", rdr)?),
+                    b"classification-status" => class_ipcr.classification_status = Some(deser_text_from(e.name(), rdr)?),
+                    b"classification-data-source" => class_ipcr.classification_data_source = Some(deser_text_from(e.name(), rdr)?),
+                    _ => return Err(Error::Deser { src: format!("unrecognized element {:?} in classification-ipcr", std::str::from_utf8(e.name())) }),
+                }
+            },
+            Ok(Event::End(e)) => {
+                if e.name() == "classification-ipcr".as_bytes() {
+                    break;
+                } else {
+                    continue;
+                }
+            },
+            Ok(e) => return Err(Error::Deser { src: format!("found non-start-element {:?} besides classification-ipcr", e) }),
+
+            Err(err) => return Err(Error::Deser { src: err.to_string() }),
+        }
+    }
+
+    Ok(())
+}
+
+/// pub struct ClassificationsCpc {
+///     pub main_cpc: Option<ClassificationCpc>,
+///     pub further_cpc: Vec<ClassificationCpc>,
+/// }
+///
+/// called after tag classifications-cpc is already hit
+fn deser_classes_cpc<B: BufRead>(
+    rdr: &mut quick_xml::Reader<B>,
+    buf: &mut Vec<u8>,
+    classes_cpc: &mut ClassificationsCpc,
+    ) -> Result<(), Error>
+{
+    // whether the classification-cpc tags are under main-cpc or further-cpc
+    let mut in_main = true;
+
+    loop {
+        match rdr.read_event(buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"main-cpc" => in_main = true,
+                    b"further-cpc" => in_main = false,
+                    b"classification-cpc" => {
+                        let mut class_cpc = ClassificationCpc::default();
+
+                        deser_class_cpc(rdr, buf, &mut class_cpc)?;
+                        if in_main {
+                            classes_cpc.main_cpc = Some(class_cpc);
+                        } else {
+                            classes_cpc.further_cpc.push(class_cpc);
+                        }
+                    },
+                    b"combination-set" => {
+                        // TODO skip for now
+                        skip_to_tag_within(b"", b"combination-set", rdr, buf)?;
+                    },
+                    _ => return Err(Error::Deser { src: format!("found element {:?}, not expected in classifications-cpc", std::str::from_utf8(e.name())) }),
+                }
+            },
+            Ok(Event::End(e)) => {
+                if e.name() == "classifications-cpc".as_bytes() {
+                    break;
+                } else {
+                    continue;
+                }
+            },
+            Ok(_) => return Err(Error::Deser { src: "found non-start-element besides classifications-cpc".to_string() }),
+
+            Err(err) => return Err(Error::Deser { src: err.to_string() }),
+        }
+    }
+
+    Ok(())
+}
+
+/// pub struct ClassificationCpc {
+///     pub cpc_version_indicator: String,
+///     pub section: String,
+///     pub class: String,
+///     pub subclass: String,
+///     pub main_group: String,
+///     pub subgroup: String,
+///     ...
+/// }
+///
+/// called after tag classification-cpc is already hit
+fn deser_class_cpc<B: BufRead>(
+    rdr: &mut quick_xml::Reader<B>,
+    buf: &mut Vec<u8>,
+    class_cpc: &mut ClassificationCpc,
+    ) -> Result<(), Error>
+{
+    loop {
+        match rdr.read_event(buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"cpc-version-indicator" => class_cpc.cpc_version_indicator = deser_text(b"date", rdr)?,
+                    b"section" => class_cpc.section = deser_text_from(e.name(), rdr)?,
+                    b"class" => class_cpc.class = deser_text_from(e.name(), rdr)?,
+                    b"subclass" => class_cpc.subclass = deser_text_from(e.name(), rdr)?,
+                    b"main-group" => class_cpc.main_group = deser_text_from(e.name(), rdr)?,
+                    b"subgroup" => class_cpc.subgroup = deser_text_from(e.name(), rdr)?,
+                    b"symbol-position" => class_cpc.symbol_position = deser_text_from(e.name(), rdr)?,
+                    b"classification-value" => class_cpc.classification_value = deser_text_from(e.name(), rdr)?,
+                    b"action-date" => class_cpc.action_date = deser_text(b"date", rdr)?.into(),
+                    b"generating-office" => class_cpc.generating_office = deser_text(b"country", rdr)?,
+                    b"classification-status" => class_cpc.classification_status = Some(deser_text_from(e.name(), rdr)?),
+                    b"classification-data-source" => class_cpc.classification_data_source = Some(deser_text_from(e.name(), rdr)?),
+                    b"scheme-origination-code" => class_cpc.scheme_origination_code = Some(deser_text_from(e.name(), rdr)?),
+                    _ => return Err(Error::Deser { src: format!("unrecognized element {:?} in classification-cpc", std::str::from_utf8(e.name())) }),
+                }
+            },
+            Ok(Event::End(e)) => {
+                if e.name() == "classification-cpc".as_bytes() {
+                    break;
+                } else {
+                    continue;
+                }
+            },
+            Ok(e) => return Err(Error::Deser { src: format!("found non-start-element {:?} besides classification-cpc", e) }),
+
+            Err(err) => return Err(Error::Deser { src: err.to_string() }),
+        }
+    }
+
+    Ok(())
+}
+
+/// Deserializes a Vec of UsCitation
+///
+/// Search report details after the citations (date-search-completed etc.) are skipped.
+///
+/// called after tag us-references-cited is already hit
+fn deser_us_references_cited<B: BufRead>(
+    rdr: &mut quick_xml::Reader<B>,
+    buf: &mut Vec<u8>,
+    citations: &mut Vec<UsCitation>,
+    ) -> Result<(), Error>
+{
+    loop {
+        match rdr.read_event(buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"us-citation" => {
+                        let mut citation = UsCitation::default();
+
+                        deser_us_citation(rdr, buf, &mut citation)?;
+                        citations.push(citation);
+                    },
+                    _ => {
+                        let name = e.name().to_vec();
+                        skip_to_tag_within(b"", &name, rdr, buf)?;
+                    },
+                }
+            },
+            Ok(Event::End(e)) => {
+                if e.name() == "us-references-cited".as_bytes() {
+                    break;
+                } else {
+                    continue;
+                }
+            },
+            Ok(_) => continue,
+
+            Err(err) => return Err(Error::Deser { src: err.to_string() }),
+        }
+    }
+
+    Ok(())
+}
+
+/// pub struct UsCitation {
+///     pub sequence: String,
+///     pub patcit: Option<DocumentId>,
+///     pub nplcit: Option<String>,
+///     pub category: Option<String>,
+///     pub classification_cpc_text: Option<String>,
+///     pub classification_national: Option<ClassificationNational>,
+/// }
+///
+/// called after tag us-citation is already hit
+fn deser_us_citation<B: BufRead>(
+    rdr: &mut quick_xml::Reader<B>,
+    buf: &mut Vec<u8>,
+    citation: &mut UsCitation,
+    ) -> Result<(), Error>
+{
+    loop {
+        match rdr.read_event(buf) {
+            Ok(Event::Start(ref e)) => {
+                match e.name() {
+                    b"patcit" | b"nplcit" => {
+                        for attr_res in e.attributes() {
+                            let attr = attr_res
+                                .map_err(|err| Error::Deser { src: err.to_string() })?;
+
+                            if attr.key == b"num" {
+                                citation.sequence = deser_attr_value(&attr, rdr)?;
+                            }
+                        }
+                    },
+                    b"document-id" => {
+                        let mut doc_id = DocumentId::default();
+
+                        parse_struct_update_from!(
+                            rdr,
+                            buf,
+                            "document-id",
+                            doc_id,
+                            // Required
+                            {
+                                b"country" => country,
+                                b"doc-number" => doc_number,
+                                b"date" => date,
+                            },
+                            // Optional
+                            {
+                                b"kind" => kind,
+                                b"name" => name,
+                            }
+                        );
+                        citation.patcit = Some(doc_id);
+                    },
+                    b"othercit" => citation.nplcit = Some(deser_text_with_tags_from(e.name(), rdr)?),
+                    b"category" => citation.category = Some(deser_text_from(e.name(), rdr)?),
+                    b"classification-cpc-text" => citation.classification_cpc_text = Some(deser_text_from(e.name(), rdr)?),
+                    b"classification-national" => {
+                        let mut class_national = ClassificationNational::default();
+
+                        parse_struct_update_from!(
+                            rdr,
+                            buf,
+                            "classification-national",
+                            class_national,
+                            // Required
+                            {
+                                b"country" => country,
+                                b"additional-info" => additional_info,
+                                b"main-classification" => main_classification,
+                            },
+                            // Optional
+                            {
+                                b"further-classification" => further_classification,
+                            }
+                        );
+                        citation.classification_national = Some(class_national);
+                    },
+                    // rel-passage, text, etc.
+                    _ => {
+                        let name = e.name().to_vec();
+                        skip_to_tag_within(b"", &name, rdr, buf)?;
+                    },
+                }
+            },
+            Ok(Event::End(e)) => {
+                if e.name() == "us-citation".as_bytes() {
+                    break;
+                } else {
+                    continue;
+                }
+            },
+            Ok(_) => continue,
+
+            Err(err) => return Err(Error::Deser { src: err.to_string() }),
+        }
+    }
+
+    Ok(())
+}
+
 /// pub struct ClassificationLocarno {
 ///     pub edition: String,
 ///     pub main_classification: String,
@@ -480,10 +825,10 @@ fn deser_us_applicants<B: BufRead>(
                                 .map_err(|err| Error::Deser { src: err.to_string() })?;
 
                             match attr.key {
-                                b"sequence" => applicant.sequence = attr.unescape_and_decode_value(rdr).expect("never fail utf8?"),
-                                b"app-type" => applicant.app_type = attr.unescape_and_decode_value(rdr).expect("never fail utf8?"),
-                                b"designation" => applicant.designation = attr.unescape_and_decode_value(rdr).expect("never fail utf8?"),
-                                b"applicant-authority-category" => applicant.applicant_authority_category = Some(attr.unescape_and_decode_value(rdr).expect("never fail utf8?")),
+                                b"sequence" => applicant.sequence = deser_attr_value(&attr, rdr)?,
+                                b"app-type" => applicant.app_type = deser_attr_value(&attr, rdr)?,
+                                b"designation" => applicant.designation = deser_attr_value(&attr, rdr)?,
+                                b"applicant-authority-category" => applicant.applicant_authority_category = Some(deser_attr_value(&attr, rdr)?),
                                 _ => return Err(Error::Deser { src: format!("unrecognized attr in us-applicant") }),
                             }
                         }
@@ -546,8 +891,8 @@ fn deser_inventors<B: BufRead>(
                                 .map_err(|err| Error::Deser { src: err.to_string() })?;
 
                             match attr.key {
-                                b"sequence" => inventor.sequence = attr.unescape_and_decode_value(rdr).expect("never fail utf8?"),
-                                b"designation" => inventor.designation = attr.unescape_and_decode_value(rdr).expect("never fail utf8?"),
+                                b"sequence" => inventor.sequence = deser_attr_value(&attr, rdr)?,
+                                b"designation" => inventor.designation = deser_attr_value(&attr, rdr)?,
                                 _ => return Err(Error::Deser { src: format!("unrecognized attr in inventor") }),
                             }
                         }
@@ -605,8 +950,8 @@ fn deser_agents<B: BufRead>(
                                 .map_err(|err| Error::Deser { src: err.to_string() })?;
 
                             match attr.key {
-                                b"sequence" => agent.sequence = attr.unescape_and_decode_value(rdr).expect("never fail utf8?"),
-                                b"rep-type" => agent.rep_type = attr.unescape_and_decode_value(rdr).expect("never fail utf8?"),
+                                b"sequence" => agent.sequence = deser_attr_value(&attr, rdr)?,
+                                b"rep-type" => agent.rep_type = deser_attr_value(&attr, rdr)?,
                                 _ => return Err(Error::Deser { src: format!("unrecognized attr in agent") }),
                             }
                         }
@@ -862,6 +1207,21 @@ fn deser_examiners<B: BufRead>(
     Ok(())
 }
 
+/// decodes an attribute value.
+///
+/// Falls back to latin-1 decoding and iso-latin-1 entities when quick-xml can't handle the
+/// value; anything still undecodable is an error, never a panic.
+///
+/// Invalid utf-8 goes straight to the fallback, since quick-xml decodes it lossily (to
+/// U+FFFD) rather than failing.
+fn deser_attr_value<B: BufRead>(attr: &Attribute, rdr: &quick_xml::Reader<B>) -> Result<String, Error> {
+    if std::str::from_utf8(&attr.value).is_err() {
+        return decode_latin1_value(&attr.value);
+    }
+    attr.unescape_and_decode_value(rdr)
+        .or_else(|_| decode_latin1_value(&attr.value))
+}
+
 /// call when the start tag has already been consumed, now you need the text to the end tag
 fn deser_text_from<B: BufRead, K: AsRef<[u8]>>(end: K, rdr: &mut quick_xml::Reader<B>) -> Result<String, Error> {
     match rdr.read_text(end, &mut Vec::new()) {
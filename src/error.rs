@@ -22,5 +22,37 @@ pub enum Error {
     Fetch{ source: reqwest::Error },
     #[snafu(display("Fetch Create File Error: {}", source))]
     CreateFile{ source: std::io::Error },
+    #[snafu(display("Invalid Date Error: {:?}", raw))]
+    InvalidDate{ raw: String },
+    #[snafu(display("Invalid Patent Number Error: {:?}", raw))]
+    InvalidPatentNumber{ raw: String },
+    #[snafu(display("Invalid USPC Code Error: {:?}", raw))]
+    InvalidUspc{ raw: String },
+    #[snafu(display("Invalid Locarno Code Error: {:?}", raw))]
+    InvalidLocarno{ raw: String },
+    #[snafu(display("Invalid Locarno Edition Error: {} has no next edition", edition))]
+    InvalidLocarnoEdition{ edition: u8 },
+    #[snafu(display("Invalid Classification Symbol Error: {:?}", raw))]
+    InvalidSymbol{ raw: String },
+    #[snafu(display("Invalid Gazetteer Row Error: line {}", line))]
+    InvalidGazetteer{ line: u64 },
+    #[snafu(display("Unknown Country Error: {}", code))]
+    UnknownCountry{ code: String },
+    #[snafu(display("Unknown Column Error: {}; available columns are {}", name, available))]
+    UnknownColumn{ name: String, available: String },
+    #[snafu(display("Read Table Error: {}", source))]
+    ReadTable{ source: csv::Error },
+    #[snafu(display("Write Table Error: {}", source))]
+    WriteTable{ source: csv::Error },
+    #[snafu(display("Write Cube Error: {}", source))]
+    WriteCube{ source: csv::Error },
+    #[snafu(display("Write Network Error: {}", source))]
+    WriteNetwork{ source: std::io::Error },
+    #[snafu(display("Write Postgres Copy Error: {}", source))]
+    WritePgCopy{ source: std::io::Error },
+    #[snafu(display("Columnar Output Error: {}", src))]
+    Columnar{ src: String },
+    #[snafu(display("Sqlite Export Error: {}", src))]
+    Sqlite{ src: String },
 }
 
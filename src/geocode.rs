@@ -0,0 +1,408 @@
+//! Offline geocoding of party addresses against a GeoNames gazetteer.
+//!
+//! Nothing is bundled: load a GeoNames dump from disk (`allCountries.txt`, a country file like
+//! `US.txt`, or a smaller `cities15000.txt`; all share one tab-separated format), and
+//! optionally `admin1CodesASCII.txt` and `admin2Codes.txt` for region names. Only populated
+//! places (feature class P) are kept.
+//!
+//! A city is looked up within its country by normalized name: the place's name first, then
+//! its alternate names, then the most similar name (Jaro-Winkler) starting with the same
+//! letter. A state narrows the candidates to places whose admin1 code or name matches it (for
+//! US addresses, admin1 codes are the postal codes grants use). Among equally good
+//! candidates the most populous wins.
+//!
+//! Confidence is 1 for an exact name match in the given state, and lower for alternate names
+//! (x0.9), fuzzy matches (x0.8 x similarity), addresses without a state (x0.9) and states
+//! that matched no candidate (x0.6).
+
+use serde::Serialize;
+use snafu::ResultExt;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Read;
+
+use crate::country::{CountryCode, CountryNormalizer, SuccessorPolicy};
+use crate::data::{Address, PatentGrant};
+use crate::error::{Error, ReadTable};
+use crate::matching::{fold, jaro_winkler};
+
+const ALTERNATE: f64 = 0.9;
+const FUZZY: f64 = 0.8;
+const NO_STATE: f64 = 0.9;
+const STATE_MISMATCH: f64 = 0.6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub geonameid: u64,
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// ISO alpha-2
+    pub country: String,
+    pub admin1: String,
+    pub admin2: String,
+    pub population: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    Name,
+    Alternate,
+    Fuzzy,
+}
+
+impl fmt::Display for MatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            MatchKind::Name => "name",
+            MatchKind::Alternate => "alternate",
+            MatchKind::Fuzzy => "fuzzy",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A resolved address
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location<'a> {
+    pub place: &'a Place,
+    pub admin1_name: Option<&'a str>,
+    pub admin2_name: Option<&'a str>,
+    pub kind: MatchKind,
+    /// 0 to 1; see the module docs
+    pub confidence: f64,
+}
+
+/// Upper case ascii letters and digits separated by single spaces; None for names with
+/// letters that don't fold to ascii, which grants never spell places in.
+pub(crate) fn normalize(raw: &str) -> Option<String> {
+    let mut cleaned = String::with_capacity(raw.len());
+    for c in raw.chars().map(fold) {
+        match c {
+            '.' | '\'' => (),
+            c if c.is_ascii_alphanumeric() => cleaned.push(c),
+            c if c.is_alphabetic() => return None,
+            _ => cleaned.push(' '),
+        }
+    }
+
+    let normalized = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    if normalized.is_empty() {
+        None
+    } else {
+        Some(normalized)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    place: usize,
+    alternate: bool,
+}
+
+pub struct Gazetteer {
+    places: Vec<Place>,
+    // country, then normalized name
+    names: HashMap<String, BTreeMap<String, Vec<Entry>>>,
+    // "US.CA", "US.CA.085"
+    admin1_names: HashMap<String, String>,
+    admin2_names: HashMap<String, String>,
+    countries: CountryNormalizer,
+    min_similarity: f64,
+}
+
+impl Gazetteer {
+    /// GeoNames main table, tab-separated without a header
+    pub fn from_geonames<R: Read>(r: R) -> Result<Self, Error> {
+        let mut gazetteer = Self {
+            places: Vec::new(),
+            names: HashMap::new(),
+            admin1_names: HashMap::new(),
+            admin2_names: HashMap::new(),
+            countries: CountryNormalizer::new(CountryCode::Alpha2, SuccessorPolicy::Successor),
+            min_similarity: 0.9,
+        };
+
+        for record in tab_separated(r).records() {
+            let record = record.context(ReadTable)?;
+            if record.get(6) != Some("P") {
+                continue;
+            }
+            let line = record.position().map_or(0, |p| p.line());
+            let field = |i: usize| record.get(i).unwrap_or("");
+            let invalid = || Error::InvalidGazetteer { line };
+            let coordinate = |i: usize| field(i).parse::<f64>().map_err(|_| invalid());
+
+            gazetteer.insert(
+                Place {
+                    geonameid: field(0).parse().map_err(|_| invalid())?,
+                    name: field(1).to_owned(),
+                    latitude: coordinate(4)?,
+                    longitude: coordinate(5)?,
+                    country: field(8).to_owned(),
+                    admin1: field(10).to_owned(),
+                    admin2: field(11).to_owned(),
+                    population: field(14).parse().unwrap_or(0),
+                },
+                field(2).split(',').chain(field(3).split(',')),
+            );
+        }
+
+        Ok(gazetteer)
+    }
+
+    /// Adds a place, found by its name and `alternate_names`
+    pub fn insert<'a, I>(&mut self, place: Place, alternate_names: I)
+        where I: IntoIterator<Item = &'a str>,
+    {
+        let index = self.places.len();
+        let names = self.names.entry(place.country.clone()).or_default();
+
+        let name = normalize(&place.name);
+        if let Some(name) = &name {
+            names.entry(name.clone()).or_default().push(Entry { place: index, alternate: false });
+        }
+        for alternate in alternate_names.into_iter().filter_map(normalize) {
+            if Some(&alternate) == name.as_ref() {
+                continue;
+            }
+            let entries = names.entry(alternate).or_default();
+            if !entries.iter().any(|e| e.place == index) {
+                entries.push(Entry { place: index, alternate: true });
+            }
+        }
+
+        self.places.push(place);
+    }
+
+    /// admin1CodesASCII.txt: code ("US.CA"), name, ascii name, geonameid
+    pub fn with_admin1_names<R: Read>(mut self, r: R) -> Result<Self, Error> {
+        self.admin1_names = admin_names(r)?;
+        Ok(self)
+    }
+
+    /// admin2Codes.txt: code ("US.CA.085"), name, ascii name, geonameid
+    pub fn with_admin2_names<R: Read>(mut self, r: R) -> Result<Self, Error> {
+        self.admin2_names = admin_names(r)?;
+        Ok(self)
+    }
+
+    /// Jaro-Winkler similarity a fuzzy match needs; default 0.9, above 1 turns fuzzy
+    /// matching off
+    pub fn with_min_similarity(mut self, min_similarity: f64) -> Self {
+        self.min_similarity = min_similarity;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+
+    pub fn places(&self) -> &[Place] {
+        &self.places
+    }
+
+    pub fn admin1_name(&self, place: &Place) -> Option<&str> {
+        self.admin1_names.get(&format!("{}.{}", place.country, place.admin1)).map(|s| s.as_str())
+    }
+
+    pub fn admin2_name(&self, place: &Place) -> Option<&str> {
+        self.admin2_names.get(&format!("{}.{}.{}", place.country, place.admin1, place.admin2)).map(|s| s.as_str())
+    }
+
+    fn state_matches(&self, place: &Place, state: &str) -> bool {
+        place.admin1.eq_ignore_ascii_case(state)
+            || self.admin1_name(place).and_then(normalize).as_deref() == Some(state)
+    }
+
+    /// The place of a city; country codes are normalized as by `CountryNormalizer`, so a US
+    /// state without a country is enough.
+    pub fn locate(&self, city: Option<&str>, state: Option<&str>, country: Option<&str>) -> Option<Location<'_>> {
+        let country = self.countries.code(country, state)?;
+        let names = self.names.get(country)?;
+        let city = normalize(city?)?;
+        let state = state.and_then(normalize);
+
+        // candidates with their confidence before the state is taken into account
+        let candidates: Vec<(Entry, MatchKind, f64)> = match names.get(&city) {
+            Some(entries) => entries.iter()
+                .map(|e| if e.alternate {
+                    (*e, MatchKind::Alternate, ALTERNATE)
+                } else {
+                    (*e, MatchKind::Name, 1.0)
+                })
+                .collect(),
+            None => {
+                let initial = city.chars().next()?;
+                names.range(initial.to_string()..)
+                    .take_while(|(name, _)| name.starts_with(initial))
+                    .filter_map(|(name, entries)| {
+                        let similarity = jaro_winkler(&city, name);
+                        if similarity >= self.min_similarity { Some((entries, similarity)) } else { None }
+                    })
+                    .flat_map(|(entries, similarity)| {
+                        let alternate = |e: &Entry| if e.alternate { ALTERNATE } else { 1.0 };
+                        entries.iter().map(move |e| (*e, MatchKind::Fuzzy, FUZZY * similarity * alternate(e)))
+                    })
+                    .collect()
+            },
+        };
+
+        let in_state: Vec<&(Entry, MatchKind, f64)> = match &state {
+            Some(state) => candidates.iter()
+                .filter(|(e, _, _)| self.state_matches(&self.places[e.place], state))
+                .collect(),
+            None => Vec::new(),
+        };
+        let (pool, factor) = match (&state, in_state.is_empty()) {
+            (None, _) => (candidates.iter().collect(), NO_STATE),
+            (Some(_), true) => (candidates.iter().collect(), STATE_MISMATCH),
+            (Some(_), false) => (in_state, 1.0),
+        };
+
+        pool.into_iter()
+            .max_by(|a, b| {
+                a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal)
+                    .then(self.places[a.0.place].population.cmp(&self.places[b.0.place].population))
+                    .then(self.places[b.0.place].geonameid.cmp(&self.places[a.0.place].geonameid))
+            })
+            .map(|(entry, kind, confidence)| {
+                let place = &self.places[entry.place];
+                Location {
+                    place,
+                    admin1_name: self.admin1_name(place),
+                    admin2_name: self.admin2_name(place),
+                    kind: *kind,
+                    confidence: confidence * factor,
+                }
+            })
+    }
+
+    pub fn locate_address(&self, address: &Address) -> Option<Location<'_>> {
+        self.locate(address.city.as_deref(), address.state.as_deref(), address.country.as_deref())
+    }
+
+    /// One row per inventor and assignee of a grant, located or not. Assignees are numbered
+    /// by position, as in `tables`.
+    pub fn rows<'a>(&'a self, pg: &'a PatentGrant) -> Vec<LocationRow<'a>> {
+        let dg = &pg.us_bibliographic_data_grant;
+        let inventors = dg.inventors.iter()
+            .map(|inventor| ("inventor", inventor.sequence.clone(), &inventor.addressbook.address));
+        let assignees = dg.assignees.iter()
+            .enumerate()
+            .map(|(i, assignee)| ("assignee", (i + 1).to_string(), &assignee.addressbook.address));
+
+        inventors.chain(assignees)
+            .map(|(party, sequence, address)| {
+                let location = self.locate_address(address);
+                let place = location.as_ref().map(|l| l.place);
+                LocationRow {
+                    doc_number: &dg.publication_reference.doc_number,
+                    party,
+                    sequence,
+                    city: address.city.as_deref(),
+                    state: address.state.as_deref(),
+                    country: address.country.as_deref(),
+                    geonameid: place.map(|p| p.geonameid),
+                    place_name: place.map(|p| p.name.as_str()),
+                    latitude: place.map(|p| p.latitude),
+                    longitude: place.map(|p| p.longitude),
+                    place_country: place.map(|p| p.country.as_str()),
+                    admin1: place.map(|p| p.admin1.as_str()),
+                    admin1_name: location.as_ref().and_then(|l| l.admin1_name),
+                    admin2: place.map(|p| p.admin2.as_str()),
+                    admin2_name: location.as_ref().and_then(|l| l.admin2_name),
+                    match_kind: location.as_ref().map(|l| l.kind),
+                    confidence: location.as_ref().map(|l| l.confidence),
+                }
+            })
+            .collect()
+    }
+}
+
+/// One party address and where it was located; empty place columns when it wasn't
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LocationRow<'a> {
+    pub doc_number: &'a str,
+    /// inventor or assignee
+    pub party: &'static str,
+    pub sequence: String,
+    pub city: Option<&'a str>,
+    pub state: Option<&'a str>,
+    pub country: Option<&'a str>,
+    pub geonameid: Option<u64>,
+    pub place_name: Option<&'a str>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub place_country: Option<&'a str>,
+    pub admin1: Option<&'a str>,
+    pub admin1_name: Option<&'a str>,
+    pub admin2: Option<&'a str>,
+    pub admin2_name: Option<&'a str>,
+    pub match_kind: Option<MatchKind>,
+    pub confidence: Option<f64>,
+}
+
+fn tab_separated<R: Read>(r: R) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .has_headers(false)
+        .quoting(false)
+        .flexible(true)
+        .from_reader(r)
+}
+
+fn admin_names<R: Read>(r: R) -> Result<HashMap<String, String>, Error> {
+    let mut names = HashMap::new();
+    for record in tab_separated(r).records() {
+        let record = record.context(ReadTable)?;
+        if let (Some(code), Some(name)) = (record.get(0), record.get(1)) {
+            names.insert(code.to_owned(), name.to_owned());
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEONAMES: &str = "\
+5392171\tSan Jose\tSan Jose\tSan Jose,San José,SJC\t37.33939\t-121.89496\tP\tPPLA2\tUS\t\tCA\t085\t\t\t1026908\t26\t27\tAmerica/Los_Angeles\t2019-02-26
+4726206\tSan Jose\tSan Jose\t\t29.97\t-98.36\tP\tPPL\tUS\t\tTX\t091\t\t\t0\t\t\tAmerica/Chicago\t2006-01-17
+2867714\tMunich\tMunich\tMunchen,München,Muenchen,Мюнхен\t48.13743\t11.57549\tP\tPPLA\tDE\t\t02\t091\t\t\t1260391\t\t524\tEurope/Berlin\t2023-10-12
+5332921\tCalifornia\tCalifornia\t\t37.25\t-119.75\tA\tADM1\tUS\t\tCA\t\t\t\t37001548\t\t\tAmerica/Los_Angeles\t2019-02-26
+";
+
+    #[test]
+    fn locate() {
+        let gazetteer = Gazetteer::from_geonames(GEONAMES.as_bytes()).unwrap()
+            .with_admin1_names("US.CA\tCalifornia\tCalifornia\t5332921\nDE.02\tBavaria\tBavaria\t2951839\n".as_bytes())
+            .unwrap();
+        assert_eq!(gazetteer.len(), 3);
+
+        let sj = gazetteer.locate(Some("San Jose"), Some("CA"), Some("US")).unwrap();
+        assert_eq!(sj.place.geonameid, 5392171);
+        assert_eq!(sj.admin1_name, Some("California"));
+        assert_eq!((sj.kind, sj.confidence), (MatchKind::Name, 1.0));
+
+        let tx = gazetteer.locate(Some("SAN JOSE"), Some("TX"), None).unwrap();
+        assert_eq!(tx.place.geonameid, 4726206);
+
+        let munich = gazetteer.locate(Some("München"), None, Some("DE")).unwrap();
+        assert_eq!(munich.place.geonameid, 2867714);
+        assert_eq!(munich.kind, MatchKind::Alternate);
+
+        let typo = gazetteer.locate(Some("Munchn"), None, Some("DE")).unwrap();
+        assert_eq!(typo.kind, MatchKind::Fuzzy);
+        assert!(typo.confidence < munich.confidence);
+
+        assert!(gazetteer.locate(Some("Berlin"), None, Some("DE")).is_none());
+        assert!(gazetteer.locate(Some("San Jose"), None, Some("EP")).is_none());
+    }
+}
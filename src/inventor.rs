@@ -0,0 +1,367 @@
+//! Inventor disambiguation: persistent inventor ids across grants.
+//!
+//! Every inventor on every grant is a mention. Mentions are blocked by last name and first
+//! initial, and within a block each pair is scored on:
+//!
+//! | evidence      | weight | similarity                                        |
+//! |---------------|--------|---------------------------------------------------|
+//! | first name    | 0.30   | Jaro-Winkler; an initial matches a name           |
+//! | location      | 0.20   | 1 same city, 0.6 same state, 0.3 same country     |
+//! | co-inventors  | 0.20   | Jaccard of the other inventors' names             |
+//! | assignees     | 0.15   | Jaccard of normalized assignee names              |
+//! | cpc           | 0.15   | Jaccard of cpc subclasses                         |
+//!
+//! Pairs whose first names aren't similar enough score zero whatever the rest, and mentions
+//! on the same grant are never compared. Pairs at or above the threshold are linked, and the
+//! linked mentions (single linkage) are one inventor.
+//!
+//! Blocks of common names, with more than `MAX_BLOCK` mentions, are split by full first name
+//! and country, and each mention is compared with only the next `MAX_BLOCK` in its sub-block
+//! by patent number. That bounds the work, at the cost of never linking an initial to a full
+//! name, or across countries, for those names.
+//!
+//! An inventor's id is a hash of its earliest mention (lowest patent number, then sequence).
+//! Adding later grants keeps ids unless a new mention links two inventors; single linkage then
+//! merges them, and the later one takes the earlier one's id.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::assignee::normalize_name;
+use crate::data::PatentGrant;
+use crate::matching::{fold, jaro_winkler, stable_id, Clusters};
+use crate::number::PatentNumber;
+
+const W_FIRST_NAME: f64 = 0.30;
+const W_LOCATION: f64 = 0.20;
+const W_COINVENTORS: f64 = 0.20;
+const W_ASSIGNEES: f64 = 0.15;
+const W_CPC: f64 = 0.15;
+
+/// first names less similar than this never match
+const MIN_FIRST_NAME: f64 = 0.85;
+
+/// blocks larger than this are sub-blocked, and sub-blocks compared within a window this wide
+const MAX_BLOCK: usize = 1000;
+
+/// One inventor on one grant
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mention {
+    pub doc_number: String,
+    pub sequence: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub city: String,
+    pub state: String,
+    pub country: String,
+    /// "LAST F" of the grant's other inventors
+    pub coinventors: BTreeSet<String>,
+    /// normalized assignee names
+    pub assignees: BTreeSet<String>,
+    /// cpc subclasses
+    pub cpc: BTreeSet<String>,
+}
+
+impl Mention {
+    /// "LAST F", which is also the blocking key
+    fn key(&self) -> String {
+        name_key(&self.first_name, &self.last_name)
+    }
+
+    /// by patent number, then sequence; doc numbers that don't parse sort last
+    fn order(&self) -> (bool, Option<PatentNumber>, &str, Option<u32>, &str) {
+        let number = self.doc_number.parse().ok();
+        (number.is_none(), number, &self.doc_number, self.sequence.parse().ok(), &self.sequence)
+    }
+}
+
+/// upper case letters and spaces only
+fn clean(s: &str) -> String {
+    s.chars()
+        .map(fold)
+        .map(|c| if c.is_ascii_alphabetic() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn name_key(first_name: &str, last_name: &str) -> String {
+    match first_name.chars().next() {
+        Some(initial) => format!("{} {}", last_name, initial),
+        None => last_name.to_owned(),
+    }
+}
+
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / a.union(b).count() as f64
+}
+
+/// first given names only, so a middle name or initial on one mention doesn't count against
+fn first_name_similarity(a: &str, b: &str) -> f64 {
+    let a = a.split_whitespace().next().unwrap_or("");
+    let b = b.split_whitespace().next().unwrap_or("");
+
+    if a.len() == 1 || b.len() == 1 {
+        return if a.chars().next() == b.chars().next() { MIN_FIRST_NAME } else { 0.0 };
+    }
+    jaro_winkler(a, b)
+}
+
+fn location_similarity(a: &Mention, b: &Mention) -> f64 {
+    if a.country.is_empty() || a.country != b.country {
+        0.0
+    } else if !a.city.is_empty() && a.city == b.city && a.state == b.state {
+        1.0
+    } else if !a.state.is_empty() && a.state == b.state {
+        0.6
+    } else {
+        0.3
+    }
+}
+
+/// 0 to 1; see the module docs for the weights
+pub fn score(a: &Mention, b: &Mention) -> f64 {
+    let first_name = first_name_similarity(&a.first_name, &b.first_name);
+    if first_name < MIN_FIRST_NAME {
+        return 0.0;
+    }
+
+    W_FIRST_NAME * first_name
+        + W_LOCATION * location_similarity(a, b)
+        + W_COINVENTORS * jaccard(&a.coinventors, &b.coinventors)
+        + W_ASSIGNEES * jaccard(&a.assignees, &b.assignees)
+        + W_CPC * jaccard(&a.cpc, &b.cpc)
+}
+
+/// One mention and its inventor id; the output of `Disambiguator::rows`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct InventorRow<'a> {
+    pub doc_number: &'a str,
+    pub sequence: &'a str,
+    pub inventor_id: &'a str,
+    pub first_name: &'a str,
+    pub last_name: &'a str,
+    pub city: &'a str,
+    pub state: &'a str,
+    pub country: &'a str,
+}
+
+/// Collects inventor mentions over the corpus, then clusters them into inventors
+pub struct Disambiguator {
+    threshold: f64,
+    mentions: Vec<Mention>,
+}
+
+impl Disambiguator {
+    /// Mentions scoring at least `threshold` are linked. With the default weights, 0.55 needs
+    /// more than a matching name and city.
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            mentions: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, pg: &PatentGrant) {
+        let dg = &pg.us_bibliographic_data_grant;
+
+        let names: Vec<(String, String)> = dg.inventors.iter()
+            .map(|inventor| {
+                let ab = &inventor.addressbook;
+                (clean(ab.first_name.as_deref().unwrap_or("")), clean(ab.last_name.as_deref().unwrap_or("")))
+            })
+            .collect();
+        let assignees: BTreeSet<String> = dg.assignees.iter()
+            .filter_map(|a| a.orgname.as_deref().or(a.addressbook.orgname.as_deref()))
+            .map(normalize_name)
+            .collect();
+        let cpcs = &dg.classifications_cpc;
+        let cpc: BTreeSet<String> = cpcs.main_cpc.iter().chain(cpcs.further_cpc.iter())
+            .map(|c| format!("{}{}{}", c.section, c.class, c.subclass))
+            .collect();
+
+        for (i, inventor) in dg.inventors.iter().enumerate() {
+            let (first_name, last_name) = names[i].clone();
+            if last_name.is_empty() {
+                continue;
+            }
+            let coinventors = names.iter().enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, (first, last))| name_key(first, last))
+                .collect();
+
+            let address = &inventor.addressbook.address;
+            let field = |value: &Option<String>| value.as_deref().map(clean).unwrap_or_default();
+            self.mentions.push(Mention {
+                doc_number: dg.publication_reference.doc_number.clone(),
+                sequence: inventor.sequence.clone(),
+                first_name,
+                last_name,
+                city: field(&address.city),
+                state: field(&address.state),
+                country: field(&address.country),
+                coinventors,
+                assignees: assignees.clone(),
+                cpc: cpc.clone(),
+            });
+        }
+    }
+
+    /// Adds a mention directly, e.g. from an earlier run's output
+    pub fn add_mention(&mut self, mention: Mention) {
+        self.mentions.push(mention);
+    }
+
+    pub fn mentions(&self) -> &[Mention] {
+        &self.mentions
+    }
+
+    /// An inventor id per mention, in the order the mentions were added
+    pub fn ids(&self) -> Vec<String> {
+        let mut blocks: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (i, mention) in self.mentions.iter().enumerate() {
+            blocks.entry(mention.key()).or_default().push(i);
+        }
+
+        let mut clusters = Clusters::new(self.mentions.len());
+        for block in blocks.values() {
+            if block.len() <= MAX_BLOCK {
+                self.link(block, block.len(), &mut clusters);
+                continue;
+            }
+            let mut sub_blocks: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+            for &i in block {
+                let mention = &self.mentions[i];
+                let first_name = mention.first_name.split_whitespace().next().unwrap_or("");
+                sub_blocks.entry((first_name, &mention.country)).or_default().push(i);
+            }
+            for sub_block in sub_blocks.values_mut() {
+                sub_block.sort_by_cached_key(|i| self.mentions[*i].order());
+                self.link(sub_block, MAX_BLOCK, &mut clusters);
+            }
+        }
+
+        let mut ids = vec![String::new(); self.mentions.len()];
+        for group in clusters.groups() {
+            let earliest = group.iter()
+                .map(|i| &self.mentions[*i])
+                .min_by(|a, b| a.order().cmp(&b.order()))
+                .expect("groups aren't empty");
+            let id = stable_id("I", &format!("{}-{}", earliest.doc_number, earliest.sequence));
+            for i in group {
+                ids[i] = id.clone();
+            }
+        }
+
+        ids
+    }
+
+    /// links each mention in the block to any of the next `window` that score at least the
+    /// threshold
+    fn link(&self, block: &[usize], window: usize, clusters: &mut Clusters) {
+        for (n, &i) in block.iter().enumerate() {
+            for &j in block[n + 1..].iter().take(window) {
+                if clusters.find(i) == clusters.find(j) {
+                    continue;
+                }
+                let (a, b) = (&self.mentions[i], &self.mentions[j]);
+                if a.doc_number != b.doc_number && score(a, b) >= self.threshold {
+                    clusters.union(i, j);
+                }
+            }
+        }
+    }
+
+    /// one row per mention, sorted by doc number and sequence
+    pub fn rows<'a>(&'a self, ids: &'a [String]) -> Vec<InventorRow<'a>> {
+        let mut rows: Vec<InventorRow<'a>> = self.mentions.iter().zip(ids)
+            .map(|(m, id)| InventorRow {
+                doc_number: &m.doc_number,
+                sequence: &m.sequence,
+                inventor_id: id,
+                first_name: &m.first_name,
+                last_name: &m.last_name,
+                city: &m.city,
+                state: &m.state,
+                country: &m.country,
+            })
+            .collect();
+        rows.sort_by(|a, b| (a.doc_number, a.sequence).cmp(&(b.doc_number, b.sequence)));
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mention(doc_number: &str, first_name: &str, city: &str, coinventors: &[&str], cpc: &[&str]) -> Mention {
+        Mention {
+            doc_number: doc_number.to_owned(),
+            sequence: "001".to_owned(),
+            first_name: first_name.to_owned(),
+            last_name: "SMITH".to_owned(),
+            city: city.to_owned(),
+            state: "CA".to_owned(),
+            country: "US".to_owned(),
+            coinventors: coinventors.iter().map(|s| s.to_string()).collect(),
+            assignees: BTreeSet::new(),
+            cpc: cpc.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn link_mentions() {
+        let mut disambiguator = Disambiguator::new(0.55);
+        disambiguator.add_mention(mention("10000002", "JOHN A", "SAN JOSE", &["DOE J"], &["H04L"]));
+        disambiguator.add_mention(mention("10000001", "JOHN", "SAN JOSE", &["DOE J", "ROE R"], &["H04L", "G06F"]));
+        // moved, but same co-inventor and field
+        disambiguator.add_mention(mention("10000003", "J", "PALO ALTO", &["DOE J"], &["H04L"]));
+        // same name and city, nothing else in common
+        disambiguator.add_mention(mention("10000004", "JOHN", "SAN JOSE", &[], &["A61K"]));
+        // same city, different first name
+        disambiguator.add_mention(mention("10000005", "JANE", "SAN JOSE", &["ROE R"], &["A61K"]));
+
+        let ids = disambiguator.ids();
+        assert_eq!(ids[0], ids[1]);
+        assert_eq!(ids[0], ids[2]);
+        assert_ne!(ids[0], ids[3]);
+        assert_ne!(ids[0], ids[4]);
+        assert_eq!(ids[0], stable_id("I", "10000001-001"));
+
+        let rows = disambiguator.rows(&ids);
+        assert_eq!(rows[0].doc_number, "10000001");
+
+        // earliest by patent number, not by string
+        disambiguator.add_mention(mention("9999999", "JOHN", "SAN JOSE", &["DOE J"], &["H04L"]));
+        let ids = disambiguator.ids();
+        assert_eq!(ids[0], ids[5]);
+        assert_eq!(ids[0], stable_id("I", "9999999-001"));
+    }
+
+    #[test]
+    fn sub_block_common_names() {
+        let mut disambiguator = Disambiguator::new(0.55);
+        for n in 0..MAX_BLOCK {
+            disambiguator.add_mention(mention(&(10000001 + n).to_string(), "JOHN", "SAN JOSE", &["DOE J"], &["H04L"]));
+        }
+        let mut abroad = mention("11000001", "JOHN", "", &["DOE J"], &["H04L"]);
+        abroad.country = "DE".to_owned();
+        disambiguator.add_mention(abroad.clone());
+
+        // a block this small compares across countries
+        let mut small = Disambiguator::new(0.55);
+        small.add_mention(disambiguator.mentions()[0].clone());
+        small.add_mention(abroad);
+        let ids = small.ids();
+        assert_eq!(ids[0], ids[1]);
+
+        let ids = disambiguator.ids();
+        assert!(ids[..MAX_BLOCK].iter().all(|id| *id == ids[0]));
+        assert_ne!(ids[0], ids[MAX_BLOCK]);
+    }
+}
@@ -1,13 +1,39 @@
+pub mod assignee;
+pub mod citation;
+pub mod collaboration;
+#[cfg(feature = "columnar")]
+pub mod columnar;
+pub mod complexity;
+pub mod concordance;
+pub mod country;
+pub mod cube;
 pub mod data;
+pub mod dates;
 mod deserialize;
 pub mod error;
 pub mod fetch;
+pub mod geocode;
+pub mod inventor;
+pub mod locarno;
+pub mod matching;
+pub mod network;
+pub mod number;
+pub mod pgcopy;
+pub mod region;
+pub mod scheme;
 mod serialize;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod tables;
+pub mod techspace;
+#[cfg(test)]
+mod testing;
+pub mod uspc;
 pub mod util;
 
 pub use crate::deserialize::PatentGrants;
 pub use crate::error::Error;
-pub use crate::serialize::PatentOutput;
+pub use crate::serialize::{Columns, PatentOutput};
 
 #[cfg(test)]
 mod tests {
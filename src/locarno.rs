@@ -0,0 +1,535 @@
+//! Locarno (industrial design) classification codes and editions.
+//!
+//! Grant xml gives the code as four digits, class then subclass ("0202" is class 02, subclass
+//! 02), and the edition as a number ("12"). WIPO writes codes as "02-02", which is what
+//! `Display` uses; `code()` gives the xml form back.
+//!
+//! Class and subclass titles are bundled. Class titles have been stable since the 10th
+//! edition added class 32; subclass titles are reworded now and then, and vacant subclasses
+//! and some added in recent editions aren't bundled, so a titles csv for the edition in use
+//! (see CLASSIFICATION.md) takes precedence. Classes 31, 32 and 99 have no subclasses, and
+//! are coded with subclass 00.
+//!
+//! `EditionConcordance` is loaded from a list of the subclasses that moved between editions;
+//! codes that aren't listed are taken as unchanged. No list is bundled, since WIPO publishes
+//! the changes per edition as amendment notes rather than as a table.
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::error::{Error, ReadTable};
+
+/// class titles, from the 12th edition
+const CLASSES: [(u8, &str); 33] = [
+    (1, "Foodstuffs"),
+    (2, "Articles of clothing and haberdashery"),
+    (3, "Travel goods, cases, parasols and personal belongings, not elsewhere specified"),
+    (4, "Brushware"),
+    (5, "Textile piecegoods, artificial and natural sheet material"),
+    (6, "Furnishing"),
+    (7, "Household goods, not elsewhere specified"),
+    (8, "Tools and hardware"),
+    (9, "Packages and containers for the transport or handling of goods"),
+    (10, "Clocks and watches and other measuring instruments, checking and signalling instruments"),
+    (11, "Articles of adornment"),
+    (12, "Means of transport or hoisting"),
+    (13, "Equipment for production, distribution or transformation of electricity"),
+    (14, "Recording, telecommunication or data processing equipment"),
+    (15, "Machines, not elsewhere specified"),
+    (16, "Photographic, cinematographic and optical apparatus"),
+    (17, "Musical instruments"),
+    (18, "Printing and office machinery"),
+    (19, "Stationery and office equipment, artists' and teaching materials"),
+    (20, "Sales and advertising equipment, signs"),
+    (21, "Games, toys, tents and sports goods"),
+    (22, "Arms, pyrotechnic articles, articles for hunting, fishing and pest killing"),
+    (23, "Fluid distribution equipment, sanitary, heating, ventilation and air-conditioning equipment, solid fuel"),
+    (24, "Medical and laboratory equipment"),
+    (25, "Building units and construction elements"),
+    (26, "Lighting apparatus"),
+    (27, "Tobacco and smokers' supplies"),
+    (28, "Pharmaceutical and cosmetic products, toilet articles and apparatus"),
+    (29, "Devices and equipment against fire hazards, for accident prevention and for rescue"),
+    (30, "Articles for the care and handling of animals"),
+    (31, "Machines and appliances for preparing food or drink, not elsewhere specified"),
+    (32, "Graphic symbols and logos, surface patterns, ornamentation"),
+    (99, "Miscellaneous"),
+];
+
+/// subclass titles; see the module docs for what's missing
+const SUBCLASSES: [((u8, u8), &str); 219] = [
+    ((1, 1), "Bakers' products, biscuits, pastry, macaroni and other cereal products, chocolates, confectionery, ices"),
+    ((1, 2), "Fruit, vegetables and products made from fruit and vegetables"),
+    ((1, 3), "Cheeses, butter and butter substitutes, other dairy produce"),
+    ((1, 4), "Butchers' meat, pork products, fish"),
+    ((1, 6), "Animal foodstuffs"),
+    ((1, 99), "Miscellaneous"),
+    ((2, 1), "Undergarments, lingerie, corsets, brassieres, nightwear"),
+    ((2, 2), "Garments"),
+    ((2, 3), "Headwear"),
+    ((2, 4), "Footwear, socks and stockings"),
+    ((2, 5), "Neckties, scarves, neckerchiefs and handkerchiefs"),
+    ((2, 6), "Gloves"),
+    ((2, 7), "Haberdashery and clothing accessories"),
+    ((2, 99), "Miscellaneous"),
+    ((3, 1), "Trunks, suitcases, briefcases, handbags, keyholders, cases specially designed for their contents, wallets and similar articles"),
+    ((3, 3), "Umbrellas, parasols, sunshades and walking sticks"),
+    ((3, 4), "Fans"),
+    ((3, 99), "Miscellaneous"),
+    ((4, 1), "Brushes and brooms for cleaning"),
+    ((4, 2), "Toilet brushes, clothes brushes and shoe brushes"),
+    ((4, 3), "Brushes for machines"),
+    ((4, 4), "Paintbrushes, brushes for use in cooking"),
+    ((4, 99), "Miscellaneous"),
+    ((5, 1), "Spun articles"),
+    ((5, 2), "Lace"),
+    ((5, 3), "Embroidery"),
+    ((5, 4), "Ribbons, braids and other decorative trimmings"),
+    ((5, 5), "Textile fabrics"),
+    ((5, 6), "Artificial or natural sheet material"),
+    ((5, 99), "Miscellaneous"),
+    ((6, 1), "Seats"),
+    ((6, 2), "Beds"),
+    ((6, 3), "Tables and similar furniture"),
+    ((6, 4), "Storage furniture"),
+    ((6, 5), "Composite furniture"),
+    ((6, 6), "Other furniture and furniture parts"),
+    ((6, 7), "Mirrors and frames"),
+    ((6, 8), "Clothes hangers"),
+    ((6, 9), "Mattresses and cushions"),
+    ((6, 10), "Curtains and indoor blinds"),
+    ((6, 11), "Carpets, mats and rugs"),
+    ((6, 12), "Tapestries"),
+    ((6, 13), "Blankets and other covering materials, household linen and napery"),
+    ((6, 99), "Miscellaneous"),
+    ((7, 1), "China, glassware, dishes and other articles of a similar nature"),
+    ((7, 2), "Cooking appliances, utensils and containers"),
+    ((7, 3), "Table knives, forks and spoons"),
+    ((7, 4), "Appliances and utensils, hand-manipulated, for preparing food or drink"),
+    ((7, 5), "Flatirons and laundering, cleaning and drying equipment"),
+    ((7, 6), "Other table utensils"),
+    ((7, 7), "Other household receptacles"),
+    ((7, 8), "Fireplace implements"),
+    ((7, 99), "Miscellaneous"),
+    ((8, 1), "Tools and implements for drilling, milling or digging"),
+    ((8, 2), "Hammers and other similar tools and implements"),
+    ((8, 3), "Cutting tools and implements"),
+    ((8, 4), "Screwdrivers and other similar tools and implements"),
+    ((8, 5), "Other tools and implements"),
+    ((8, 6), "Handles, knobs and hinges"),
+    ((8, 7), "Locking or closing devices"),
+    ((8, 8), "Fastening, supporting or mounting devices not included in other classes"),
+    ((8, 9), "Metal fittings and mountings for doors, windows and furniture, and similar articles"),
+    ((8, 10), "Bicycle and motorcycle racks"),
+    ((8, 11), "Curtain fittings"),
+    ((8, 99), "Miscellaneous"),
+    ((9, 1), "Bottles, flasks, pots, carboys, demijohns, and pressurized containers"),
+    ((9, 2), "Storage cans, drums and casks"),
+    ((9, 3), "Boxes, cases, containers, (preserve) tins or cans"),
+    ((9, 4), "Hampers, crates and baskets"),
+    ((9, 5), "Bags, sachets, tubes and capsules"),
+    ((9, 6), "Ropes and hooping materials"),
+    ((9, 7), "Closing means and attachments for packages and containers"),
+    ((9, 8), "Pallets and platforms for forklifts"),
+    ((9, 9), "Refuse and trash containers and stands therefor"),
+    ((9, 10), "Handles and grips for transporting or handling packages and containers"),
+    ((9, 99), "Miscellaneous"),
+    ((10, 1), "Clocks and alarm clocks"),
+    ((10, 2), "Watches and wrist watches"),
+    ((10, 3), "Other time-measuring instruments"),
+    ((10, 4), "Other measuring instruments, apparatus and devices"),
+    ((10, 5), "Instruments, apparatus and devices for checking, security or testing"),
+    ((10, 6), "Signalling apparatus and devices"),
+    ((10, 7), "Casings, cases, dials, hands and all other parts and accessories of instruments for measuring, checking and signalling"),
+    ((10, 99), "Miscellaneous"),
+    ((11, 1), "Jewellery"),
+    ((11, 2), "Trinkets, table, mantel and wall ornaments, flower vases and pots"),
+    ((11, 3), "Medals and badges"),
+    ((11, 4), "Artificial flowers, fruit and plants"),
+    ((11, 5), "Flags, festive decorations"),
+    ((11, 99), "Miscellaneous"),
+    ((12, 1), "Vehicles drawn by animals"),
+    ((12, 2), "Handcarts, wheelbarrows"),
+    ((12, 3), "Locomotives and rolling stock for railways and all other rail vehicles"),
+    ((12, 4), "Telpher carriers, chair lifts and ski lifts"),
+    ((12, 5), "Elevators and hoists for loading or conveying"),
+    ((12, 6), "Ships and boats"),
+    ((12, 7), "Aircraft and space vehicles"),
+    ((12, 8), "Motor cars, buses and lorries"),
+    ((12, 9), "Tractors"),
+    ((12, 10), "Road vehicle trailers"),
+    ((12, 11), "Cycles and motorcycles"),
+    ((12, 12), "Perambulators, invalid chairs, stretchers"),
+    ((12, 13), "Special-purpose vehicles"),
+    ((12, 14), "Other vehicles"),
+    ((12, 15), "Tyres and anti-skid chains for vehicles"),
+    ((12, 16), "Parts, equipment and accessories for vehicles, not included in other classes or subclasses"),
+    ((12, 99), "Miscellaneous"),
+    ((13, 1), "Generators and motors"),
+    ((13, 2), "Power transformers, rectifiers, batteries and accumulators"),
+    ((13, 3), "Equipment for distribution or control of electric power"),
+    ((13, 99), "Miscellaneous"),
+    ((14, 1), "Equipment for the recording or reproduction of sounds or pictures"),
+    ((14, 2), "Data processing equipment as well as peripheral apparatus and devices"),
+    ((14, 3), "Communications equipment, wireless remote controls and radio amplifiers"),
+    ((14, 4), "Screen displays and icons"),
+    ((14, 99), "Miscellaneous"),
+    ((15, 1), "Engines"),
+    ((15, 2), "Pumps and compressors"),
+    ((15, 3), "Agricultural and forestry machinery"),
+    ((15, 4), "Construction machinery"),
+    ((15, 5), "Washing, cleaning and drying machines"),
+    ((15, 6), "Textile, sewing, knitting and embroidering machines, including their integral parts"),
+    ((15, 7), "Refrigeration machinery and apparatus"),
+    ((15, 9), "Machine tools, abrading and founding machinery"),
+    ((15, 10), "Machines for filling, packing or packaging"),
+    ((15, 99), "Miscellaneous"),
+    ((16, 1), "Photographic cameras and film cameras"),
+    ((16, 2), "Projectors and viewers"),
+    ((16, 3), "Photocopying apparatus and enlargers"),
+    ((16, 4), "Developing apparatus and equipment"),
+    ((16, 5), "Accessories"),
+    ((16, 6), "Optical articles"),
+    ((16, 99), "Miscellaneous"),
+    ((17, 1), "Keyboard instruments"),
+    ((17, 2), "Wind instruments"),
+    ((17, 3), "Stringed instruments"),
+    ((17, 4), "Percussion instruments"),
+    ((17, 5), "Mechanical instruments"),
+    ((17, 99), "Miscellaneous"),
+    ((18, 1), "Typewriters and calculating machines"),
+    ((18, 2), "Printing machines"),
+    ((18, 3), "Type and type faces"),
+    ((18, 4), "Bookbinding machines, printers' stapling machines, guillotines and trimmers (for bookbinding)"),
+    ((18, 99), "Miscellaneous"),
+    ((19, 1), "Writing paper, cards for correspondence and announcements"),
+    ((19, 2), "Office equipment"),
+    ((19, 3), "Calendars"),
+    ((19, 4), "Books, booklets and objects of similar outward appearance"),
+    ((19, 6), "Materials and instruments for writing by hand, for drawing, for painting, for sculpture, for engraving and for other artistic techniques"),
+    ((19, 7), "Teaching materials"),
+    ((19, 8), "Other printed matter"),
+    ((19, 99), "Miscellaneous"),
+    ((20, 1), "Automatic vending machines"),
+    ((20, 2), "Display and sales equipment"),
+    ((20, 3), "Signs, signboards and advertising devices"),
+    ((20, 99), "Miscellaneous"),
+    ((21, 1), "Games and toys"),
+    ((21, 2), "Gymnastics and sports apparatus and equipment"),
+    ((21, 3), "Other amusement and entertainment articles"),
+    ((21, 4), "Tents and accessories thereof"),
+    ((21, 99), "Miscellaneous"),
+    ((22, 1), "Projectile weapons"),
+    ((22, 2), "Other weapons"),
+    ((22, 3), "Ammunition, rockets and pyrotechnic articles"),
+    ((22, 4), "Targets and accessories"),
+    ((22, 5), "Hunting and fishing equipment"),
+    ((22, 6), "Traps, articles for pest destruction"),
+    ((22, 99), "Miscellaneous"),
+    ((23, 1), "Fluid distribution equipment"),
+    ((23, 2), "Sanitary appliances"),
+    ((23, 3), "Heating equipment"),
+    ((23, 4), "Ventilation and air-conditioning equipment"),
+    ((23, 5), "Solid fuel"),
+    ((23, 99), "Miscellaneous"),
+    ((24, 1), "Apparatus and equipment for doctors, hospitals and laboratories"),
+    ((24, 2), "Medical instruments, instruments and tools for laboratory use"),
+    ((24, 3), "Prosthetic articles"),
+    ((24, 4), "Materials for dressing wounds, nursing and medical care"),
+    ((24, 99), "Miscellaneous"),
+    ((25, 1), "Building materials"),
+    ((25, 2), "Prefabricated or pre-assembled building parts"),
+    ((25, 3), "Houses, garages and other buildings"),
+    ((25, 4), "Steps, ladders and scaffolds"),
+    ((25, 99), "Miscellaneous"),
+    ((26, 1), "Candlesticks and candelabra"),
+    ((26, 2), "Torches and hand lamps and lanterns"),
+    ((26, 3), "Public lighting fixtures"),
+    ((26, 4), "Luminous sources, electrical or not"),
+    ((26, 5), "Lamps, standard lamps, chandeliers, wall and ceiling fixtures, lampshades, reflectors, photographic and cinematographic projector lamps"),
+    ((26, 6), "Luminous devices for vehicles"),
+    ((26, 99), "Miscellaneous"),
+    ((27, 1), "Tobacco, cigars and cigarettes"),
+    ((27, 2), "Pipes, cigar and cigarette holders"),
+    ((27, 3), "Ashtrays"),
+    ((27, 4), "Matches"),
+    ((27, 5), "Lighters"),
+    ((27, 6), "Cigar cases, cigarette cases, tobacco jars and pouches"),
+    ((27, 99), "Miscellaneous"),
+    ((28, 1), "Pharmaceutical products"),
+    ((28, 2), "Cosmetic products"),
+    ((28, 3), "Toilet articles and beauty parlour equipment"),
+    ((28, 4), "Wigs, false hairpieces"),
+    ((28, 99), "Miscellaneous"),
+    ((29, 1), "Devices and equipment against fire hazards"),
+    ((29, 2), "Devices and equipment for accident prevention and for rescue, not elsewhere specified"),
+    ((29, 99), "Miscellaneous"),
+    ((30, 1), "Animal clothing"),
+    ((30, 2), "Pens, cages, kennels and similar shelters"),
+    ((30, 3), "Feeders and waterers"),
+    ((30, 4), "Saddlery"),
+    ((30, 5), "Whips and prods"),
+    ((30, 6), "Beds and nests"),
+    ((30, 7), "Perches and other cage attachments"),
+    ((30, 8), "Markers, marks and shackles"),
+    ((30, 9), "Hitching posts"),
+    ((30, 99), "Miscellaneous"),
+    ((31, 0), "Machines and appliances for preparing food or drink, not elsewhere specified"),
+    ((32, 0), "Graphic symbols and logos, surface patterns, ornamentation"),
+    ((99, 0), "Miscellaneous"),
+];
+
+/// editions and the date each entered into force
+const EDITIONS: [(u8, (i32, u32, u32)); 9] = [
+    (7, (1999, 1, 1)),
+    (8, (2004, 1, 1)),
+    (9, (2009, 1, 1)),
+    (10, (2014, 1, 1)),
+    (11, (2017, 1, 1)),
+    (12, (2019, 1, 1)),
+    (13, (2021, 1, 1)),
+    (14, (2023, 1, 1)),
+    (15, (2025, 1, 1)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocarnoCode {
+    pub class: u8,
+    pub subclass: u8,
+}
+
+impl LocarnoCode {
+    /// as in grant xml, e.g. "0202"
+    pub fn code(&self) -> String {
+        format!("{:02}{:02}", self.class, self.subclass)
+    }
+
+    /// e.g. "02"
+    pub fn class_code(&self) -> String {
+        format!("{:02}", self.class)
+    }
+
+    pub fn class_title(&self) -> Option<&'static str> {
+        class_title(self.class)
+    }
+
+    /// the bundled title; see `LocarnoTitles` for an edition's own
+    pub fn subclass_title(&self) -> Option<&'static str> {
+        SUBCLASSES.iter()
+            .find(|((class, subclass), _)| *class == self.class && *subclass == self.subclass)
+            .map(|(_, title)| *title)
+    }
+}
+
+/// e.g. "02-02"
+impl fmt::Display for LocarnoCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.class, self.subclass)
+    }
+}
+
+/// Accepts the xml form ("0202") and separated forms ("02-02", "2-2", "02.02")
+impl FromStr for LocarnoCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidLocarno { raw: s.to_owned() };
+        let s = s.trim();
+        if !s.is_ascii() {
+            return Err(invalid());
+        }
+
+        let (class, subclass) = match s.find(&['-', '.', ' '][..]) {
+            Some(i) => (&s[..i], s[i + 1..].trim()),
+            None if s.len() == 4 => s.split_at(2),
+            None => return Err(invalid()),
+        };
+        let number = |part: &str| -> Result<u8, Error> {
+            if part.is_empty() || part.len() > 2 || !part.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            part.parse().map_err(|_| invalid())
+        };
+
+        // subclass 00 is the whole of a class without subclasses, e.g. "32-00"
+        let code = LocarnoCode { class: number(class)?, subclass: number(subclass)? };
+        if code.class == 0 {
+            return Err(invalid());
+        }
+        Ok(code)
+    }
+}
+
+pub fn class_title(class: u8) -> Option<&'static str> {
+    CLASSES.iter().find(|(c, _)| *c == class).map(|(_, title)| *title)
+}
+
+/// Edition number from "12", "012", or "LOC (12)"; None if there's no number
+pub fn parse_edition(raw: &str) -> Option<u8> {
+    let digits: String = raw.chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok().filter(|edition| *edition > 0)
+}
+
+/// when the edition entered into force; known from the 7th edition on
+pub fn in_force(edition: u8) -> Option<NaiveDate> {
+    EDITIONS.iter()
+        .find(|(e, _)| *e == edition)
+        .and_then(|(_, (y, m, d))| NaiveDate::from_ymd_opt(*y, *m, *d))
+}
+
+/// the edition in force on `date`, for grants that don't give one
+pub fn edition_at(date: NaiveDate) -> Option<u8> {
+    EDITIONS.iter()
+        .rev()
+        .find(|(edition, _)| matches!(in_force(*edition), Some(start) if start <= date))
+        .map(|(edition, _)| *edition)
+}
+
+#[derive(Deserialize)]
+struct TitleRecord {
+    code: String,
+    title: String,
+}
+
+/// Subclass titles for one edition, over the bundled ones
+#[derive(Debug, Default, Clone)]
+pub struct LocarnoTitles {
+    titles: BTreeMap<LocarnoCode, String>,
+}
+
+impl LocarnoTitles {
+    /// csv with a header row: code,title. Codes in any form `LocarnoCode` parses.
+    pub fn from_reader<R: Read>(r: R) -> Result<Self, Error> {
+        let mut titles = BTreeMap::new();
+
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: TitleRecord = record.context(ReadTable)?;
+            titles.insert(record.code.parse()?, record.title);
+        }
+
+        Ok(Self { titles })
+    }
+
+    /// the subclass title if loaded, otherwise the bundled one
+    pub fn title(&self, code: &LocarnoCode) -> Option<&str> {
+        self.titles.get(code).map(|title| title.as_str()).or_else(|| code.subclass_title())
+    }
+}
+
+#[derive(Deserialize)]
+struct MoveRecord {
+    edition: u8,
+    code: String,
+    next_code: String,
+}
+
+/// Subclasses that moved from one edition to the next
+#[derive(Debug, Default, Clone)]
+pub struct EditionConcordance {
+    // (edition, code in that edition) to the code in edition + 1
+    forward: BTreeMap<(u8, LocarnoCode), LocarnoCode>,
+    // (edition, code in that edition) to the code in edition - 1
+    backward: BTreeMap<(u8, LocarnoCode), LocarnoCode>,
+}
+
+impl EditionConcordance {
+    /// no moves; every code maps to itself
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// csv with a header row: edition,code,next_code, where `code` in `edition` became
+    /// `next_code` in the edition after it.
+    pub fn from_reader<R: Read>(r: R) -> Result<Self, Error> {
+        let mut concordance = Self::new();
+
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: MoveRecord = record.context(ReadTable)?;
+            concordance.insert(record.edition, record.code.parse()?, record.next_code.parse()?)?;
+        }
+
+        Ok(concordance)
+    }
+
+    /// `code` in `edition` became `next_code` in `edition + 1`. Where a subclass was split,
+    /// the first move inserted is the one followed backward. Errors for the last edition a
+    /// `u8` can hold, which has no next one.
+    pub fn insert(&mut self, edition: u8, code: LocarnoCode, next_code: LocarnoCode) -> Result<(), Error> {
+        let next = edition.checked_add(1).ok_or(Error::InvalidLocarnoEdition { edition })?;
+        self.forward.insert((edition, code), next_code);
+        self.backward.entry((next, next_code)).or_insert(code);
+        Ok(())
+    }
+
+    /// `code` from edition `from`, as it is in edition `to`; either direction
+    pub fn map(&self, code: LocarnoCode, from: u8, to: u8) -> LocarnoCode {
+        let mut code = code;
+        let mut edition = from;
+
+        while edition < to {
+            code = self.forward.get(&(edition, code)).cloned().unwrap_or(code);
+            edition += 1;
+        }
+        while edition > to {
+            code = self.backward.get(&(edition, code)).cloned().unwrap_or(code);
+            edition -= 1;
+        }
+
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(s: &str) -> LocarnoCode {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn codes_and_editions() {
+        assert_eq!(code("0202"), LocarnoCode { class: 2, subclass: 2 });
+        assert_eq!(code("2-2"), code("02-02"));
+        assert_eq!(code("1405").to_string(), "14-05");
+        assert_eq!(code("14-05").code(), "1405");
+        assert_eq!(code("3201").class_title(), Some("Graphic symbols and logos, surface patterns, ornamentation"));
+        assert_eq!(code("3200"), LocarnoCode { class: 32, subclass: 0 });
+        assert_eq!(code("99-00").subclass_title(), Some("Miscellaneous"));
+        assert_eq!(code("02-02").subclass_title(), Some("Garments"));
+        assert!("202".parse::<LocarnoCode>().is_err());
+        assert!("0002".parse::<LocarnoCode>().is_err());
+        assert!("1é1".parse::<LocarnoCode>().is_err());
+
+        let titles = LocarnoTitles::from_reader("code,title\n02-02,Clothing\n".as_bytes()).unwrap();
+        assert_eq!(titles.title(&code("0202")), Some("Clothing"));
+        assert_eq!(titles.title(&code("0203")), Some("Headwear"));
+
+        assert_eq!(parse_edition("12"), Some(12));
+        assert_eq!(parse_edition("LOC (11)"), Some(11));
+        assert_eq!(parse_edition(""), None);
+        assert_eq!(edition_at(NaiveDate::from_ymd_opt(2018, 6, 1).unwrap()), Some(11));
+        assert_eq!(edition_at(NaiveDate::from_ymd_opt(1990, 6, 1).unwrap()), None);
+    }
+
+    #[test]
+    fn concordance() {
+        let csv = "edition,code,next_code\n9,99-00,32-00\n12,14-04,14-05\n";
+        let concordance = EditionConcordance::from_reader(csv.as_bytes()).unwrap();
+        assert_eq!(concordance.map(code("9900"), 9, 13), code("3200"));
+        assert_eq!(concordance.map(code("1404"), 11, 14), code("1405"));
+        assert_eq!(concordance.map(code("1405"), 14, 11), code("1404"));
+        assert_eq!(concordance.map(code("0202"), 8, 14), code("0202"));
+
+        let csv = "edition,code,next_code\n255,14-04,14-05\n";
+        assert!(EditionConcordance::from_reader(csv.as_bytes()).is_err());
+    }
+}
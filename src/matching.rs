@@ -0,0 +1,143 @@
+//! Helpers shared by assignee harmonization and inventor disambiguation: string similarity,
+//! clustering, and ids that don't depend on input order.
+
+/// Upper case, with the accented latin letters common in names folded, e.g. 'é' to 'E'
+pub fn fold(c: char) -> char {
+    match c {
+        'À'..='Å' | 'à'..='å' => 'A',
+        'Ç' | 'ç' => 'C',
+        'È'..='Ë' | 'è'..='ë' => 'E',
+        'Ì'..='Ï' | 'ì'..='ï' => 'I',
+        'Ñ' | 'ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' | 'ò'..='ö' | 'ø' => 'O',
+        'Ù'..='Ü' | 'ù'..='ü' => 'U',
+        'Ý' | 'ý' | 'ÿ' => 'Y',
+        c => c.to_ascii_uppercase(),
+    }
+}
+
+/// Jaro-Winkler similarity, 0 (nothing in common) to 1 (equal)
+pub fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut a_matched = vec![false; a.len()];
+    let mut b_matched = vec![false; b.len()];
+    let mut matches = 0;
+
+    for (i, ca) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        for j in start..end {
+            if !b_matched[j] && b[j] == *ca {
+                a_matched[i] = true;
+                b_matched[j] = true;
+                matches += 1;
+                break;
+            }
+        }
+    }
+    if matches == 0 {
+        return 0.0;
+    }
+
+    let a_seq = a.iter().zip(&a_matched).filter(|(_, m)| **m).map(|(c, _)| c);
+    let b_seq = b.iter().zip(&b_matched).filter(|(_, m)| **m).map(|(c, _)| c);
+    let transpositions = a_seq.zip(b_seq).filter(|(x, y)| x != y).count() / 2;
+
+    let m = matches as f64;
+    let jaro = (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64) / m) / 3.0;
+
+    let prefix = a.iter().zip(&b).take(4).take_while(|(x, y)| x == y).count();
+    jaro + prefix as f64 * 0.1 * (1.0 - jaro)
+}
+
+/// "prefix" and 16 hex digits of the FNV-1a hash of `key`, so the same key always gets the
+/// same id
+pub fn stable_id(prefix: &str, key: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{}{:016x}", prefix, hash)
+}
+
+/// Union-find over 0..n, for single-linkage clustering
+pub struct Clusters {
+    parent: Vec<usize>,
+}
+
+impl Clusters {
+    pub fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect() }
+    }
+
+    pub fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // path compression
+        let mut i = i;
+        while self.parent[i] != root {
+            let next = self.parent[i];
+            self.parent[i] = root;
+            i = next;
+        }
+        root
+    }
+
+    pub fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            // the smaller index is the root, so roots don't depend on merge order
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+
+    /// members of each cluster, each in ascending order, clusters by their first member
+    pub fn groups(&mut self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut index = std::collections::HashMap::new();
+
+        for i in 0..self.parent.len() {
+            let root = self.find(i);
+            let group = *index.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group].push(i);
+        }
+
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_and_clusters() {
+        assert_eq!(jaro_winkler("MARTHA", "MARTHA"), 1.0);
+        assert!((jaro_winkler("MARTHA", "MARHTA") - 0.961).abs() < 0.001);
+        assert!((jaro_winkler("DWAYNE", "DUANE") - 0.84).abs() < 0.001);
+        assert_eq!(jaro_winkler("ABC", ""), 0.0);
+
+        assert_eq!(stable_id("O", "IBM"), stable_id("O", "IBM"));
+        assert_ne!(stable_id("O", "IBM"), stable_id("O", "IBN"));
+
+        let mut clusters = Clusters::new(5);
+        clusters.union(3, 1);
+        clusters.union(4, 3);
+        assert_eq!(clusters.groups(), vec![vec![0], vec![1, 3, 4], vec![2]]);
+    }
+}
@@ -0,0 +1,190 @@
+//! Undirected weighted graphs and their export formats.
+//!
+//! Node ids are the codes or names themselves (e.g. "H04L"), so output from the same input is
+//! identical run to run: nodes and edges are written in sorted order, and edge ids in GEXF are
+//! assigned in that order.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkFormat {
+    /// csv of source,target,weight
+    EdgeList,
+    GraphMl,
+    Gexf,
+}
+
+impl FromStr for NetworkFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "edge-list" => Ok(NetworkFormat::EdgeList),
+            "graphml" => Ok(NetworkFormat::GraphMl),
+            "gexf" => Ok(NetworkFormat::Gexf),
+            _ => Err(format!("unknown network format {:?}, expected one of: edge-list, graphml, gexf", s)),
+        }
+    }
+}
+
+/// Undirected graph with a weight on every node and edge
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Graph {
+    nodes: BTreeMap<String, f64>,
+    // keyed with the smaller id first
+    edges: BTreeMap<(String, String), f64>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `weight` to the node, creating it if needed
+    pub fn add_node(&mut self, id: &str, weight: f64) {
+        *self.nodes.entry(id.to_owned()).or_insert(0.0) += weight;
+    }
+
+    /// Adds `weight` to the edge, creating it (and its nodes, with weight zero) if needed.
+    /// Self-loops are ignored.
+    pub fn add_edge(&mut self, a: &str, b: &str, weight: f64) {
+        if a == b {
+            return;
+        }
+        self.add_node(a, 0.0);
+        self.add_node(b, 0.0);
+
+        let key = if a < b { (a.to_owned(), b.to_owned()) } else { (b.to_owned(), a.to_owned()) };
+        *self.edges.entry(key).or_insert(0.0) += weight;
+    }
+
+    /// nodes and their weights, sorted by id
+    pub fn nodes(&self) -> impl Iterator<Item = (&str, f64)> {
+        self.nodes.iter().map(|(id, weight)| (id.as_str(), *weight))
+    }
+
+    /// edges and their weights, sorted, with the smaller id first
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str, f64)> {
+        self.edges.iter().map(|((a, b), weight)| (a.as_str(), b.as_str(), *weight))
+    }
+
+    /// the edge's weight, in either direction
+    pub fn edge(&self, a: &str, b: &str) -> Option<f64> {
+        let key = if a < b { (a.to_owned(), b.to_owned()) } else { (b.to_owned(), a.to_owned()) };
+        self.edges.get(&key).cloned()
+    }
+
+    pub fn write<W: Write>(&self, format: NetworkFormat, w: W) -> Result<(), Error> {
+        match format {
+            NetworkFormat::EdgeList => self.write_edge_list(w),
+            NetworkFormat::GraphMl => self.write_graphml(w),
+            NetworkFormat::Gexf => self.write_gexf(w),
+        }
+        .map_err(|source| Error::WriteNetwork { source })
+    }
+
+    fn write_edge_list<W: Write>(&self, w: W) -> std::io::Result<()> {
+        let mut wtr = csv::Writer::from_writer(w);
+
+        wtr.write_record(["source", "target", "weight"])?;
+        for (a, b, weight) in self.edges() {
+            wtr.write_record([a, b, &weight.to_string()])?;
+        }
+        wtr.flush()
+    }
+
+    fn write_graphml<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(w, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+        writeln!(w, r#"  <key id="node_weight" for="node" attr.name="weight" attr.type="double"/>"#)?;
+        writeln!(w, r#"  <key id="edge_weight" for="edge" attr.name="weight" attr.type="double"/>"#)?;
+        writeln!(w, r#"  <graph edgedefault="undirected">"#)?;
+        for (id, weight) in self.nodes() {
+            writeln!(w, r#"    <node id="{}"><data key="node_weight">{}</data></node>"#, xml_escape(id), weight)?;
+        }
+        for (a, b, weight) in self.edges() {
+            writeln!(
+                w,
+                r#"    <edge source="{}" target="{}"><data key="edge_weight">{}</data></edge>"#,
+                xml_escape(a), xml_escape(b), weight,
+            )?;
+        }
+        writeln!(w, "  </graph>")?;
+        writeln!(w, "</graphml>")?;
+        w.flush()
+    }
+
+    fn write_gexf<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(w, r#"<gexf xmlns="http://gexf.net/1.3" version="1.3">"#)?;
+        writeln!(w, r#"  <graph mode="static" defaultedgetype="undirected">"#)?;
+        writeln!(w, r#"    <attributes class="node">"#)?;
+        writeln!(w, r#"      <attribute id="weight" title="weight" type="double"/>"#)?;
+        writeln!(w, "    </attributes>")?;
+        writeln!(w, "    <nodes>")?;
+        for (id, weight) in self.nodes() {
+            let id = xml_escape(id);
+            writeln!(
+                w,
+                r#"      <node id="{}" label="{}"><attvalues><attvalue for="weight" value="{}"/></attvalues></node>"#,
+                id, id, weight,
+            )?;
+        }
+        writeln!(w, "    </nodes>")?;
+        writeln!(w, "    <edges>")?;
+        for (i, (a, b, weight)) in self.edges().enumerate() {
+            writeln!(
+                w,
+                r#"      <edge id="{}" source="{}" target="{}" weight="{}"/>"#,
+                i, xml_escape(a), xml_escape(b), weight,
+            )?;
+        }
+        writeln!(w, "    </edges>")?;
+        writeln!(w, "  </graph>")?;
+        writeln!(w, "</gexf>")?;
+        w.flush()
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_formats() {
+        let mut graph = Graph::new();
+        graph.add_node("H04L", 3.0);
+        graph.add_edge("H04L", "G06F", 0.5);
+        graph.add_edge("G06F", "H04L", 0.25);
+        graph.add_edge("A&B", "A&B", 1.0);
+
+        assert_eq!(graph.edges().collect::<Vec<_>>(), vec![("G06F", "H04L", 0.75)]);
+
+        let mut edge_list = Vec::new();
+        graph.write(NetworkFormat::EdgeList, &mut edge_list).unwrap();
+        assert_eq!(String::from_utf8(edge_list).unwrap(), "source,target,weight\nG06F,H04L,0.75\n");
+
+        let mut graphml = Vec::new();
+        graph.write(NetworkFormat::GraphMl, &mut graphml).unwrap();
+        let graphml = String::from_utf8(graphml).unwrap();
+        assert!(graphml.contains(r#"<node id="H04L"><data key="node_weight">3</data></node>"#));
+        assert!(graphml.contains(r#"<edge source="G06F" target="H04L"><data key="edge_weight">0.75</data></edge>"#));
+
+        let mut gexf = Vec::new();
+        graph.write(NetworkFormat::Gexf, &mut gexf).unwrap();
+        let gexf = String::from_utf8(gexf).unwrap();
+        assert!(gexf.contains(r#"<edge id="0" source="G06F" target="H04L" weight="0.75"/>"#));
+    }
+}
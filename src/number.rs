@@ -0,0 +1,271 @@
+//! Patent numbers and kind codes.
+//!
+//! Grant xml zero-pads numbers to eight characters after the type prefix ("D0845432",
+//! "RE047539", "PP031234"), while other datasets drop the zeros ("D845432") or add commas
+//! ("7,654,321"). `PatentNumber` parses any of these and formats either way, so numbers from
+//! different sources can be joined on `to_string()` (unpadded) or `padded()`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// width of the padded form, prefix included
+const PADDED_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PatentType {
+    Utility,
+    /// D
+    Design,
+    /// PP
+    Plant,
+    /// RE
+    Reissue,
+    /// H, statutory invention registration
+    Sir,
+    /// T, defensive publication
+    DefensivePublication,
+    /// X, patents issued before the 1836 renumbering
+    XPatent,
+}
+
+impl PatentType {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            PatentType::Utility => "",
+            PatentType::Design => "D",
+            PatentType::Plant => "PP",
+            PatentType::Reissue => "RE",
+            PatentType::Sir => "H",
+            PatentType::DefensivePublication => "T",
+            PatentType::XPatent => "X",
+        }
+    }
+}
+
+/// two-letter prefixes first, so "PP" isn't read as something else
+const PREFIXED: [PatentType; 6] = [
+    PatentType::Plant,
+    PatentType::Reissue,
+    PatentType::Design,
+    PatentType::Sir,
+    PatentType::DefensivePublication,
+    PatentType::XPatent,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PatentNumber {
+    pub patent_type: PatentType,
+    pub number: u32,
+}
+
+impl PatentNumber {
+    /// as in grant xml, e.g. "D0845432", "10165721"
+    pub fn padded(&self) -> String {
+        let prefix = self.patent_type.prefix();
+        let width = PADDED_WIDTH - prefix.len();
+        format!("{}{:0width$}", prefix, self.number, width = width)
+    }
+}
+
+/// Without leading zeros, e.g. "D845432", "RE47539"
+impl fmt::Display for PatentNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.patent_type.prefix(), self.number)
+    }
+}
+
+/// Accepts padded or unpadded numbers, in any case, with or without a "US" prefix,
+/// commas, or spaces: "RE047539", "re47539", "US 7,654,321".
+impl FromStr for PatentNumber {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidPatentNumber { raw: s.to_owned() };
+
+        let cleaned: String = s.chars()
+            .filter(|c| !c.is_whitespace() && *c != ',' && *c != '-')
+            .collect::<String>()
+            .to_ascii_uppercase();
+        let cleaned = cleaned.strip_prefix("US").unwrap_or(&cleaned);
+
+        let (patent_type, digits) = PREFIXED.iter()
+            .find_map(|t| cleaned.strip_prefix(t.prefix()).map(|digits| (*t, digits)))
+            .unwrap_or((PatentType::Utility, cleaned));
+
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let number: u32 = digits.parse().map_err(|_| invalid())?;
+        if number == 0 {
+            return Err(invalid());
+        }
+
+        Ok(PatentNumber { patent_type, number })
+    }
+}
+
+/// WIPO ST.16 kind codes used by the USPTO
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum KindCode {
+    /// utility grant before 2001
+    A,
+    /// pre-grant publication
+    A1,
+    /// pre-grant republication
+    A2,
+    /// corrected pre-grant publication
+    A9,
+    /// utility grant, no pre-grant publication
+    B1,
+    /// utility grant, with pre-grant publication
+    B2,
+    /// reexamination certificates
+    C1,
+    C2,
+    C3,
+    /// reissue before 2001
+    E,
+    E1,
+    /// statutory invention registration before 2001
+    H,
+    H1,
+    /// plant grant before 2001
+    P,
+    /// plant pre-grant publication
+    P1,
+    /// plant grant, no pre-grant publication
+    P2,
+    /// plant grant, with pre-grant publication
+    P3,
+    P4,
+    P9,
+    /// design grant before 2001
+    S,
+    S1,
+    /// anything else, as given
+    Other(String),
+}
+
+impl KindCode {
+    pub fn as_str(&self) -> &str {
+        match self {
+            KindCode::A => "A",
+            KindCode::A1 => "A1",
+            KindCode::A2 => "A2",
+            KindCode::A9 => "A9",
+            KindCode::B1 => "B1",
+            KindCode::B2 => "B2",
+            KindCode::C1 => "C1",
+            KindCode::C2 => "C2",
+            KindCode::C3 => "C3",
+            KindCode::E => "E",
+            KindCode::E1 => "E1",
+            KindCode::H => "H",
+            KindCode::H1 => "H1",
+            KindCode::P => "P",
+            KindCode::P1 => "P1",
+            KindCode::P2 => "P2",
+            KindCode::P3 => "P3",
+            KindCode::P4 => "P4",
+            KindCode::P9 => "P9",
+            KindCode::S => "S",
+            KindCode::S1 => "S1",
+            KindCode::Other(s) => s,
+        }
+    }
+
+    /// The patent type a grant of this kind has; None for publications, reexaminations,
+    /// and unknown codes.
+    pub fn patent_type(&self) -> Option<PatentType> {
+        match self {
+            KindCode::A | KindCode::B1 | KindCode::B2 => Some(PatentType::Utility),
+            KindCode::E | KindCode::E1 => Some(PatentType::Reissue),
+            KindCode::H | KindCode::H1 => Some(PatentType::Sir),
+            KindCode::P | KindCode::P2 | KindCode::P3 => Some(PatentType::Plant),
+            KindCode::S | KindCode::S1 => Some(PatentType::Design),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for KindCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Never fails; unknown codes become `Other`
+impl FromStr for KindCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_uppercase();
+
+        Ok(match s.as_str() {
+            "A" => KindCode::A,
+            "A1" => KindCode::A1,
+            "A2" => KindCode::A2,
+            "A9" => KindCode::A9,
+            "B1" => KindCode::B1,
+            "B2" => KindCode::B2,
+            "C1" => KindCode::C1,
+            "C2" => KindCode::C2,
+            "C3" => KindCode::C3,
+            "E" => KindCode::E,
+            "E1" => KindCode::E1,
+            "H" => KindCode::H,
+            "H1" => KindCode::H1,
+            "P" => KindCode::P,
+            "P1" => KindCode::P1,
+            "P2" => KindCode::P2,
+            "P3" => KindCode::P3,
+            "P4" => KindCode::P4,
+            "P9" => KindCode::P9,
+            "S" => KindCode::S,
+            "S1" => KindCode::S1,
+            _ => KindCode::Other(s),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_numbers() {
+        let cases = [
+            ("10123456", PatentType::Utility, "10123456", "10123456"),
+            ("07654321", PatentType::Utility, "07654321", "7654321"),
+            ("US 7,654,321", PatentType::Utility, "07654321", "7654321"),
+            ("D0845432", PatentType::Design, "D0845432", "D845432"),
+            ("pp31234", PatentType::Plant, "PP031234", "PP31234"),
+            ("RE047539", PatentType::Reissue, "RE047539", "RE47539"),
+            ("H0002345", PatentType::Sir, "H0002345", "H2345"),
+            ("T0987654", PatentType::DefensivePublication, "T0987654", "T987654"),
+            ("X0000123", PatentType::XPatent, "X0000123", "X123"),
+        ];
+
+        for (raw, patent_type, padded, unpadded) in cases.iter() {
+            let number: PatentNumber = raw.parse().unwrap();
+            assert_eq!(number.patent_type, *patent_type);
+            assert_eq!(number.padded(), *padded);
+            assert_eq!(number.to_string(), *unpadded);
+        }
+
+        assert!("".parse::<PatentNumber>().is_err());
+        assert!("D".parse::<PatentNumber>().is_err());
+        assert!("Z0001234".parse::<PatentNumber>().is_err());
+        assert!("00000000".parse::<PatentNumber>().is_err());
+    }
+
+    #[test]
+    fn kind_codes() {
+        assert_eq!("b2".parse::<KindCode>().unwrap(), KindCode::B2);
+        assert_eq!(KindCode::S1.patent_type(), Some(PatentType::Design));
+        assert_eq!("I4".parse::<KindCode>().unwrap(), KindCode::Other("I4".to_owned()));
+        assert_eq!(KindCode::Other("I4".to_owned()).to_string(), "I4");
+    }
+}
@@ -0,0 +1,451 @@
+//! PostgreSQL `COPY` output for the normalized tables in `tables`.
+//!
+//! Writes a directory with `schema.sql` (DDL) and one file per table:
+//!
+//! - text format: `<table>.sql`, a `COPY <table> (...) FROM STDIN;` command followed by the
+//!   rows and the `\.` terminator. Load with `psql -f schema.sql`, then `psql -f <table>.sql`
+//!   for each table (or `cat schema.sql patents.sql ... | psql`).
+//! - binary format: `<table>.bin` in `COPY ... WITH (FORMAT binary)` layout, plus `load.sql`
+//!   with a `\copy` line per table. Load with `psql -f schema.sql -f load.sql` from inside the
+//!   directory.
+//!
+//! All columns are text, so no type-specific binary encoding is needed.
+//!
+//! Rows are staged in memory until `commit`, so a week that fails part way through can be
+//! dropped with `rollback`, the way the sqlite export rolls back its transaction.
+
+use snafu::ResultExt;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::data::PatentGrant;
+use crate::error::Error;
+use crate::error::WritePgCopy;
+use crate::tables::{
+    AgentRow, ApplicantRow, AssigneeRow, CitationRow, ClaimRow, ClassificationRow, ExaminerRow,
+    InventorRow, PatentRow, TableRow, Tables,
+};
+
+const SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS patents (
+    doc_number text PRIMARY KEY,
+    kind text,
+    date text NOT NULL,
+    country text NOT NULL,
+    application_doc_number text NOT NULL,
+    application_date text NOT NULL,
+    us_application_series_code text NOT NULL,
+    us_term_of_grant text NOT NULL,
+    invention_title text NOT NULL,
+    number_of_claims text NOT NULL,
+    us_exemplary_claim text NOT NULL,
+    us_claim_statement text NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS inventors (
+    doc_number text NOT NULL,
+    sequence text NOT NULL,
+    designation text NOT NULL,
+    orgname text,
+    first_name text,
+    last_name text,
+    city text,
+    state text,
+    country text,
+    PRIMARY KEY (doc_number, sequence)
+);
+
+CREATE TABLE IF NOT EXISTS assignees (
+    doc_number text NOT NULL,
+    sequence text NOT NULL,
+    orgname text,
+    first_name text,
+    last_name text,
+    role text,
+    city text,
+    state text,
+    country text,
+    PRIMARY KEY (doc_number, sequence)
+);
+
+CREATE TABLE IF NOT EXISTS applicants (
+    doc_number text NOT NULL,
+    sequence text NOT NULL,
+    app_type text NOT NULL,
+    designation text NOT NULL,
+    applicant_authority_category text,
+    orgname text,
+    first_name text,
+    last_name text,
+    city text,
+    state text,
+    country text,
+    residence text,
+    PRIMARY KEY (doc_number, sequence)
+);
+
+CREATE TABLE IF NOT EXISTS agents (
+    doc_number text NOT NULL,
+    sequence text NOT NULL,
+    rep_type text NOT NULL,
+    orgname text,
+    first_name text,
+    last_name text,
+    city text,
+    state text,
+    country text,
+    PRIMARY KEY (doc_number, sequence)
+);
+
+CREATE TABLE IF NOT EXISTS examiners (
+    doc_number text NOT NULL,
+    examiner_type text NOT NULL,
+    first_name text NOT NULL,
+    last_name text NOT NULL,
+    department text,
+    PRIMARY KEY (doc_number, examiner_type)
+);
+
+CREATE TABLE IF NOT EXISTS classifications (
+    doc_number text NOT NULL,
+    sequence text NOT NULL,
+    scheme text NOT NULL,
+    role text NOT NULL,
+    symbol text NOT NULL,
+    version text,
+    PRIMARY KEY (doc_number, sequence)
+);
+
+CREATE TABLE IF NOT EXISTS citations (
+    doc_number text NOT NULL,
+    sequence text NOT NULL,
+    cited_country text,
+    cited_doc_number text,
+    cited_kind text,
+    cited_name text,
+    cited_date text,
+    npl_text text,
+    category text,
+    PRIMARY KEY (doc_number, sequence)
+);
+
+CREATE TABLE IF NOT EXISTS claims (
+    doc_number text NOT NULL,
+    sequence text NOT NULL,
+    text text NOT NULL,
+    PRIMARY KEY (doc_number, sequence)
+);
+";
+
+/// 11-byte signature, then flags and header extension length (both zero)
+const BINARY_HEADER: &[u8] = b"PGCOPY\n\xff\r\n\0\0\0\0\0\0\0\0\0";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PgCopyFormat {
+    Text,
+    Binary,
+}
+
+/// One table's copy file, and the rows staged for it
+struct TableFile {
+    file: BufWriter<fs::File>,
+    staged: Vec<u8>,
+}
+
+pub struct PgCopyWriter {
+    format: PgCopyFormat,
+    patents: TableFile,
+    inventors: TableFile,
+    assignees: TableFile,
+    applicants: TableFile,
+    agents: TableFile,
+    examiners: TableFile,
+    classifications: TableFile,
+    citations: TableFile,
+    claims: TableFile,
+}
+
+impl PgCopyWriter {
+    /// creates the directory if it doesn't exist, and writes `schema.sql`.
+    pub fn from_dir(dir: &Path, format: PgCopyFormat) -> Result<Self, Error> {
+        fs::create_dir_all(dir)
+            .context(WritePgCopy)?;
+        fs::write(dir.join("schema.sql"), SCHEMA)
+            .context(WritePgCopy)?;
+        if format == PgCopyFormat::Binary {
+            // table_file appends to this
+            fs::write(dir.join("load.sql"), "")
+                .context(WritePgCopy)?;
+        }
+
+        Ok(Self {
+            format,
+            patents: table_file::<PatentRow>(dir, format)?,
+            inventors: table_file::<InventorRow>(dir, format)?,
+            assignees: table_file::<AssigneeRow>(dir, format)?,
+            applicants: table_file::<ApplicantRow>(dir, format)?,
+            agents: table_file::<AgentRow>(dir, format)?,
+            examiners: table_file::<ExaminerRow>(dir, format)?,
+            classifications: table_file::<ClassificationRow>(dir, format)?,
+            citations: table_file::<CitationRow>(dir, format)?,
+            claims: table_file::<ClaimRow>(dir, format)?,
+        })
+    }
+
+    fn tables(&mut self) -> [&mut TableFile; 9] {
+        [
+            &mut self.patents,
+            &mut self.inventors,
+            &mut self.assignees,
+            &mut self.applicants,
+            &mut self.agents,
+            &mut self.examiners,
+            &mut self.classifications,
+            &mut self.citations,
+            &mut self.claims,
+        ]
+    }
+
+    /// stages the grant's rows; they're written by the next `commit`.
+    pub fn write(&mut self, pg: &PatentGrant) -> Result<(), Error> {
+        let tables = Tables::from(pg);
+        let format = self.format;
+
+        write_rows(&mut self.patents.staged, format, &tables.patents)?;
+        write_rows(&mut self.inventors.staged, format, &tables.inventors)?;
+        write_rows(&mut self.assignees.staged, format, &tables.assignees)?;
+        write_rows(&mut self.applicants.staged, format, &tables.applicants)?;
+        write_rows(&mut self.agents.staged, format, &tables.agents)?;
+        write_rows(&mut self.examiners.staged, format, &tables.examiners)?;
+        write_rows(&mut self.classifications.staged, format, &tables.classifications)?;
+        write_rows(&mut self.citations.staged, format, &tables.citations)?;
+        write_rows(&mut self.claims.staged, format, &tables.claims)?;
+
+        Ok(())
+    }
+
+    /// writes the staged rows to the table files.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        for table in self.tables().iter_mut() {
+            table.file.write_all(&table.staged)
+                .context(WritePgCopy)?;
+            table.staged.clear();
+        }
+        Ok(())
+    }
+
+    /// drops the staged rows.
+    pub fn rollback(&mut self) {
+        for table in self.tables().iter_mut() {
+            table.staged.clear();
+        }
+    }
+
+    /// Writes the end-of-data marker to each table; files are incomplete until this is called.
+    /// Rows still staged are dropped.
+    pub fn finish(mut self) -> Result<(), Error> {
+        let trailer: &[u8] = match self.format {
+            PgCopyFormat::Text => b"\\.\n",
+            PgCopyFormat::Binary => &[0xff, 0xff], // -1 as i16
+        };
+
+        for table in self.tables().iter_mut() {
+            table.file.write_all(trailer)
+                .and_then(|_| table.file.flush())
+                .context(WritePgCopy)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn table_file<R: TableRow>(dir: &Path, format: PgCopyFormat) -> Result<TableFile, Error> {
+    let columns = R::COLUMNS.join(", ");
+
+    let (file_name, header) = match format {
+        PgCopyFormat::Text => {
+            let header = format!("COPY {} ({}) FROM STDIN;\n", R::TABLE, columns);
+            (format!("{}.sql", R::TABLE), header.into_bytes())
+        },
+        PgCopyFormat::Binary => {
+            // binary data can't be inlined in a script, so loading goes through load.sql
+            let mut load = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join("load.sql"))
+                .context(WritePgCopy)?;
+            writeln!(load, "\\copy {} ({}) FROM '{}.bin' WITH (FORMAT binary)", R::TABLE, columns, R::TABLE)
+                .context(WritePgCopy)?;
+
+            (format!("{}.bin", R::TABLE), BINARY_HEADER.to_vec())
+        },
+    };
+
+    let mut wtr = BufWriter::new(fs::File::create(dir.join(file_name)).context(WritePgCopy)?);
+    wtr.write_all(&header)
+        .context(WritePgCopy)?;
+
+    Ok(TableFile { file: wtr, staged: Vec::new() })
+}
+
+fn write_rows<W: Write, R: TableRow>(wtr: &mut W, format: PgCopyFormat, rows: &[R]) -> Result<(), Error> {
+    for row in rows {
+        match format {
+            PgCopyFormat::Text => write_text_row(wtr, &row.values()),
+            PgCopyFormat::Binary => write_binary_row(wtr, &row.values()),
+        }
+        .context(WritePgCopy)?;
+    }
+    Ok(())
+}
+
+/// tab-separated, `\N` for null, with backslash escapes for backslash, tab, and newlines.
+fn write_text_row<W: Write>(wtr: &mut W, values: &[Option<&str>]) -> std::io::Result<()> {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            wtr.write_all(b"\t")?;
+        }
+        match value {
+            None => wtr.write_all(b"\\N")?,
+            Some(value) => {
+                for c in value.chars() {
+                    match c {
+                        '\\' => wtr.write_all(b"\\\\")?,
+                        '\t' => wtr.write_all(b"\\t")?,
+                        '\n' => wtr.write_all(b"\\n")?,
+                        '\r' => wtr.write_all(b"\\r")?,
+                        _ => write!(wtr, "{}", c)?,
+                    }
+                }
+            },
+        }
+    }
+    wtr.write_all(b"\n")
+}
+
+/// field count, then for each field its byte length (-1 for null) and utf-8 bytes.
+/// Everything is big-endian.
+fn write_binary_row<W: Write>(wtr: &mut W, values: &[Option<&str>]) -> std::io::Result<()> {
+    wtr.write_all(&(values.len() as i16).to_be_bytes())?;
+    for value in values {
+        match value {
+            None => wtr.write_all(&(-1i32).to_be_bytes())?,
+            Some(value) => {
+                wtr.write_all(&(value.len() as i32).to_be_bytes())?;
+                wtr.write_all(value.as_bytes())?;
+            },
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stand-in for the server side of COPY: parses text format the way postgres does.
+    fn parse_text_row(line: &str) -> Vec<Option<String>> {
+        line.split('\t')
+            .map(|field| {
+                if field == "\\N" {
+                    return None;
+                }
+                let mut out = String::new();
+                let mut chars = field.chars();
+                while let Some(c) = chars.next() {
+                    if c == '\\' {
+                        match chars.next() {
+                            Some('t') => out.push('\t'),
+                            Some('n') => out.push('\n'),
+                            Some('r') => out.push('\r'),
+                            Some(c) => out.push(c),
+                            None => panic!("dangling backslash"),
+                        }
+                    } else {
+                        out.push(c);
+                    }
+                }
+                Some(out)
+            })
+            .collect()
+    }
+
+    // Stand-in for the server side of binary COPY, for one row.
+    fn parse_binary_row(mut bytes: &[u8]) -> Vec<Option<String>> {
+        let take = |bytes: &mut &[u8], n: usize| {
+            let (head, tail) = bytes.split_at(n);
+            *bytes = tail;
+            head.to_vec()
+        };
+        let count = i16::from_be_bytes([bytes[0], bytes[1]]);
+        bytes = &bytes[2..];
+
+        (0..count)
+            .map(|_| {
+                let len = take(&mut bytes, 4);
+                let len = i32::from_be_bytes([len[0], len[1], len[2], len[3]]);
+                if len < 0 {
+                    None
+                } else {
+                    Some(String::from_utf8(take(&mut bytes, len as usize)).unwrap())
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let values = vec![Some("10123456"), None, Some("tab\there\\ and\nnewline"), Some("")];
+
+        let mut out = Vec::new();
+        write_text_row(&mut out, &values).unwrap();
+        let line = String::from_utf8(out).unwrap();
+
+        assert_eq!(line.matches('\n').count(), 1);
+        let parsed = parse_text_row(line.trim_end_matches('\n'));
+        let expected: Vec<Option<String>> = values.iter().map(|v| v.map(|s| s.to_owned())).collect();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let values = vec![Some("D0845432"), None, Some("Müller")];
+
+        let mut out = Vec::new();
+        write_binary_row(&mut out, &values).unwrap();
+
+        let expected: Vec<Option<String>> = values.iter().map(|v| v.map(|s| s.to_owned())).collect();
+        assert_eq!(parse_binary_row(&out), expected);
+    }
+
+    #[test]
+    fn rolled_back_rows_are_dropped() {
+        let dir = std::env::temp_dir().join("uspto_pgcopy_rolled_back_rows_are_dropped");
+        let mut wtr = PgCopyWriter::from_dir(&dir, PgCopyFormat::Text).unwrap();
+
+        let grant = |doc_number: &str| {
+            let mut pg = PatentGrant::default();
+            pg.us_bibliographic_data_grant.publication_reference.doc_number = doc_number.to_owned();
+            pg
+        };
+        wtr.write(&grant("10000001")).unwrap();
+        wtr.commit().unwrap();
+        wtr.write(&grant("10000002")).unwrap();
+        wtr.rollback();
+        wtr.write(&grant("10000003")).unwrap();
+        wtr.finish().unwrap();
+
+        let patents = fs::read_to_string(dir.join("patents.sql")).unwrap();
+        let doc_numbers: Vec<&str> = patents.lines()
+            .filter_map(|line| line.split('\t').next())
+            .collect();
+        assert_eq!(doc_numbers[1..], ["10000001", "\\."]);
+    }
+
+    #[test]
+    fn binary_header() {
+        assert_eq!(BINARY_HEADER.len(), 19);
+        assert_eq!(&BINARY_HEADER[..11], b"PGCOPY\n\xff\r\n\0");
+    }
+}
@@ -0,0 +1,447 @@
+//! US regions of inventors and assignees: counties, CBSAs, MSAs and commuting zones.
+//!
+//! Grants give a US party's city and state only, so regions take two crosswalks:
+//! - city to county, from a csv (`with_places`) or from a GeoNames gazetteer
+//!   (`with_gazetteer`), whose admin2 codes for US places are county FIPS codes.
+//! - county to region, from the OMB CBSA delineation (`with_cbsas`) and a commuting zone
+//!   table such as the USDA ERS one (`with_commuting_zones`).
+//!
+//! Only the state FIPS codes are bundled. The city, CBSA and commuting zone crosswalks are not
+//! bundled yet: each needs a sourced vintage (e.g. the July 2023 OMB delineation, the 2000
+//! USDA ERS commuting zones), and until one is checked in they have to be passed as files,
+//! which the cli requires for the level asked for. The csv formats, all with a header row, are:
+//!
+//! | table           | columns                                       |
+//! |-----------------|-----------------------------------------------|
+//! | places          | state,city,county_fips[,weight]               |
+//! | cbsas           | county_fips,cbsa_code,cbsa_title,cbsa_type    |
+//! | commuting zones | county_fips,commuting_zone                    |
+//!
+//! `state` is the postal code grants use; `county_fips` the five digit state and county
+//! code. A city in several counties is split among them by `weight` (e.g. population),
+//! equally if there's none. `cbsa_type` is "Metropolitan Statistical Area" or
+//! "Micropolitan Statistical Area"; MSAs are the metropolitan CBSAs.
+
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::country::{CountryCode, CountryNormalizer, SuccessorPolicy};
+use crate::cube::{CountryOf, Counting};
+use crate::data::{Address, BibliographicDataGrant, PatentGrant};
+use crate::error::{Error, ReadTable};
+use crate::geocode::{normalize, Gazetteer};
+
+/// postal code and FIPS code of the states, DC and territories
+const STATE_FIPS: [(&str, &str); 56] = [
+    ("AL", "01"), ("AK", "02"), ("AZ", "04"), ("AR", "05"), ("CA", "06"), ("CO", "08"),
+    ("CT", "09"), ("DE", "10"), ("DC", "11"), ("FL", "12"), ("GA", "13"), ("HI", "15"),
+    ("ID", "16"), ("IL", "17"), ("IN", "18"), ("IA", "19"), ("KS", "20"), ("KY", "21"),
+    ("LA", "22"), ("ME", "23"), ("MD", "24"), ("MA", "25"), ("MI", "26"), ("MN", "27"),
+    ("MS", "28"), ("MO", "29"), ("MT", "30"), ("NE", "31"), ("NV", "32"), ("NH", "33"),
+    ("NJ", "34"), ("NM", "35"), ("NY", "36"), ("NC", "37"), ("ND", "38"), ("OH", "39"),
+    ("OK", "40"), ("OR", "41"), ("PA", "42"), ("RI", "44"), ("SC", "45"), ("SD", "46"),
+    ("TN", "47"), ("TX", "48"), ("UT", "49"), ("VT", "50"), ("VA", "51"), ("WA", "53"),
+    ("WV", "54"), ("WI", "55"), ("WY", "56"), ("AS", "60"), ("GU", "66"), ("MP", "69"),
+    ("PR", "72"), ("VI", "78"),
+];
+
+/// FIPS code of a state's postal code, e.g. "06" for CA
+pub fn state_fips(state: &str) -> Option<&'static str> {
+    let state = state.trim().to_ascii_uppercase();
+    STATE_FIPS.iter().find(|(postal, _)| *postal == state).map(|(_, fips)| *fips)
+}
+
+/// five digits, restoring leading zeros lost in spreadsheets ("6085" is "06085")
+fn county_fips(raw: &str) -> String {
+    format!("{:0>5}", raw.trim())
+}
+
+/// The regions US parties are assigned to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionLevel {
+    County,
+    /// metropolitan and micropolitan
+    Cbsa,
+    /// metropolitan CBSAs only
+    Msa,
+    CommutingZone,
+}
+
+impl FromStr for RegionLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "county" => Ok(RegionLevel::County),
+            "cbsa" => Ok(RegionLevel::Cbsa),
+            "msa" => Ok(RegionLevel::Msa),
+            "cz" => Ok(RegionLevel::CommutingZone),
+            _ => Err(format!("unknown region level {:?}, expected one of: county, cbsa, msa, cz", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cbsa {
+    pub code: String,
+    pub title: String,
+    pub metropolitan: bool,
+}
+
+#[derive(Deserialize)]
+struct PlaceRecord {
+    state: String,
+    city: String,
+    county_fips: String,
+    #[serde(default)]
+    weight: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct CbsaRecord {
+    county_fips: String,
+    cbsa_code: String,
+    cbsa_title: String,
+    cbsa_type: String,
+}
+
+#[derive(Deserialize)]
+struct CommutingZoneRecord {
+    county_fips: String,
+    commuting_zone: String,
+}
+
+/// City-to-county and county-to-region crosswalks
+#[derive(Debug, Clone)]
+pub struct Regions {
+    // (state, normalized city) to counties and their weights
+    places: HashMap<(String, String), Vec<(String, f64)>>,
+    cbsas: HashMap<String, Cbsa>,
+    commuting_zones: HashMap<String, String>,
+    countries: CountryNormalizer,
+}
+
+impl Default for Regions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Regions {
+    pub fn new() -> Self {
+        Self {
+            places: HashMap::new(),
+            cbsas: HashMap::new(),
+            commuting_zones: HashMap::new(),
+            countries: CountryNormalizer::new(CountryCode::Alpha2, SuccessorPolicy::Successor),
+        }
+    }
+
+    /// csv: state,city,county_fips[,weight]
+    pub fn with_places<R: Read>(mut self, r: R) -> Result<Self, Error> {
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: PlaceRecord = record.context(ReadTable)?;
+            self.insert_place(&record.state, &record.city, &record.county_fips, record.weight.unwrap_or(1.0));
+        }
+        Ok(self)
+    }
+
+    /// Cities of the gazetteer's US places, for those not already added. Where a state has
+    /// several places of one name, the most populous is taken.
+    pub fn with_gazetteer(mut self, gazetteer: &Gazetteer) -> Self {
+        let mut found: BTreeMap<(String, String), (String, u64)> = BTreeMap::new();
+        for place in gazetteer.places().iter().filter(|p| p.country == "US" && !p.admin2.is_empty()) {
+            let (fips, city) = match (state_fips(&place.admin1), normalize(&place.name)) {
+                (Some(fips), Some(city)) => (fips, city),
+                _ => continue,
+            };
+            let key = (place.admin1.to_ascii_uppercase(), city);
+            let county = format!("{}{}", fips, place.admin2);
+            match found.get(&key) {
+                Some((_, population)) if *population >= place.population => (),
+                _ => {
+                    found.insert(key, (county, place.population));
+                },
+            }
+        }
+
+        for (key, (county, _)) in found {
+            self.places.entry(key).or_insert_with(|| vec![(county, 1.0)]);
+        }
+        self
+    }
+
+    /// csv: county_fips,cbsa_code,cbsa_title,cbsa_type
+    pub fn with_cbsas<R: Read>(mut self, r: R) -> Result<Self, Error> {
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: CbsaRecord = record.context(ReadTable)?;
+            self.insert_cbsa(&record.county_fips, Cbsa {
+                code: record.cbsa_code,
+                title: record.cbsa_title,
+                metropolitan: record.cbsa_type.trim().starts_with("Metro"),
+            });
+        }
+        Ok(self)
+    }
+
+    /// csv: county_fips,commuting_zone
+    pub fn with_commuting_zones<R: Read>(mut self, r: R) -> Result<Self, Error> {
+        for record in csv::Reader::from_reader(r).deserialize() {
+            let record: CommutingZoneRecord = record.context(ReadTable)?;
+            self.insert_commuting_zone(&record.county_fips, &record.commuting_zone);
+        }
+        Ok(self)
+    }
+
+    pub fn insert_place(&mut self, state: &str, city: &str, county: &str, weight: f64) {
+        if let Some(city) = normalize(city) {
+            self.places.entry((state.trim().to_ascii_uppercase(), city))
+                .or_default()
+                .push((county_fips(county), weight));
+        }
+    }
+
+    pub fn insert_cbsa(&mut self, county: &str, cbsa: Cbsa) {
+        self.cbsas.insert(county_fips(county), cbsa);
+    }
+
+    pub fn insert_commuting_zone(&mut self, county: &str, commuting_zone: &str) {
+        self.commuting_zones.insert(county_fips(county), commuting_zone.trim().to_owned());
+    }
+
+    /// Counties of a US address with their shares, adding up to one. Empty for addresses
+    /// outside the US and cities not in the crosswalk.
+    pub fn counties(&self, address: &Address) -> Vec<(String, f64)> {
+        if self.countries.address_code(address) != Some("US") {
+            return Vec::new();
+        }
+        let key = match (address.state.as_deref(), address.city.as_deref().and_then(normalize)) {
+            (Some(state), Some(city)) => (state.trim().to_ascii_uppercase(), city),
+            _ => return Vec::new(),
+        };
+
+        match self.places.get(&key) {
+            Some(counties) => {
+                let total: f64 = counties.iter().map(|(_, weight)| weight).sum();
+                if total > 0.0 {
+                    counties.iter().map(|(county, weight)| (county.clone(), weight / total)).collect()
+                } else {
+                    let share = 1.0 / counties.len() as f64;
+                    counties.iter().map(|(county, _)| (county.clone(), share)).collect()
+                }
+            },
+            None => Vec::new(),
+        }
+    }
+
+    pub fn cbsa(&self, county: &str) -> Option<&Cbsa> {
+        self.cbsas.get(county)
+    }
+
+    pub fn cbsa_title(&self, code: &str) -> Option<&str> {
+        self.cbsas.values().find(|cbsa| cbsa.code == code).map(|cbsa| cbsa.title.as_str())
+    }
+
+    pub fn commuting_zone(&self, county: &str) -> Option<&str> {
+        self.commuting_zones.get(county).map(|cz| cz.as_str())
+    }
+
+    /// The region of a county at `level`; None for counties outside any CBSA (or MSA) and
+    /// those missing from the crosswalk.
+    pub fn region(&self, county: &str, level: RegionLevel) -> Option<String> {
+        match level {
+            RegionLevel::County => Some(county.to_owned()),
+            RegionLevel::Cbsa => self.cbsa(county).map(|cbsa| cbsa.code.clone()),
+            RegionLevel::Msa => self.cbsa(county).filter(|cbsa| cbsa.metropolitan).map(|cbsa| cbsa.code.clone()),
+            RegionLevel::CommutingZone => self.commuting_zone(county).map(|cz| cz.to_owned()),
+        }
+    }
+
+    /// Regions of an address and their shares. These add up to less than one when some of
+    /// its counties are in no region.
+    pub fn regions(&self, address: &Address, level: RegionLevel) -> Vec<(String, f64)> {
+        let mut regions: Vec<(String, f64)> = Vec::new();
+        for (county, share) in self.counties(address) {
+            if let Some(region) = self.region(&county, level) {
+                accumulate(&mut regions, &region, share);
+            }
+        }
+        regions
+    }
+
+    /// Each region's share of a grant, in document order. `counting` is as for countries in
+    /// `cube`: fractional shares are among all inventors (or assignees) with a country, so
+    /// those outside the US, or in no region, take their share with them.
+    pub fn weights(
+        &self,
+        dg: &BibliographicDataGrant,
+        country_of: CountryOf,
+        counting: Counting,
+        level: RegionLevel,
+        ) -> Vec<(String, f64)>
+    {
+        let addresses: Vec<&Address> = match country_of {
+            CountryOf::Inventor => dg.inventors.iter().map(|i| &i.addressbook.address).collect(),
+            CountryOf::Assignee => dg.assignees.iter().map(|a| &a.addressbook.address).collect(),
+        };
+        // parties without a country are left out, as they are for countries
+        let parties: Vec<Vec<(String, f64)>> = addresses.into_iter()
+            .filter(|address| self.countries.address_code(address).is_some())
+            .map(|address| self.regions(address, level))
+            .collect();
+
+        let mut weights: Vec<(String, f64)> = Vec::new();
+        match counting {
+            Counting::Whole => {
+                for (region, _) in parties.iter().flatten() {
+                    if !weights.iter().any(|(r, _)| r == region) {
+                        weights.push((region.clone(), 1.0));
+                    }
+                }
+            },
+            Counting::Fractional => {
+                let share = 1.0 / parties.len() as f64;
+                for (region, weight) in parties.iter().flatten() {
+                    accumulate(&mut weights, region, weight * share);
+                }
+            },
+            Counting::FractionalCountry => {
+                let mut seen = BTreeSet::new();
+                let distinct: Vec<&String> = parties.iter().flatten()
+                    .map(|(region, _)| region)
+                    .filter(|region| seen.insert(*region))
+                    .collect();
+                let share = 1.0 / distinct.len() as f64;
+                weights = distinct.into_iter().map(|region| (region.clone(), share)).collect();
+            },
+            Counting::First => {
+                for (region, weight) in parties.iter().take(1).flatten() {
+                    accumulate(&mut weights, region, *weight);
+                }
+            },
+        }
+
+        weights
+    }
+
+    /// One row per county of each US inventor and assignee of a grant; parties that
+    /// couldn't be placed get a row without a county.
+    pub fn rows<'a>(&'a self, pg: &'a PatentGrant) -> Vec<RegionRow<'a>> {
+        let dg = &pg.us_bibliographic_data_grant;
+        let inventors = dg.inventors.iter()
+            .map(|inventor| ("inventor", inventor.sequence.clone(), &inventor.addressbook.address));
+        let assignees = dg.assignees.iter()
+            .enumerate()
+            .map(|(i, assignee)| ("assignee", (i + 1).to_string(), &assignee.addressbook.address));
+
+        let mut rows = Vec::new();
+        for (party, sequence, address) in inventors.chain(assignees) {
+            if self.countries.address_code(address) != Some("US") {
+                continue;
+            }
+            let row = |county: Option<String>, share: f64| {
+                let cbsa = county.as_deref().and_then(|county| self.cbsa(county));
+                RegionRow {
+                    doc_number: &dg.publication_reference.doc_number,
+                    party,
+                    sequence: sequence.clone(),
+                    city: address.city.as_deref(),
+                    state: address.state.as_deref(),
+                    commuting_zone: county.as_deref().and_then(|county| self.commuting_zone(county)),
+                    county,
+                    share,
+                    cbsa: cbsa.map(|c| c.code.as_str()),
+                    cbsa_title: cbsa.map(|c| c.title.as_str()),
+                    msa: cbsa.filter(|c| c.metropolitan).map(|c| c.code.as_str()),
+                }
+            };
+
+            let counties = self.counties(address);
+            if counties.is_empty() {
+                rows.push(row(None, 0.0));
+            }
+            for (county, share) in counties {
+                rows.push(row(Some(county), share));
+            }
+        }
+        rows
+    }
+}
+
+/// adds to a region's weight, keeping first-seen order
+fn accumulate(weights: &mut Vec<(String, f64)>, region: &str, weight: f64) {
+    match weights.iter_mut().find(|(r, _)| r == region) {
+        Some((_, total)) => *total += weight,
+        None => weights.push((region.to_owned(), weight)),
+    }
+}
+
+/// One US party's county and its regions
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RegionRow<'a> {
+    pub doc_number: &'a str,
+    /// inventor or assignee
+    pub party: &'static str,
+    pub sequence: String,
+    pub city: Option<&'a str>,
+    pub state: Option<&'a str>,
+    pub county: Option<String>,
+    /// the party's share in the county, for cities split among counties
+    pub share: f64,
+    pub cbsa: Option<&'a str>,
+    pub cbsa_title: Option<&'a str>,
+    pub msa: Option<&'a str>,
+    pub commuting_zone: Option<&'a str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{AddressBook, Inventor};
+
+    fn inventor(city: &str, state: &str, country: &str) -> Inventor {
+        Inventor {
+            addressbook: AddressBook {
+                address: Address {
+                    city: Some(city.to_owned()),
+                    state: Some(state.to_owned()),
+                    country: Some(country.to_owned()),
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn region_weights() {
+        let places = "state,city,county_fips,weight\nCA,San Jose,6085,\nNY,New York,36061,1\nNY,New York,36047,3\n";
+        let cbsas = "county_fips,cbsa_code,cbsa_title,cbsa_type\n\
+            06085,41940,\"San Jose-Sunnyvale-Santa Clara, CA\",Metropolitan Statistical Area\n\
+            36061,35620,\"New York-Newark-Jersey City, NY-NJ-PA\",Metropolitan Statistical Area\n\
+            36047,35620,\"New York-Newark-Jersey City, NY-NJ-PA\",Metropolitan Statistical Area\n";
+        let regions = Regions::new()
+            .with_places(places.as_bytes()).unwrap()
+            .with_cbsas(cbsas.as_bytes()).unwrap();
+
+        let address = &inventor("New York", "NY", "US").addressbook.address;
+        assert_eq!(regions.counties(address), vec![("36061".to_owned(), 0.25), ("36047".to_owned(), 0.75)]);
+        assert_eq!(regions.regions(address, RegionLevel::Msa), vec![("35620".to_owned(), 1.0)]);
+
+        let mut dg = BibliographicDataGrant::default();
+        dg.inventors = vec![
+            inventor("San Jose", "CA", "US"),
+            inventor("New York", "NY", "US"),
+            inventor("New York", "NY", ""),
+            inventor("Munich", "", "DE"),
+        ];
+        let cbsa = |counting| regions.weights(&dg, CountryOf::Inventor, counting, RegionLevel::Cbsa);
+        assert_eq!(cbsa(Counting::Fractional), vec![("41940".to_owned(), 0.25), ("35620".to_owned(), 0.5)]);
+        assert_eq!(cbsa(Counting::First), vec![("41940".to_owned(), 1.0)]);
+        assert_eq!(cbsa(Counting::Whole), vec![("41940".to_owned(), 1.0), ("35620".to_owned(), 1.0)]);
+        assert_eq!(regions.cbsa_title("41940"), Some("San Jose-Sunnyvale-Santa Clara, CA"));
+    }
+}
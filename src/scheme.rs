@@ -0,0 +1,517 @@
+//! CPC and IPC schemes as trees: titles, ancestors, descendants, and roll-up.
+//!
+//! Symbols are normalized to the form grants use, "H04L 9/32", whichever form the source has
+//! ("H04L9/32" in CPC files, "H04L0009320000" in IPC master files). The levels are section
+//! ("H"), class ("H04"), subclass ("H04L"), main group ("H04L 9/00") and subgroup
+//! ("H04L 9/32"); subgroups can nest, so a subgroup's parent may be another subgroup.
+//!
+//! `roll_up` only needs the symbol, so it works without a scheme loaded. A `Scheme` is loaded
+//! from either:
+//! - tsv, as in the CPC title list: `symbol<TAB>title`, or `symbol<TAB>dots<TAB>title` where
+//!   `dots` is a subgroup's indent level. Without dots, subgroups hang off their main group.
+//! - xml, CPC scheme files (nested `classification-item`s) or the IPC master file (nested
+//!   `ipcEntry`s).
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Read};
+use std::str::FromStr;
+
+use crate::error::{Error, ReadTable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Section,
+    Class,
+    Subclass,
+    MainGroup,
+    Subgroup,
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "section" => Ok(Level::Section),
+            "class" => Ok(Level::Class),
+            "subclass" => Ok(Level::Subclass),
+            "main-group" => Ok(Level::MainGroup),
+            "subgroup" => Ok(Level::Subgroup),
+            _ => Err(format!("unknown level {:?}, expected one of: section, class, subclass, main-group, subgroup", s)),
+        }
+    }
+}
+
+/// "H04L9/32", "h04l 9/32" and "H04L0009320000" to "H04L 9/32"; None if it isn't a symbol
+pub fn normalize(symbol: &str) -> Option<String> {
+    let compact: String = symbol.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+    // byte offsets below are char boundaries only in ascii
+    if !compact.is_ascii() {
+        return None;
+    }
+    let b = compact.as_bytes();
+
+    let is_class = |b: &[u8]| b[0].is_ascii_uppercase() && b[1].is_ascii_digit() && b[2].is_ascii_digit();
+    match b.len() {
+        0 | 2 => return None,
+        1 => return if b[0].is_ascii_uppercase() { Some(compact) } else { None },
+        3 => return if is_class(b) { Some(compact) } else { None },
+        _ => (),
+    }
+    if !is_class(b) || !b[3].is_ascii_uppercase() {
+        return None;
+    }
+
+    let subclass = &compact[..4];
+    let rest = &compact[4..];
+    if rest.is_empty() {
+        return Some(subclass.to_owned());
+    }
+
+    let (group, subgroup) = match rest.find('/') {
+        Some(i) => (&rest[..i], rest[i + 1..].to_owned()),
+        // ipc master file form: four digits of main group, six of subgroup, zero-filled
+        None if rest.len() == 10 => {
+            let subgroup = &rest[4..];
+            let trimmed = subgroup.trim_end_matches('0');
+            (&rest[..4], if trimmed.len() < 2 { subgroup[..2].to_owned() } else { trimmed.to_owned() })
+        },
+        None => return None,
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(group) || !digits(&subgroup) {
+        return None;
+    }
+
+    let group: u32 = group.parse().ok()?;
+    if group == 0 {
+        // the ipc master file gives subclasses as "A01B0000000000"
+        return if subgroup.bytes().all(|b| b == b'0') { Some(subclass.to_owned()) } else { None };
+    }
+    Some(format!("{} {}/{}", subclass, group, subgroup))
+}
+
+/// level of a normalized symbol
+fn symbol_level(symbol: &str) -> Level {
+    match symbol.len() {
+        1 => Level::Section,
+        3 => Level::Class,
+        4 => Level::Subclass,
+        _ if symbol.ends_with("/00") => Level::MainGroup,
+        _ => Level::Subgroup,
+    }
+}
+
+pub fn level(symbol: &str) -> Option<Level> {
+    normalize(symbol).map(|symbol| symbol_level(&symbol))
+}
+
+/// The symbol at a coarser (or the same) level, e.g. "H04L 9/32" to "H04L 9/00" at main group
+/// or "H04" at class. None if the symbol is coarser than `level`.
+pub fn roll_up(symbol: &str, level: Level) -> Option<String> {
+    let symbol = normalize(symbol)?;
+    if level > symbol_level(&symbol) {
+        return None;
+    }
+
+    Some(match level {
+        Level::Section => symbol[..1].to_owned(),
+        Level::Class => symbol[..3].to_owned(),
+        Level::Subclass => symbol[..4].to_owned(),
+        Level::MainGroup => format!("{}/00", symbol.split('/').next().unwrap_or("")),
+        Level::Subgroup => symbol,
+    })
+}
+
+/// Sums weights by the symbol each rolls up to; symbols coarser than `level` are left out.
+pub fn roll_up_weights<I>(weights: I, level: Level) -> BTreeMap<String, f64>
+    where I: IntoIterator<Item = (String, f64)>,
+{
+    let mut rolled = BTreeMap::new();
+
+    for (symbol, weight) in weights {
+        if let Some(symbol) = roll_up(&symbol, level) {
+            *rolled.entry(symbol).or_insert(0.0) += weight;
+        }
+    }
+
+    rolled
+}
+
+/// the next level up, by symbol alone
+fn structural_parent(symbol: &str) -> Option<String> {
+    match symbol_level(symbol) {
+        Level::Section => None,
+        Level::Class => roll_up(symbol, Level::Section),
+        Level::Subclass => roll_up(symbol, Level::Class),
+        Level::MainGroup => roll_up(symbol, Level::Subclass),
+        Level::Subgroup => roll_up(symbol, Level::MainGroup),
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Node {
+    title: String,
+    parent: Option<String>,
+    children: Vec<String>,
+}
+
+/// A classification scheme, or the part of one that was loaded
+#[derive(Debug, Default, Clone)]
+pub struct Scheme {
+    nodes: BTreeMap<String, Node>,
+}
+
+impl Scheme {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or retitles a symbol. Without a parent, the parent is the next level up by symbol.
+    /// Missing ancestors are added without titles, so every symbol reaches its section.
+    pub fn insert(&mut self, symbol: &str, title: &str, parent: Option<&str>) -> Result<(), Error> {
+        let symbol = normalize(symbol).ok_or_else(|| Error::InvalidSymbol { raw: symbol.to_owned() })?;
+        let parent = match parent {
+            Some(parent) => Some(normalize(parent).ok_or_else(|| Error::InvalidSymbol { raw: parent.to_owned() })?),
+            None => structural_parent(&symbol),
+        };
+
+        self.attach(&symbol, parent);
+        if !title.is_empty() {
+            if let Some(node) = self.nodes.get_mut(&symbol) {
+                node.title = title.to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    /// adds the node if needed, under `parent` if it doesn't have one yet
+    fn attach(&mut self, symbol: &str, parent: Option<String>) {
+        if matches!(self.nodes.get(symbol), Some(node) if node.parent.is_some()) {
+            return;
+        }
+
+        if let Some(ref parent) = parent {
+            if !self.nodes.contains_key(parent) {
+                self.attach(parent, structural_parent(parent));
+            }
+            let siblings = &mut self.nodes.get_mut(parent).expect("just attached").children;
+            if !siblings.iter().any(|s| s == symbol) {
+                siblings.push(symbol.to_owned());
+            }
+        }
+
+        self.nodes.entry(symbol.to_owned()).or_default().parent = parent;
+    }
+
+    pub fn from_tsv<R: Read>(r: R) -> Result<Self, Error> {
+        let mut scheme = Self::new();
+        let mut rdr = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .flexible(true)
+            .quoting(false)
+            .from_reader(r);
+
+        // open subgroups under the current main group, by dots
+        let mut open: Vec<(usize, String)> = Vec::new();
+
+        for record in rdr.records() {
+            let record = record.context(ReadTable)?;
+            let symbol = match record.get(0).and_then(normalize) {
+                Some(symbol) => symbol,
+                // blank lines and headers
+                None => continue,
+            };
+            let (dots, title) = match record.len() {
+                0 | 1 => (None, ""),
+                2 => (None, &record[1]),
+                _ => (record[1].trim().parse::<usize>().ok(), &record[2]),
+            };
+
+            let parent = match (symbol_level(&symbol), dots) {
+                (Level::MainGroup, _) => {
+                    open = vec![(0, symbol.clone())];
+                    None
+                },
+                (Level::Subgroup, Some(dots)) => {
+                    while matches!(open.last(), Some((d, _)) if *d >= dots) {
+                        open.pop();
+                    }
+                    let parent = open.last().map(|(_, parent)| parent.clone());
+                    open.push((dots, symbol.clone()));
+                    parent
+                },
+                _ => None,
+            };
+
+            scheme.insert(&symbol, title.trim(), parent.as_deref())?;
+        }
+
+        Ok(scheme)
+    }
+
+    pub fn from_xml<B: BufRead>(b: B) -> Result<Self, Error> {
+        let mut scheme = Self::new();
+        let mut rdr = Reader::from_reader(b);
+        rdr.trim_text(true);
+        let mut buf = Vec::new();
+
+        let mut items: Vec<XmlItem> = Vec::new();
+        let mut in_symbol = false;
+        let mut in_title = false;
+        // inside a reference within a title, which isn't part of the title text
+        let mut in_reference = 0;
+
+        loop {
+            match rdr.read_event(&mut buf) {
+                Ok(Event::Start(ref e)) => match e.name() {
+                    b"classification-item" | b"ipcEntry" => {
+                        let item = XmlItem::start(e, &rdr, &mut items, &mut scheme)?;
+                        items.push(item);
+                    },
+                    b"classification-symbol" => in_symbol = true,
+                    b"class-title" | b"title" => in_title = true,
+                    b"title-part" | b"titlePart" if in_title => {
+                        if let Some(item) = items.last_mut() {
+                            item.title_parts.push(String::new());
+                        }
+                    },
+                    b"reference" | b"entryReference" if in_title => in_reference += 1,
+                    _ => (),
+                },
+                Ok(Event::Text(ref e)) => {
+                    if let Some(item) = items.last_mut() {
+                        let text = e.unescape_and_decode(&rdr).map_err(|err| Error::Deser { src: err.to_string() })?;
+                        if in_symbol {
+                            item.symbol = normalize(&text);
+                        } else if in_title && in_reference == 0 && item.inserted.is_none() {
+                            if item.title_parts.is_empty() {
+                                item.title_parts.push(String::new());
+                            }
+                            let part = item.title_parts.last_mut().expect("not empty");
+                            if !part.is_empty() {
+                                part.push(' ');
+                            }
+                            part.push_str(&text);
+                        }
+                    }
+                },
+                Ok(Event::End(ref e)) => match e.name() {
+                    b"classification-item" | b"ipcEntry" => {
+                        if let Some(mut item) = items.pop() {
+                            item.flush(&mut scheme)?;
+                        }
+                    },
+                    b"classification-symbol" => in_symbol = false,
+                    b"class-title" | b"title" => in_title = false,
+                    b"reference" | b"entryReference" if in_title => in_reference -= 1,
+                    _ => (),
+                },
+                Ok(Event::Empty(ref e)) if e.name() == b"ipcEntry" || e.name() == b"classification-item" => {
+                    XmlItem::start(e, &rdr, &mut items, &mut scheme)?.flush(&mut scheme)?;
+                },
+                Ok(Event::Eof) => break,
+                Ok(_) => (),
+                Err(err) => return Err(Error::Deser { src: err.to_string() }),
+            }
+            buf.clear();
+        }
+
+        Ok(scheme)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn contains(&self, symbol: &str) -> bool {
+        matches!(normalize(symbol), Some(symbol) if self.nodes.contains_key(&symbol))
+    }
+
+    /// None if the symbol isn't in the scheme or was only added as an ancestor
+    pub fn title(&self, symbol: &str) -> Option<&str> {
+        self.node(symbol)
+            .map(|node| node.title.as_str())
+            .filter(|title| !title.is_empty())
+    }
+
+    pub fn parent(&self, symbol: &str) -> Option<&str> {
+        self.node(symbol).and_then(|node| node.parent.as_deref())
+    }
+
+    /// nearest first, ending with the section
+    pub fn ancestors(&self, symbol: &str) -> Vec<&str> {
+        let mut ancestors = Vec::new();
+        let mut current = self.parent(symbol);
+
+        while let Some(symbol) = current {
+            ancestors.push(symbol);
+            current = self.parent(symbol);
+        }
+
+        ancestors
+    }
+
+    pub fn children(&self, symbol: &str) -> Vec<&str> {
+        self.node(symbol)
+            .map(|node| node.children.iter().map(|child| child.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// all symbols below, depth first in scheme order
+    pub fn descendants(&self, symbol: &str) -> Vec<&str> {
+        let mut descendants = Vec::new();
+        let mut stack: Vec<&str> = self.children(symbol).into_iter().rev().collect();
+
+        while let Some(symbol) = stack.pop() {
+            descendants.push(symbol);
+            stack.extend(self.children(symbol).into_iter().rev());
+        }
+
+        descendants
+    }
+
+    /// The symbol itself or the ancestor at `level`. Unlike `roll_up`, this follows the loaded
+    /// tree, and is None for symbols not in the scheme.
+    pub fn ancestor_at(&self, symbol: &str, level: Level) -> Option<&str> {
+        let (symbol, _) = self.nodes.get_key_value(&normalize(symbol)?)?;
+
+        std::iter::once(symbol.as_str())
+            .chain(self.ancestors(symbol))
+            .find(|s| symbol_level(s) == level)
+    }
+
+    fn node(&self, symbol: &str) -> Option<&Node> {
+        normalize(symbol).and_then(|symbol| self.nodes.get(&symbol))
+    }
+}
+
+/// an open classification-item or ipcEntry
+#[derive(Default)]
+struct XmlItem {
+    symbol: Option<String>,
+    skip: bool,
+    parent: Option<String>,
+    title_parts: Vec<String>,
+    /// set once added to the scheme, which is before its first child
+    inserted: Option<String>,
+}
+
+impl XmlItem {
+    /// from the attributes of an item's start (or empty) tag; the enclosing item is added to
+    /// the scheme first, so it can be the parent
+    fn start<B: BufRead>(e: &BytesStart, rdr: &Reader<B>, items: &mut [XmlItem], scheme: &mut Scheme) -> Result<Self, Error> {
+        if let Some(item) = items.last_mut() {
+            item.flush(scheme)?;
+        }
+        let mut item = XmlItem::default();
+        for attr in e.attributes() {
+            let attr = attr.map_err(|err| Error::Deser { src: err.to_string() })?;
+            let value = attr.unescape_and_decode_value(rdr).map_err(|err| Error::Deser { src: err.to_string() })?;
+            match attr.key {
+                b"symbol" => item.symbol = normalize(&value),
+                // ipc entries that aren't classes: notes, indexes, guidance headings
+                b"kind" => item.skip = !matches!(value.as_str(), "s" | "c" | "u" | "m")
+                    && !value.bytes().all(|b| b.is_ascii_digit()),
+                _ => (),
+            }
+        }
+        item.parent = items.iter().rev().find_map(|item| item.inserted.clone());
+        Ok(item)
+    }
+
+    fn flush(&mut self, scheme: &mut Scheme) -> Result<(), Error> {
+        if self.inserted.is_some() || self.skip {
+            return Ok(());
+        }
+        if let Some(symbol) = self.symbol.take() {
+            let title = self.title_parts.iter()
+                .map(|part| part.trim())
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join("; ");
+            scheme.insert(&symbol, &title, self.parent.as_deref())?;
+            self.inserted = Some(symbol);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_and_roll_up() {
+        assert_eq!(normalize("H04L9/3226").unwrap(), "H04L 9/3226");
+        assert_eq!(normalize("A01B0001022000").unwrap(), "A01B 1/022");
+        assert_eq!(normalize("A01B0001100000").unwrap(), "A01B 1/10");
+        assert_eq!(normalize("A01B0001000000").unwrap(), "A01B 1/00");
+        assert!(normalize("H04").is_some());
+        assert!(normalize("H0").is_none());
+        assert!(normalize("H04L 9").is_none());
+        assert!(normalize("H04L000é00000").is_none());
+
+        assert_eq!(roll_up("H04L 9/3226", Level::MainGroup).unwrap(), "H04L 9/00");
+        assert_eq!(roll_up("H04L 9/3226", Level::Class).unwrap(), "H04");
+        assert!(roll_up("H04L", Level::MainGroup).is_none());
+
+        let weights = vec![("H04L 9/32".to_owned(), 0.5), ("H04W 4/00".to_owned(), 0.5), ("H04".to_owned(), 1.0)];
+        let rolled = roll_up_weights(weights, Level::Subclass);
+        assert_eq!(rolled.into_iter().collect::<Vec<_>>(), vec![("H04L".to_owned(), 0.5), ("H04W".to_owned(), 0.5)]);
+    }
+
+    #[test]
+    fn tree_from_tsv_and_xml() {
+        let tsv = "A01B\tSOIL WORKING\nA01B1/00\tHand tools\nA01B1/02\t1\tSpades; Shovels\nA01B1/022\t2\t{Collapsible}\nA01B1/04\t1\tHoes\n";
+        let scheme = Scheme::from_tsv(tsv.as_bytes()).unwrap();
+        assert_eq!(scheme.title("A01B 1/02"), Some("Spades; Shovels"));
+        assert_eq!(scheme.ancestors("A01B1/022"), vec!["A01B 1/02", "A01B 1/00", "A01B", "A01", "A"]);
+        assert_eq!(scheme.descendants("A01B 1/00"), vec!["A01B 1/02", "A01B 1/022", "A01B 1/04"]);
+        assert_eq!(scheme.ancestor_at("A01B 1/022", Level::Subclass), Some("A01B"));
+        assert_eq!(scheme.title("A01"), None);
+
+        let xml = r#"<?xml version="1.0"?>
+            <class-scheme>
+              <classification-item level="5"><classification-symbol>A01B</classification-symbol>
+                <class-title><title-part><text>SOIL WORKING</text></title-part></class-title>
+                <classification-item level="7"><classification-symbol>A01B1/00</classification-symbol>
+                  <class-title><title-part><text>Hand tools</text><reference>(edge trimmers <text>A01G3/06</text>)</reference></title-part></class-title>
+                  <classification-item level="8"><classification-symbol>A01B1/02</classification-symbol>
+                    <class-title><title-part><text>Spades</text></title-part><title-part><text>Shovels</text></title-part></class-title>
+                    <classification-item level="9"><classification-symbol>A01B1/022</classification-symbol>
+                      <class-title><title-part><text>Collapsible</text></title-part></class-title>
+                    </classification-item>
+                  </classification-item>
+                </classification-item>
+              </classification-item>
+            </class-scheme>"#;
+        let scheme = Scheme::from_xml(xml.as_bytes()).unwrap();
+        assert_eq!(scheme.title("A01B 1/00"), Some("Hand tools"));
+        assert_eq!(scheme.title("A01B 1/02"), Some("Spades; Shovels"));
+        assert_eq!(scheme.parent("A01B 1/022"), Some("A01B 1/02"));
+
+        let xml = r#"<?xml version="1.0"?>
+            <IPCScheme>
+              <ipcEntry kind="u" symbol="A01B0000000000"><textBody><title><titlePart><text>SOIL WORKING</text></titlePart></title></textBody>
+                <ipcEntry kind="n" symbol="A01B0000000000"><textBody><note><text>a note</text></note></textBody></ipcEntry>
+                <ipcEntry kind="i" symbol="A01B0003000000"/>
+                <ipcEntry kind="m" symbol="A01B0001000000"><textBody><title><titlePart><text>Hand tools</text></titlePart></title></textBody>
+                  <ipcEntry kind="1" symbol="A01B0001020000"><textBody><title><titlePart><text>Spades</text></titlePart></title></textBody></ipcEntry>
+                </ipcEntry>
+              </ipcEntry>
+            </IPCScheme>"#;
+        let scheme = Scheme::from_xml(xml.as_bytes()).unwrap();
+        assert_eq!(scheme.title("A01B"), Some("SOIL WORKING"));
+        assert_eq!(scheme.ancestors("A01B 1/02"), vec!["A01B 1/00", "A01B", "A01", "A"]);
+        assert!(!scheme.contains("A01B 3/00"));
+    }
+}
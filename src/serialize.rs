@@ -1,11 +1,13 @@
 use serde::Serialize;
 use std::convert::From;
 
-use crate::data::PatentGrant;
+use crate::concordance::{derive, UspcConcordance};
+use crate::data::{AddressBook, BibliographicDataGrant, PatentGrant};
+use crate::error::Error;
 
 /// Output format (csv) to be ingested into rows of cube
 ///
-/// No measures; just count
+/// No measures; just count. To aggregate without exporting rows, see `cube::Cube`.
 #[derive(Serialize)]
 pub struct PatentOutput {
     id: String,
@@ -38,7 +40,7 @@ impl From<&PatentGrant> for PatentOutput {
 
         PatentOutput {
             id: dg.publication_reference.doc_number.clone(),
-            date: dg.publication_reference.date.clone(),
+            date: dg.publication_reference.date.to_string(),
             country_inventor,
             country_assignee,
             classification_locarno: dg.classification_locarno.main_classification.clone(),
@@ -46,3 +48,186 @@ impl From<&PatentGrant> for PatentOutput {
         }
     }
 }
+
+/// Extracts one column value; multi-valued fields are joined with the separator.
+type Extractor = fn(&BibliographicDataGrant, &str) -> String;
+
+/// All columns available to `Columns`, in display order.
+///
+/// The `PatentOutput` column names are at the end as aliases, so its layout can be
+/// reproduced with `--columns id,date,country_inventor,...`.
+const REGISTRY: &[(&str, Extractor)] = &[
+    // publication
+    ("doc_number", |dg, _| dg.publication_reference.doc_number.clone()),
+    // without leading zeros, e.g. D845432
+    ("patent_number", |dg, _| {
+        dg.publication_reference.patent_number().map(|number| number.to_string()).unwrap_or_default()
+    }),
+    ("kind", |dg, _| dg.publication_reference.kind.clone().unwrap_or_default()),
+    ("date", |dg, _| dg.publication_reference.date.to_string()),
+    ("country", |dg, _| dg.publication_reference.country.clone()),
+    // application
+    ("app_doc_number", |dg, _| dg.application_reference.doc_number.clone()),
+    ("app_date", |dg, _| dg.application_reference.date.to_string()),
+    ("app_country", |dg, _| dg.application_reference.country.clone()),
+    ("grant_year", |dg, _| dg.grant_year().map(|year| year.to_string()).unwrap_or_default()),
+    ("grant_week", |dg, _| {
+        dg.grant_iso_week().map(|(year, week)| format!("{}-W{:02}", year, week)).unwrap_or_default()
+    }),
+    ("pendency_days", |dg, _| dg.pendency_days().map(|days| days.to_string()).unwrap_or_default()),
+    ("app_series_code", |dg, _| dg.us_application_series_code.clone()),
+    ("term_of_grant", |dg, _| dg.us_term_of_grant.clone()),
+    ("title", |dg, _| dg.invention_title.clone()),
+    ("n_claims", |dg, _| dg.number_of_claims.clone()),
+    ("exemplary_claim", |dg, _| dg.us_exemplary_claim.clone()),
+    // classifications
+    ("cpc_main", |dg, _| dg.classifications_cpc.main_cpc.as_ref().map(|c| c.symbol()).unwrap_or_default()),
+    ("cpc_further", |dg, sep| dg.classifications_cpc.further_cpc.iter().map(|c| c.symbol()).collect::<Vec<_>>().join(sep)),
+    ("cpc_all", |dg, sep| {
+        let cpc = &dg.classifications_cpc;
+        cpc.main_cpc.iter().chain(cpc.further_cpc.iter()).map(|c| c.symbol()).collect::<Vec<_>>().join(sep)
+    }),
+    ("ipc_all", |dg, sep| dg.classifications_ipcr.iter().map(|c| c.symbol()).collect::<Vec<_>>().join(sep)),
+    ("uspc_main", |dg, _| dg.classification_national.main_classification.clone()),
+    ("uspc_further", |dg, _| dg.classification_national.further_classification.clone().unwrap_or_default()),
+    // from ipc, or cpc where there's no ipc
+    ("wipo_fields", |dg, sep| {
+        derive(dg, &UspcConcordance::default()).fields.iter().map(|field| field.id.to_string()).collect::<Vec<_>>().join(sep)
+    }),
+    ("locarno", |dg, _| dg.classification_locarno.main_classification.clone()),
+    ("locarno_edition", |dg, _| dg.classification_locarno.edition.clone()),
+    ("search_uspc", |dg, sep| join(dg.us_field_of_classification_search.classification_nationals.iter().map(|c| c.main_classification.as_str()), sep)),
+    ("search_cpc", |dg, sep| join(dg.us_field_of_classification_search.classification_cpc_texts.iter().map(|s| s.as_str()), sep)),
+    // citations
+    ("n_citations", |dg, _| dg.us_references_cited.len().to_string()),
+    ("cited_doc_numbers", |dg, sep| join(dg.us_references_cited.iter().filter_map(|c| c.patcit.as_ref()).map(|d| d.doc_number.as_str()), sep)),
+    // parties
+    ("n_applicants", |dg, _| dg.us_applicants.len().to_string()),
+    ("applicant_names", |dg, sep| names(dg.us_applicants.iter().map(|a| &a.addressbook), sep)),
+    ("applicant_countries", |dg, sep| countries(dg.us_applicants.iter().map(|a| &a.addressbook), sep)),
+    ("applicant_residences", |dg, sep| join(dg.us_applicants.iter().filter_map(|a| a.residence.as_deref()), sep)),
+    ("n_inventors", |dg, _| dg.inventors.len().to_string()),
+    ("inventor_names", |dg, sep| names(dg.inventors.iter().map(|i| &i.addressbook), sep)),
+    ("inventor_cities", |dg, sep| join(dg.inventors.iter().filter_map(|i| i.addressbook.address.city.as_deref()), sep)),
+    ("inventor_states", |dg, sep| join(dg.inventors.iter().filter_map(|i| i.addressbook.address.state.as_deref()), sep)),
+    ("inventor_countries", |dg, sep| countries(dg.inventors.iter().map(|i| &i.addressbook), sep)),
+    ("agent_names", |dg, sep| names(dg.agents.iter().map(|a| &a.addressbook), sep)),
+    ("n_assignees", |dg, _| dg.assignees.len().to_string()),
+    ("assignee_names", |dg, sep| {
+        join(dg.assignees.iter().filter_map(|a| a.orgname.as_deref().or(a.addressbook.orgname.as_deref())), sep)
+    }),
+    ("assignee_countries", |dg, sep| countries(dg.assignees.iter().map(|a| &a.addressbook), sep)),
+    ("primary_examiner", |dg, _| person(&dg.examiners.primary_examiner.first_name, &dg.examiners.primary_examiner.last_name)),
+    ("assistant_examiner", |dg, _| person(&dg.examiners.assistant_examiner.first_name, &dg.examiners.assistant_examiner.last_name)),
+    ("examiner_department", |dg, _| dg.examiners.primary_examiner.department.clone().unwrap_or_default()),
+    // PatentOutput aliases
+    ("id", |dg, _| dg.publication_reference.doc_number.clone()),
+    ("country_inventor", |dg, sep| countries(dg.inventors.iter().map(|i| &i.addressbook), sep)),
+    ("country_assignee", |dg, sep| countries(dg.assignees.iter().map(|a| &a.addressbook), sep)),
+    ("classification_locarno", |dg, _| dg.classification_locarno.main_classification.clone()),
+    ("classification_national", |dg, _| dg.classification_national.main_classification.clone()),
+];
+
+fn join<'a, I: Iterator<Item = &'a str>>(values: I, sep: &str) -> String {
+    values.collect::<Vec<_>>().join(sep)
+}
+
+fn countries<'a, I: Iterator<Item = &'a AddressBook>>(abs: I, sep: &str) -> String {
+    join(abs.filter_map(|ab| ab.address.country.as_deref()), sep)
+}
+
+/// orgname if there is one, else "first last"
+fn names<'a, I: Iterator<Item = &'a AddressBook>>(abs: I, sep: &str) -> String {
+    abs.map(|ab| match ab.orgname {
+            Some(ref orgname) => orgname.clone(),
+            None => person(
+                ab.first_name.as_deref().unwrap_or_default(),
+                ab.last_name.as_deref().unwrap_or_default(),
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn person(first_name: &str, last_name: &str) -> String {
+    format!("{} {}", first_name, last_name).trim().to_owned()
+}
+
+/// User-selected output columns, an alternative to the fixed `PatentOutput` layout.
+///
+/// Built from a comma-separated list of names from the registry (see `Columns::available`),
+/// e.g. "doc_number,app_date,cpc_main,inventor_countries,n_claims".
+pub struct Columns {
+    columns: Vec<(&'static str, Extractor)>,
+    separator: String,
+}
+
+impl Columns {
+    /// `separator` joins multi-valued fields, e.g. inventor countries
+    pub fn new(spec: &str, separator: &str) -> Result<Self, Error> {
+        let columns = spec.split(',')
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                REGISTRY.iter()
+                    .find(|(registered, _)| *registered == name)
+                    .cloned()
+                    .ok_or_else(|| Error::UnknownColumn {
+                        name: name.to_owned(),
+                        available: Columns::available().collect::<Vec<_>>().join(","),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            columns,
+            separator: separator.to_owned(),
+        })
+    }
+
+    /// names of all columns that can be selected
+    pub fn available() -> impl Iterator<Item = &'static str> {
+        REGISTRY.iter().map(|(name, _)| *name)
+    }
+
+    pub fn header(&self) -> Vec<&str> {
+        self.columns.iter().map(|(name, _)| *name).collect()
+    }
+
+    pub fn row(&self, pg: &PatentGrant) -> Vec<String> {
+        let dg = &pg.us_bibliographic_data_grant;
+
+        self.columns.iter()
+            .map(|(_, extract)| extract(dg, &self.separator))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Address, Inventor};
+
+    #[test]
+    fn selected_columns() {
+        let mut grant = PatentGrant::default();
+        let dg = &mut grant.us_bibliographic_data_grant;
+        dg.publication_reference.doc_number = "10123456".to_owned();
+        dg.number_of_claims = "20".to_owned();
+        for country in &["US", "DE", "DE"] {
+            dg.inventors.push(Inventor {
+                addressbook: AddressBook {
+                    address: Address { country: Some(country.to_string()), ..Address::default() },
+                    ..AddressBook::default()
+                },
+                ..Inventor::default()
+            });
+        }
+
+        let columns = Columns::new("doc_number, inventor_countries,n_claims", "|").unwrap();
+        assert_eq!(columns.header(), vec!["doc_number", "inventor_countries", "n_claims"]);
+        assert_eq!(columns.row(&grant), vec!["10123456", "US|DE|DE", "20"]);
+
+        assert!(Columns::new("doc_number,bogus", ";").is_err());
+    }
+}
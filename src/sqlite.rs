@@ -0,0 +1,344 @@
+//! SQLite database export.
+//!
+//! Only built with the `sqlite` feature.
+//!
+//! The schema has one table per entity in `tables`, with the same columns, plus
+//! `source_files` recording which weekly files have been loaded. Each weekly file is loaded in
+//! one transaction that first deletes every grant an earlier load of the file left, and a
+//! grant's existing rows are deleted before it's inserted, so loading the same week again
+//! replaces it, including grants since dropped from the file, instead of duplicating it.
+//!
+//! A grant that fails to parse rolls back its whole week, which isn't recorded in
+//! `source_files`; the parser can't resume inside a file, so there's no skipping just that
+//! grant. The export command reports the week and goes on to the next file.
+
+use rusqlite::{params, Connection, Transaction};
+use std::path::Path;
+
+use crate::data::PatentGrant;
+use crate::error::Error;
+use crate::tables::Tables;
+
+/// tables keyed by doc_number, in the order rows are inserted; patents is the one with
+/// source_file, so it's deleted from last
+const TABLES: [&str; 9] = [
+    "patents",
+    "inventors",
+    "assignees",
+    "applicants",
+    "agents",
+    "examiners",
+    "classifications",
+    "citations",
+    "claims",
+];
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS source_files (
+    file_name TEXT PRIMARY KEY,
+    grants INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS patents (
+    doc_number TEXT PRIMARY KEY,
+    kind TEXT,
+    date TEXT NOT NULL,
+    country TEXT NOT NULL,
+    application_doc_number TEXT NOT NULL,
+    application_date TEXT NOT NULL,
+    us_application_series_code TEXT NOT NULL,
+    us_term_of_grant TEXT NOT NULL,
+    invention_title TEXT NOT NULL,
+    number_of_claims TEXT NOT NULL,
+    us_exemplary_claim TEXT NOT NULL,
+    us_claim_statement TEXT NOT NULL,
+    source_file TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS patents_date ON patents (date);
+CREATE INDEX IF NOT EXISTS patents_application_date ON patents (application_date);
+
+CREATE TABLE IF NOT EXISTS inventors (
+    doc_number TEXT NOT NULL,
+    sequence TEXT NOT NULL,
+    designation TEXT NOT NULL,
+    orgname TEXT,
+    first_name TEXT,
+    last_name TEXT,
+    city TEXT,
+    state TEXT,
+    country TEXT,
+    PRIMARY KEY (doc_number, sequence)
+);
+CREATE INDEX IF NOT EXISTS inventors_country ON inventors (country);
+
+CREATE TABLE IF NOT EXISTS assignees (
+    doc_number TEXT NOT NULL,
+    sequence TEXT NOT NULL,
+    orgname TEXT,
+    first_name TEXT,
+    last_name TEXT,
+    role TEXT,
+    city TEXT,
+    state TEXT,
+    country TEXT,
+    PRIMARY KEY (doc_number, sequence)
+);
+CREATE INDEX IF NOT EXISTS assignees_country ON assignees (country);
+
+CREATE TABLE IF NOT EXISTS applicants (
+    doc_number TEXT NOT NULL,
+    sequence TEXT NOT NULL,
+    app_type TEXT NOT NULL,
+    designation TEXT NOT NULL,
+    applicant_authority_category TEXT,
+    orgname TEXT,
+    first_name TEXT,
+    last_name TEXT,
+    city TEXT,
+    state TEXT,
+    country TEXT,
+    residence TEXT,
+    PRIMARY KEY (doc_number, sequence)
+);
+CREATE INDEX IF NOT EXISTS applicants_country ON applicants (country);
+
+CREATE TABLE IF NOT EXISTS agents (
+    doc_number TEXT NOT NULL,
+    sequence TEXT NOT NULL,
+    rep_type TEXT NOT NULL,
+    orgname TEXT,
+    first_name TEXT,
+    last_name TEXT,
+    city TEXT,
+    state TEXT,
+    country TEXT,
+    PRIMARY KEY (doc_number, sequence)
+);
+
+CREATE TABLE IF NOT EXISTS examiners (
+    doc_number TEXT NOT NULL,
+    examiner_type TEXT NOT NULL,
+    first_name TEXT NOT NULL,
+    last_name TEXT NOT NULL,
+    department TEXT,
+    PRIMARY KEY (doc_number, examiner_type)
+);
+
+CREATE TABLE IF NOT EXISTS classifications (
+    doc_number TEXT NOT NULL,
+    sequence TEXT NOT NULL,
+    scheme TEXT NOT NULL,
+    role TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    version TEXT,
+    -- first four characters of cpc and ipc symbols, e.g. H04L
+    subclass TEXT,
+    PRIMARY KEY (doc_number, sequence)
+);
+CREATE INDEX IF NOT EXISTS classifications_subclass ON classifications (scheme, subclass);
+
+CREATE TABLE IF NOT EXISTS citations (
+    doc_number TEXT NOT NULL,
+    sequence TEXT NOT NULL,
+    cited_country TEXT,
+    cited_doc_number TEXT,
+    cited_kind TEXT,
+    cited_name TEXT,
+    cited_date TEXT,
+    npl_text TEXT,
+    category TEXT,
+    PRIMARY KEY (doc_number, sequence)
+);
+CREATE INDEX IF NOT EXISTS citations_cited_doc_number ON citations (cited_doc_number);
+
+CREATE TABLE IF NOT EXISTS claims (
+    doc_number TEXT NOT NULL,
+    sequence TEXT NOT NULL,
+    text TEXT NOT NULL,
+    PRIMARY KEY (doc_number, sequence)
+);
+";
+
+pub struct SqliteExport {
+    conn: Connection,
+}
+
+impl SqliteExport {
+    /// opens or creates the database, and creates the schema if it's not there yet.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let conn = Connection::open(path)
+            .map_err(|err| Error::Sqlite { src: err.to_string() })?;
+
+        conn.execute_batch(SCHEMA)
+            .map_err(|err| Error::Sqlite { src: err.to_string() })?;
+
+        Ok(Self { conn })
+    }
+
+    /// Loads one weekly file in a single transaction, replacing what an earlier load of the
+    /// same file added.
+    ///
+    /// If any grant fails to parse, nothing from this week is committed and the parse error
+    /// is returned; database errors are returned as `Error::Sqlite`.
+    /// Returns the number of grants loaded.
+    pub fn load_week<I>(&mut self, file_name: &str, grants: I) -> Result<usize, Error>
+        where I: IntoIterator<Item = Result<PatentGrant, Error>>,
+    {
+        let tx = self.conn.transaction()
+            .map_err(|err| Error::Sqlite { src: err.to_string() })?;
+
+        delete_week(&tx, file_name)
+            .map_err(|err| Error::Sqlite { src: err.to_string() })?;
+
+        let mut count = 0;
+        for grant in grants {
+            insert_grant(&tx, file_name, &grant?)
+                .map_err(|err| Error::Sqlite { src: err.to_string() })?;
+            count += 1;
+        }
+
+        tx.execute(
+            "INSERT OR REPLACE INTO source_files (file_name, grants) VALUES (?1, ?2)",
+            params![file_name, count as i64],
+        )
+        .and_then(|_| tx.commit())
+        .map_err(|err| Error::Sqlite { src: err.to_string() })?;
+
+        Ok(count)
+    }
+}
+
+fn delete_week(tx: &Transaction, file_name: &str) -> rusqlite::Result<()> {
+    for table in TABLES.iter().rev() {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE doc_number IN (SELECT doc_number FROM patents WHERE source_file = ?1)", table),
+            params![file_name],
+        )?;
+    }
+    Ok(())
+}
+
+fn insert_grant(tx: &Transaction, file_name: &str, pg: &PatentGrant) -> rusqlite::Result<()> {
+    let tables = Tables::from(pg);
+    let doc_number = &pg.us_bibliographic_data_grant.publication_reference.doc_number;
+
+    for table in TABLES.iter() {
+        tx.prepare_cached(&format!("DELETE FROM {} WHERE doc_number = ?1", table))?
+            .execute(params![doc_number])?;
+    }
+
+    for r in &tables.patents {
+        tx.prepare_cached(
+            "INSERT INTO patents VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )?
+        .execute(params![
+            r.doc_number, r.kind, r.date, r.country, r.application_doc_number, r.application_date,
+            r.us_application_series_code, r.us_term_of_grant, r.invention_title, r.number_of_claims,
+            r.us_exemplary_claim, r.us_claim_statement, file_name,
+        ])?;
+    }
+    for r in &tables.inventors {
+        tx.prepare_cached("INSERT INTO inventors VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?
+            .execute(params![
+                r.doc_number, r.sequence, r.designation, r.orgname, r.first_name, r.last_name,
+                r.city, r.state, r.country,
+            ])?;
+    }
+    for r in &tables.assignees {
+        tx.prepare_cached("INSERT INTO assignees VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?
+            .execute(params![
+                r.doc_number, r.sequence, r.orgname, r.first_name, r.last_name, r.role,
+                r.city, r.state, r.country,
+            ])?;
+    }
+    for r in &tables.applicants {
+        tx.prepare_cached("INSERT INTO applicants VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)")?
+            .execute(params![
+                r.doc_number, r.sequence, r.app_type, r.designation, r.applicant_authority_category,
+                r.orgname, r.first_name, r.last_name, r.city, r.state, r.country, r.residence,
+            ])?;
+    }
+    for r in &tables.agents {
+        tx.prepare_cached("INSERT INTO agents VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?
+            .execute(params![
+                r.doc_number, r.sequence, r.rep_type, r.orgname, r.first_name, r.last_name,
+                r.city, r.state, r.country,
+            ])?;
+    }
+    for r in &tables.examiners {
+        tx.prepare_cached("INSERT INTO examiners VALUES (?1, ?2, ?3, ?4, ?5)")?
+            .execute(params![r.doc_number, r.examiner_type, r.first_name, r.last_name, r.department])?;
+    }
+    for r in &tables.classifications {
+        let subclass = match r.scheme.as_str() {
+            "cpc" | "ipc" => r.symbol.get(..4),
+            _ => None,
+        };
+        tx.prepare_cached("INSERT INTO classifications VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?
+            .execute(params![r.doc_number, r.sequence, r.scheme, r.role, r.symbol, r.version, subclass])?;
+    }
+    for r in &tables.citations {
+        tx.prepare_cached("INSERT INTO citations VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?
+            .execute(params![
+                r.doc_number, r.sequence, r.cited_country, r.cited_doc_number, r.cited_kind,
+                r.cited_name, r.cited_date, r.npl_text, r.category,
+            ])?;
+    }
+    for r in &tables.claims {
+        tx.prepare_cached("INSERT INTO claims VALUES (?1, ?2, ?3)")?
+            .execute(params![r.doc_number, r.sequence, r.text])?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Inventor;
+
+    fn grant(doc_number: &str, n_inventors: usize) -> PatentGrant {
+        let mut grant = PatentGrant::default();
+        let dg = &mut grant.us_bibliographic_data_grant;
+        dg.publication_reference.doc_number = doc_number.to_owned();
+        for i in 0..n_inventors {
+            dg.inventors.push(Inventor { sequence: format!("{:03}", i + 1), ..Inventor::default() });
+        }
+        grant
+    }
+
+    fn count(export: &SqliteExport, table: &str) -> i64 {
+        export.conn
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), params![], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn reload_week_is_idempotent() {
+        let path = std::env::temp_dir().join("uspto_reload_week_is_idempotent.db");
+        let _ = std::fs::remove_file(&path);
+        let mut export = SqliteExport::open(&path).unwrap();
+
+        let week = vec![Ok(grant("10000001", 2)), Ok(grant("10000002", 1))];
+        assert_eq!(export.load_week("ipgb20190101_wk01.xml", week).unwrap(), 2);
+
+        // second run of the same week, where a grant now has fewer inventors
+        let week = vec![Ok(grant("10000001", 1)), Ok(grant("10000002", 1))];
+        export.load_week("ipgb20190101_wk01.xml", week).unwrap();
+
+        assert_eq!(count(&export, "patents"), 2);
+        assert_eq!(count(&export, "inventors"), 2);
+        assert_eq!(count(&export, "source_files"), 1);
+
+        // a corrected file without the second grant
+        let week = vec![Ok(grant("10000001", 2))];
+        export.load_week("ipgb20190101_wk01.xml", week).unwrap();
+        assert_eq!(count(&export, "patents"), 1);
+        assert_eq!(count(&export, "inventors"), 2);
+
+        // a failed week rolls back entirely
+        let week = vec![Ok(grant("10000003", 1)), Err(Error::Deser { src: "bad grant".to_owned() })];
+        assert!(export.load_week("ipgb20190108_wk02.xml", week).is_err());
+        assert_eq!(count(&export, "patents"), 1);
+    }
+}
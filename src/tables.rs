@@ -0,0 +1,534 @@
+//! Normalized relational tables, one per entity, keyed by publication doc-number.
+//!
+//! Rows within a grant are keyed by `sequence`: the sequence attribute from the xml where
+//! there is one (inventors, applicants, agents, citations), otherwise the 1-based position
+//! within the grant (assignees, classifications, claims).
+//!
+//! `Tables::from(&PatentGrant)` gives the rows for one grant; `TablesWriter` streams them to
+//! one csv per table in a directory.
+
+use serde::Serialize;
+use snafu::ResultExt;
+use std::fs;
+use std::path::Path;
+
+use crate::data::{AddressBook, Examiner, PatentGrant};
+use crate::error::Error;
+use crate::error::WriteTable;
+
+#[derive(Debug, Default, Serialize)]
+pub struct PatentRow {
+    pub doc_number: String,
+    pub kind: Option<String>,
+    pub date: String,
+    pub country: String,
+    pub application_doc_number: String,
+    pub application_date: String,
+    pub us_application_series_code: String,
+    pub us_term_of_grant: String,
+    pub invention_title: String,
+    pub number_of_claims: String,
+    pub us_exemplary_claim: String,
+    pub us_claim_statement: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct InventorRow {
+    pub doc_number: String,
+    pub sequence: String,
+    pub designation: String,
+    pub orgname: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AssigneeRow {
+    pub doc_number: String,
+    pub sequence: String,
+    pub orgname: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub role: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ApplicantRow {
+    pub doc_number: String,
+    pub sequence: String,
+    pub app_type: String,
+    pub designation: String,
+    pub applicant_authority_category: Option<String>,
+    pub orgname: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub residence: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AgentRow {
+    pub doc_number: String,
+    pub sequence: String,
+    pub rep_type: String,
+    pub orgname: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ExaminerRow {
+    pub doc_number: String,
+    /// primary or assistant
+    pub examiner_type: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub department: Option<String>,
+}
+
+/// One row per classification symbol, across all schemes.
+#[derive(Debug, Default, Serialize)]
+pub struct ClassificationRow {
+    pub doc_number: String,
+    pub sequence: String,
+    /// cpc, ipc, uspc, or locarno
+    pub scheme: String,
+    /// main, further, or search (us-field-of-classification-search)
+    pub role: String,
+    /// e.g. "H04L 9/32" for cpc and ipc; main-classification as published for uspc and locarno
+    pub symbol: String,
+    /// cpc/ipc version indicator, or locarno edition
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CitationRow {
+    pub doc_number: String,
+    pub sequence: String,
+    pub cited_country: Option<String>,
+    pub cited_doc_number: Option<String>,
+    pub cited_kind: Option<String>,
+    pub cited_name: Option<String>,
+    pub cited_date: Option<String>,
+    pub npl_text: Option<String>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ClaimRow {
+    pub doc_number: String,
+    pub sequence: String,
+    pub text: String,
+}
+
+/// All table rows derived from one grant
+#[derive(Debug, Default)]
+pub struct Tables {
+    pub patents: Vec<PatentRow>,
+    pub inventors: Vec<InventorRow>,
+    pub assignees: Vec<AssigneeRow>,
+    pub applicants: Vec<ApplicantRow>,
+    pub agents: Vec<AgentRow>,
+    pub examiners: Vec<ExaminerRow>,
+    pub classifications: Vec<ClassificationRow>,
+    pub citations: Vec<CitationRow>,
+    pub claims: Vec<ClaimRow>,
+}
+
+impl From<&PatentGrant> for Tables {
+    fn from(pg: &PatentGrant) -> Self {
+        let dg = &pg.us_bibliographic_data_grant;
+        let doc_number = &dg.publication_reference.doc_number;
+
+        let patents = vec![PatentRow {
+            doc_number: doc_number.clone(),
+            kind: dg.publication_reference.kind.clone(),
+            date: dg.publication_reference.date.to_string(),
+            country: dg.publication_reference.country.clone(),
+            application_doc_number: dg.application_reference.doc_number.clone(),
+            application_date: dg.application_reference.date.to_string(),
+            us_application_series_code: dg.us_application_series_code.clone(),
+            us_term_of_grant: dg.us_term_of_grant.clone(),
+            invention_title: dg.invention_title.clone(),
+            number_of_claims: dg.number_of_claims.clone(),
+            us_exemplary_claim: dg.us_exemplary_claim.clone(),
+            us_claim_statement: pg.us_claim_statement.clone(),
+        }];
+
+        let inventors = dg.inventors.iter()
+            .map(|inventor| {
+                let ab = &inventor.addressbook;
+                InventorRow {
+                    doc_number: doc_number.clone(),
+                    sequence: inventor.sequence.clone(),
+                    designation: inventor.designation.clone(),
+                    orgname: ab.orgname.clone(),
+                    first_name: ab.first_name.clone(),
+                    last_name: ab.last_name.clone(),
+                    city: ab.address.city.clone(),
+                    state: ab.address.state.clone(),
+                    country: ab.address.country.clone(),
+                }
+            })
+            .collect();
+
+        let assignees = dg.assignees.iter()
+            .enumerate()
+            .map(|(i, assignee)| {
+                let ab = &assignee.addressbook;
+                AssigneeRow {
+                    doc_number: doc_number.clone(),
+                    sequence: position(i),
+                    orgname: assignee.orgname.clone().or_else(|| ab.orgname.clone()),
+                    first_name: ab.first_name.clone(),
+                    last_name: ab.last_name.clone(),
+                    role: assignee.role.clone().or_else(|| ab.role.clone()),
+                    city: ab.address.city.clone(),
+                    state: ab.address.state.clone(),
+                    country: ab.address.country.clone(),
+                }
+            })
+            .collect();
+
+        let applicants = dg.us_applicants.iter()
+            .map(|applicant| {
+                let ab = &applicant.addressbook;
+                ApplicantRow {
+                    doc_number: doc_number.clone(),
+                    sequence: applicant.sequence.clone(),
+                    app_type: applicant.app_type.clone(),
+                    designation: applicant.designation.clone(),
+                    applicant_authority_category: applicant.applicant_authority_category.clone(),
+                    orgname: ab.orgname.clone(),
+                    first_name: ab.first_name.clone(),
+                    last_name: ab.last_name.clone(),
+                    city: ab.address.city.clone(),
+                    state: ab.address.state.clone(),
+                    country: ab.address.country.clone(),
+                    residence: applicant.residence.clone(),
+                }
+            })
+            .collect();
+
+        let agents = dg.agents.iter()
+            .map(|agent| {
+                let AddressBook { orgname, first_name, last_name, address, .. } = &agent.addressbook;
+                AgentRow {
+                    doc_number: doc_number.clone(),
+                    sequence: agent.sequence.clone(),
+                    rep_type: agent.rep_type.clone(),
+                    orgname: orgname.clone(),
+                    first_name: first_name.clone(),
+                    last_name: last_name.clone(),
+                    city: address.city.clone(),
+                    state: address.state.clone(),
+                    country: address.country.clone(),
+                }
+            })
+            .collect();
+
+        let examiners = vec![
+            ("primary", &dg.examiners.primary_examiner),
+            ("assistant", &dg.examiners.assistant_examiner),
+        ]
+            .into_iter()
+            .filter(|(_, examiner)| **examiner != Examiner::default())
+            .map(|(examiner_type, examiner)| ExaminerRow {
+                doc_number: doc_number.clone(),
+                examiner_type: examiner_type.to_owned(),
+                first_name: examiner.first_name.clone(),
+                last_name: examiner.last_name.clone(),
+                department: examiner.department.clone(),
+            })
+            .collect();
+
+        // (scheme, role, symbol, version)
+        let mut classes: Vec<(&str, &str, String, Option<String>)> = Vec::new();
+        if let Some(ref main_cpc) = dg.classifications_cpc.main_cpc {
+            classes.push(("cpc", "main", main_cpc.symbol(), Some(main_cpc.cpc_version_indicator.clone())));
+        }
+        for cpc in &dg.classifications_cpc.further_cpc {
+            classes.push(("cpc", "further", cpc.symbol(), Some(cpc.cpc_version_indicator.clone())));
+        }
+        for ipcr in &dg.classifications_ipcr {
+            // symbol-position F marks the first (main) symbol
+            let role = if ipcr.symbol_position.as_deref() == Some("F") { "main" } else { "further" };
+            classes.push(("ipc", role, ipcr.symbol(), Some(ipcr.ipc_version_indicator.clone())));
+        }
+        let cn = &dg.classification_national;
+        if !cn.main_classification.is_empty() {
+            classes.push(("uspc", "main", cn.main_classification.clone(), None));
+        }
+        if let Some(ref further) = cn.further_classification {
+            classes.push(("uspc", "further", further.clone(), None));
+        }
+        let cl = &dg.classification_locarno;
+        if !cl.main_classification.is_empty() {
+            classes.push(("locarno", "main", cl.main_classification.clone(), Some(cl.edition.clone())));
+        }
+        let search = &dg.us_field_of_classification_search;
+        for cn in &search.classification_nationals {
+            classes.push(("uspc", "search", cn.main_classification.clone(), None));
+        }
+        for cpc_text in &search.classification_cpc_texts {
+            classes.push(("cpc", "search", cpc_text.clone(), None));
+        }
+
+        let classifications = classes.into_iter()
+            .enumerate()
+            .map(|(i, (scheme, role, symbol, version))| ClassificationRow {
+                doc_number: doc_number.clone(),
+                sequence: position(i),
+                scheme: scheme.to_owned(),
+                role: role.to_owned(),
+                symbol,
+                version,
+            })
+            .collect();
+
+        let citations = dg.us_references_cited.iter()
+            .map(|citation| {
+                let cited = citation.patcit.as_ref();
+                CitationRow {
+                    doc_number: doc_number.clone(),
+                    sequence: citation.sequence.clone(),
+                    cited_country: cited.map(|d| d.country.clone()),
+                    cited_doc_number: cited.map(|d| d.doc_number.clone()),
+                    cited_kind: cited.and_then(|d| d.kind.clone()),
+                    cited_name: cited.and_then(|d| d.name.clone()),
+                    cited_date: cited.map(|d| d.date.to_string()),
+                    npl_text: citation.nplcit.clone(),
+                    category: citation.category.clone(),
+                }
+            })
+            .collect();
+
+        let claims = pg.claims.iter()
+            .enumerate()
+            .map(|(i, text)| ClaimRow {
+                doc_number: doc_number.clone(),
+                sequence: position(i),
+                text: text.clone(),
+            })
+            .collect();
+
+        Tables {
+            patents,
+            inventors,
+            assignees,
+            applicants,
+            agents,
+            examiners,
+            classifications,
+            citations,
+            claims,
+        }
+    }
+}
+
+/// Table name, column names, and column values of a row, for writers that can't go
+/// through serde (e.g. database copy formats). Values are in column order.
+pub trait TableRow {
+    const TABLE: &'static str;
+    const COLUMNS: &'static [&'static str];
+
+    fn values(&self) -> Vec<Option<&str>>;
+}
+
+macro_rules! impl_table_row {
+    ($row:ty, $table:expr, {$($field:ident),* $(,)?}) => {
+        impl TableRow for $row {
+            const TABLE: &'static str = $table;
+            const COLUMNS: &'static [&'static str] = &[$(stringify!($field)),*];
+
+            fn values(&self) -> Vec<Option<&str>> {
+                vec![$(ColumnValue::value(&self.$field)),*]
+            }
+        }
+    }
+}
+
+trait ColumnValue {
+    fn value(&self) -> Option<&str>;
+}
+
+impl ColumnValue for String {
+    fn value(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl ColumnValue for Option<String> {
+    fn value(&self) -> Option<&str> {
+        self.as_deref()
+    }
+}
+
+impl_table_row!(PatentRow, "patents", {
+    doc_number, kind, date, country, application_doc_number, application_date,
+    us_application_series_code, us_term_of_grant, invention_title, number_of_claims,
+    us_exemplary_claim, us_claim_statement,
+});
+impl_table_row!(InventorRow, "inventors", {
+    doc_number, sequence, designation, orgname, first_name, last_name, city, state, country,
+});
+impl_table_row!(AssigneeRow, "assignees", {
+    doc_number, sequence, orgname, first_name, last_name, role, city, state, country,
+});
+impl_table_row!(ApplicantRow, "applicants", {
+    doc_number, sequence, app_type, designation, applicant_authority_category, orgname,
+    first_name, last_name, city, state, country, residence,
+});
+impl_table_row!(AgentRow, "agents", {
+    doc_number, sequence, rep_type, orgname, first_name, last_name, city, state, country,
+});
+impl_table_row!(ExaminerRow, "examiners", {
+    doc_number, examiner_type, first_name, last_name, department,
+});
+impl_table_row!(ClassificationRow, "classifications", {
+    doc_number, sequence, scheme, role, symbol, version,
+});
+impl_table_row!(CitationRow, "citations", {
+    doc_number, sequence, cited_country, cited_doc_number, cited_kind, cited_name, cited_date,
+    npl_text, category,
+});
+impl_table_row!(ClaimRow, "claims", {
+    doc_number, sequence, text,
+});
+
+fn position(i: usize) -> String {
+    (i + 1).to_string()
+}
+
+/// Writes each table to `<table>.csv` in a directory, one grant at a time.
+pub struct TablesWriter {
+    patents: csv::Writer<fs::File>,
+    inventors: csv::Writer<fs::File>,
+    assignees: csv::Writer<fs::File>,
+    applicants: csv::Writer<fs::File>,
+    agents: csv::Writer<fs::File>,
+    examiners: csv::Writer<fs::File>,
+    classifications: csv::Writer<fs::File>,
+    citations: csv::Writer<fs::File>,
+    claims: csv::Writer<fs::File>,
+}
+
+impl TablesWriter {
+    /// creates the directory if it doesn't exist; existing table files are overwritten.
+    pub fn from_dir(dir: &Path) -> Result<Self, Error> {
+        fs::create_dir_all(dir)
+            .map_err(|err| Error::WriteTable { source: err.into() })?;
+
+        let table = |name: &str| {
+            csv::Writer::from_path(dir.join(format!("{}.csv", name)))
+                .context(WriteTable)
+        };
+
+        Ok(Self {
+            patents: table("patents")?,
+            inventors: table("inventors")?,
+            assignees: table("assignees")?,
+            applicants: table("applicants")?,
+            agents: table("agents")?,
+            examiners: table("examiners")?,
+            classifications: table("classifications")?,
+            citations: table("citations")?,
+            claims: table("claims")?,
+        })
+    }
+
+    pub fn write(&mut self, pg: &PatentGrant) -> Result<(), Error> {
+        let tables = Tables::from(pg);
+
+        write_rows(&mut self.patents, &tables.patents)?;
+        write_rows(&mut self.inventors, &tables.inventors)?;
+        write_rows(&mut self.assignees, &tables.assignees)?;
+        write_rows(&mut self.applicants, &tables.applicants)?;
+        write_rows(&mut self.agents, &tables.agents)?;
+        write_rows(&mut self.examiners, &tables.examiners)?;
+        write_rows(&mut self.classifications, &tables.classifications)?;
+        write_rows(&mut self.citations, &tables.citations)?;
+        write_rows(&mut self.claims, &tables.claims)?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        for wtr in vec![
+            &mut self.patents,
+            &mut self.inventors,
+            &mut self.assignees,
+            &mut self.applicants,
+            &mut self.agents,
+            &mut self.examiners,
+            &mut self.classifications,
+            &mut self.citations,
+            &mut self.claims,
+        ] {
+            wtr.flush()
+                .map_err(|err| Error::WriteTable { source: err.into() })?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_rows<T: Serialize>(wtr: &mut csv::Writer<fs::File>, rows: &[T]) -> Result<(), Error> {
+    for row in rows {
+        wtr.serialize(row)
+            .context(WriteTable)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{ClassificationCpc, ClassificationLocarno, Inventor};
+
+    #[test]
+    fn keyed_by_doc_number() {
+        let mut grant = PatentGrant::default();
+        let dg = &mut grant.us_bibliographic_data_grant;
+        dg.publication_reference.doc_number = "D0845432".to_owned();
+        dg.inventors.push(Inventor { sequence: "001".to_owned(), ..Inventor::default() });
+        dg.classifications_cpc.main_cpc = Some(ClassificationCpc {
+            section: "A".to_owned(),
+            class: "41".to_owned(),
+            subclass: "D".to_owned(),
+            main_group: "1".to_owned(),
+            subgroup: "00".to_owned(),
+            ..ClassificationCpc::default()
+        });
+        dg.classification_locarno = ClassificationLocarno {
+            edition: "11".to_owned(),
+            main_classification: "0202".to_owned(),
+        };
+        grant.claims.push("The ornamental design.".to_owned());
+
+        let tables = Tables::from(&grant);
+        assert_eq!(tables.patents.len(), 1);
+        assert_eq!(tables.inventors[0].doc_number, "D0845432");
+        assert_eq!(tables.inventors[0].sequence, "001");
+        // empty examiners are left out
+        assert!(tables.examiners.is_empty());
+
+        let classes: Vec<_> = tables.classifications.iter()
+            .map(|c| (c.sequence.as_str(), c.scheme.as_str(), c.role.as_str(), c.symbol.as_str()))
+            .collect();
+        assert_eq!(classes, vec![("1", "cpc", "main", "A41D 1/00"), ("2", "locarno", "main", "0202")]);
+
+        assert_eq!(tables.claims[0].sequence, "1");
+    }
+}
@@ -0,0 +1,143 @@
+//! Technology space: proximity networks between technology codes.
+//!
+//! Two sources of proximity:
+//! - co-classification: codes assigned to the same grant are close. With n_a grants coded a,
+//!   and n_ab coded both, proximity is n_ab / max(n_a, n_b), the smaller of the two conditional
+//!   probabilities.
+//! - rca overlap: codes in which the same countries specialize are close, as computed by
+//!   `complexity::Complexity`.
+
+use std::collections::BTreeMap;
+
+use crate::complexity::Complexity;
+use crate::cube::{technologies, Technology};
+use crate::data::{BibliographicDataGrant, PatentGrant};
+use crate::network::Graph;
+use crate::scheme::{roll_up, Level};
+
+/// Streaming co-classification counts
+pub struct CoClassification {
+    technology: Technology,
+    include_search: bool,
+    grants: BTreeMap<String, u64>,
+    pairs: BTreeMap<(String, String), u64>,
+}
+
+impl CoClassification {
+    /// With `include_search`, codes from the field of classification search count as
+    /// co-occurring with the grant's own.
+    pub fn new(technology: Technology, include_search: bool) -> Self {
+        Self {
+            technology,
+            include_search,
+            grants: BTreeMap::new(),
+            pairs: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, pg: &PatentGrant) {
+        let dg = &pg.us_bibliographic_data_grant;
+
+        let mut codes = technologies(dg, self.technology);
+        if self.include_search {
+            for code in search_technologies(dg, self.technology) {
+                if !codes.contains(&code) {
+                    codes.push(code);
+                }
+            }
+        }
+        codes.sort();
+
+        for (i, a) in codes.iter().enumerate() {
+            *self.grants.entry(a.clone()).or_insert(0) += 1;
+            for b in &codes[i + 1..] {
+                *self.pairs.entry((a.clone(), b.clone())).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Nodes are weighted by number of grants, edges by proximity. Pairs co-occurring in
+    /// fewer than `min_cooccurrence` grants are left out.
+    pub fn graph(&self, min_cooccurrence: u64) -> Graph {
+        let mut graph = Graph::new();
+
+        for (code, n) in &self.grants {
+            graph.add_node(code, *n as f64);
+        }
+        for ((a, b), n_ab) in &self.pairs {
+            if *n_ab >= min_cooccurrence {
+                let n = self.grants[a].max(self.grants[b]);
+                graph.add_edge(a, b, *n_ab as f64 / n as f64);
+            }
+        }
+
+        graph
+    }
+}
+
+/// Nodes are weighted by ubiquity, edges by rca-overlap proximity.
+pub fn rca_graph(complexity: &Complexity) -> Graph {
+    let mut graph = Graph::new();
+
+    for row in complexity.technology_rows() {
+        graph.add_node(row.technology, f64::from(row.ubiquity));
+    }
+    for row in complexity.proximity_rows() {
+        graph.add_edge(row.technology_a, row.technology_b, row.proximity);
+    }
+
+    graph
+}
+
+/// Codes from the field of classification search, at the same level as `technologies`.
+/// Search has cpc text and uspc only; other technologies have none.
+fn search_technologies(dg: &BibliographicDataGrant, technology: Technology) -> Vec<String> {
+    let search = &dg.us_field_of_classification_search;
+
+    // cpc text is e.g. "H04L 9/3226"
+    let cpc = |level: Level| -> Vec<String> {
+        search.classification_cpc_texts.iter()
+            .filter_map(|text| roll_up(text, level))
+            .collect()
+    };
+
+    let codes: Vec<String> = match technology {
+        Technology::CpcSection => cpc(Level::Section),
+        Technology::CpcClass => cpc(Level::Class),
+        Technology::CpcSubclass => cpc(Level::Subclass),
+        Technology::CpcGroup => cpc(Level::MainGroup),
+        Technology::UspcClass => search.classification_nationals.iter()
+            .filter_map(|cn| cn.main_uspc().ok())
+            .map(|code| code.class)
+            .collect(),
+        Technology::Ipc | Technology::LocarnoClass | Technology::LocarnoSubclass | Technology::WipoField => Vec::new(),
+    };
+
+    codes.into_iter().filter(|code| !code.trim().is_empty()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestGrant;
+
+    fn grant(cpc_subclasses: &[&str]) -> PatentGrant {
+        TestGrant::new().with_cpc(cpc_subclasses).build()
+    }
+
+    #[test]
+    fn co_classification_proximity() {
+        let mut co = CoClassification::new(Technology::CpcSubclass, false);
+        co.add(&grant(&["H04L", "G06F"]));
+        co.add(&grant(&["H04L", "G06F", "H04W"]));
+        co.add(&grant(&["H04L"]));
+
+        let graph = co.graph(1);
+        assert_eq!(graph.nodes().collect::<Vec<_>>(), vec![("G06F", 2.0), ("H04L", 3.0), ("H04W", 1.0)]);
+        // co-occur twice, H04L is on three grants
+        assert_eq!(graph.edge("H04L", "G06F").unwrap(), 2.0 / 3.0);
+        assert_eq!(graph.edge("H04W", "G06F").unwrap(), 0.5);
+
+        assert_eq!(co.graph(2).edges().count(), 1);
+    }
+}
@@ -0,0 +1,73 @@
+//! Grants for unit tests, with only the fields a test needs set.
+
+use crate::data::{Address, AddressBook, Assignee, ClassificationCpc, Inventor, PatentGrant};
+
+/// e.g. `TestGrant::new().with_date("20190101").with_cpc(&["H04L"]).build()`
+#[derive(Default)]
+pub(crate) struct TestGrant {
+    pg: PatentGrant,
+}
+
+impl TestGrant {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// a US publication number as in grant xml, e.g. "09000001"
+    pub(crate) fn with_doc_number(mut self, doc_number: &str) -> Self {
+        let publication = &mut self.pg.us_bibliographic_data_grant.publication_reference;
+        publication.country = "US".to_owned();
+        publication.doc_number = doc_number.to_owned();
+        self
+    }
+
+    /// grant date, e.g. "20190101"
+    pub(crate) fn with_date(mut self, date: &str) -> Self {
+        self.pg.us_bibliographic_data_grant.publication_reference.date = date.into();
+        self
+    }
+
+    /// one inventor per country, with no name or city
+    pub(crate) fn with_inventor_countries(mut self, countries: &[&str]) -> Self {
+        for country in countries {
+            self.pg.us_bibliographic_data_grant.inventors.push(Inventor {
+                addressbook: AddressBook {
+                    address: Address { country: Some(country.to_string()), ..Address::default() },
+                    ..AddressBook::default()
+                },
+                ..Inventor::default()
+            });
+        }
+        self
+    }
+
+    pub(crate) fn with_assignee(mut self, orgname: &str) -> Self {
+        self.pg.us_bibliographic_data_grant.assignees.push(Assignee { orgname: Some(orgname.to_owned()), ..Assignee::default() });
+        self
+    }
+
+    /// cpc subclasses, e.g. "H04L", each in main group 1/00; the first is the main classification
+    pub(crate) fn with_cpc(mut self, subclasses: &[&str]) -> Self {
+        let cpcs = &mut self.pg.us_bibliographic_data_grant.classifications_cpc;
+        for subclass in subclasses {
+            let cpc = ClassificationCpc {
+                section: subclass[..1].to_owned(),
+                class: subclass[1..3].to_owned(),
+                subclass: subclass[3..].to_owned(),
+                main_group: "1".to_owned(),
+                subgroup: "00".to_owned(),
+                ..ClassificationCpc::default()
+            };
+            if cpcs.main_cpc.is_none() {
+                cpcs.main_cpc = Some(cpc);
+            } else {
+                cpcs.further_cpc.push(cpc);
+            }
+        }
+        self
+    }
+
+    pub(crate) fn build(self) -> PatentGrant {
+        self.pg
+    }
+}
@@ -0,0 +1,244 @@
+//! USPC (US Patent Classification) codes.
+//!
+//! Grant xml uses a fixed-width encoding: characters 1-3 are the class, right-aligned
+//! ("  2", " 43", "713", "D 2", "D14", "PLT"), 4-6 the integer part of the subclass, also
+//! right-aligned, and anything after that is the subclass's decimal digits and/or alpha suffix.
+//! Digests replace the subclass with "DIG" and a number.
+//!
+//! | raw        | class | subclass | digest |
+//! |------------|-------|----------|--------|
+//! | " 43115"   | 43    | 115      |        |
+//! | "D 2860"   | D2    | 860      |        |
+//! | "PLT156"   | PLT   | 156      |        |
+//! | "4241301"  | 424   | 130.1    |        |
+//! | "606 32R"  | 606   | 32R      |        |
+//! | "257E27112"| 257   | E27.112  |        |
+//! | "514DIG3"  | 514   |          | 3      |
+//! | "43115R"   | 43    | 115R     |        |
+//!
+//! The last is left-trimmed, as some codes are: the class field is put back to three
+//! right-aligned characters wherever the subclass's integer part wouldn't otherwise fit.
+//!
+//! `Display` writes the conventional slashed form ("424/130.1", "514/DIG3"), which `FromStr`
+//! also accepts, along with zero-padded classes from other datasets ("D02/860", "002/2").
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UspcCode {
+    /// e.g. "43", "D2", "PLT"
+    pub class: String,
+    /// e.g. "115", "130.1", "32R"; None for digests and class-only codes
+    pub subclass: Option<String>,
+    /// digest number, for "DIG" codes
+    pub digest: Option<String>,
+}
+
+impl UspcCode {
+    /// Parses the fixed-width encoding used in grant xml
+    pub fn from_fixed_width(raw: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidUspc { raw: raw.to_owned() };
+
+        if !raw.is_ascii() || raw.trim().is_empty() {
+            return Err(invalid());
+        }
+        // codes are sometimes trimmed on the left, e.g. "43115R" for " 43115R"; then the
+        // class and subclass fields don't fit until the lost spaces are put back
+        (0..3)
+            .filter_map(|trimmed| fixed_width_fields(&format!("{:>1$}", raw, raw.len() + trimmed)))
+            .next()
+            .ok_or_else(invalid)
+    }
+
+    /// true for design classes (D1 to D99)
+    pub fn is_design(&self) -> bool {
+        self.class.starts_with('D')
+    }
+
+    pub fn is_plant(&self) -> bool {
+        self.class == "PLT"
+    }
+}
+
+/// None unless the class field and the subclass's integer field are both right-aligned
+fn fixed_width_fields(padded: &str) -> Option<UspcCode> {
+    let field = padded.get(..3)?;
+    if field.ends_with(' ') {
+        return None;
+    }
+    let class = normalize_class(field)?;
+    let rest = &padded[3..];
+
+    if let Some(digest) = rest.trim().strip_prefix("DIG") {
+        let digest = digest.trim();
+        if digest.is_empty() {
+            return None;
+        }
+        return Some(UspcCode { class, subclass: None, digest: Some(digest.to_owned()) });
+    }
+    if rest.trim().is_empty() {
+        return Some(UspcCode { class, subclass: None, digest: None });
+    }
+
+    // digits, with an optional letter prefix as in "E27"
+    let main = rest.get(..3)?.trim_start();
+    let digits = main.strip_prefix(|c: char| c.is_ascii_alphabetic()).unwrap_or(main);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // some sources write the decimal point, e.g. "428  1.1"
+    let extension = rest[3..].trim();
+    let extension = extension.strip_prefix('.').unwrap_or(extension);
+
+    let subclass = subclass(main, extension)?;
+    Some(UspcCode { class, subclass: Some(subclass), digest: None })
+}
+
+/// "  2" and "002" to "2", "D 2" and "D02" to "D2"; None if it isn't a class
+fn normalize_class(raw: &str) -> Option<String> {
+    let compact: String = raw.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+
+    if compact == "PLT" {
+        return Some(compact);
+    }
+    let (prefix, digits) = match compact.strip_prefix('D') {
+        Some(digits) => ("D", digits),
+        None => ("", compact.as_str()),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return None;
+    }
+    Some(format!("{}{}", prefix, digits))
+}
+
+/// integer part (possibly with a letter prefix, as in "E27") plus an extension of decimal
+/// digits and/or an alpha suffix: ("130", "1") is "130.1", ("32", "R") is "32R"
+fn subclass(main: &str, extension: &str) -> Option<String> {
+    let main = main.to_ascii_uppercase();
+    let extension = extension.to_ascii_uppercase();
+    if !main.bytes().all(|b| b.is_ascii_alphanumeric()) || !extension.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    // leading zeros only on all-digit subclasses, e.g. "030"
+    let main = if main.bytes().all(|b| b.is_ascii_digit()) {
+        match main.trim_start_matches('0') {
+            "" => "0".to_owned(),
+            trimmed => trimmed.to_owned(),
+        }
+    } else {
+        main
+    };
+
+    let split = extension.find(|c: char| !c.is_ascii_digit()).unwrap_or(extension.len());
+    let (decimal, alpha) = extension.split_at(split);
+    if !alpha.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    Some(match decimal {
+        "" => format!("{}{}", main, alpha),
+        decimal => format!("{}.{}{}", main, decimal, alpha),
+    })
+}
+
+impl fmt::Display for UspcCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.subclass, &self.digest) {
+            (_, Some(digest)) => write!(f, "{}/DIG{}", self.class, digest),
+            (Some(subclass), None) => write!(f, "{}/{}", self.class, subclass),
+            (None, None) => write!(f, "{}", self.class),
+        }
+    }
+}
+
+/// Parses the slashed form, e.g. "424/130.1", "D02/860", "514/DIG3", "PLT"
+impl FromStr for UspcCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidUspc { raw: s.to_owned() };
+
+        let mut parts = s.trim().splitn(2, '/');
+        let class = normalize_class(parts.next().unwrap_or("")).ok_or_else(invalid)?;
+        let rest = match parts.next() {
+            Some(rest) => rest.trim().to_ascii_uppercase(),
+            None => return Ok(UspcCode { class, subclass: None, digest: None }),
+        };
+
+        if let Some(digest) = rest.strip_prefix("DIG") {
+            let digest = digest.trim_start_matches(|c: char| c == '.' || c.is_whitespace());
+            if digest.is_empty() {
+                return Err(invalid());
+            }
+            return Ok(UspcCode { class, subclass: None, digest: Some(digest.to_owned()) });
+        }
+
+        let compact: String = rest.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut parts = compact.splitn(2, '.');
+        let main = parts.next().unwrap_or("");
+        // alpha suffix without a decimal, e.g. "32R"
+        let (main, extension) = match parts.next() {
+            Some(extension) => (main.to_owned(), extension.to_owned()),
+            None => {
+                let digits_end = main.rfind(|c: char| c.is_ascii_digit()).map(|i| i + 1).unwrap_or(0);
+                (main[..digits_end].to_owned(), main[digits_end..].to_owned())
+            },
+        };
+        if main.is_empty() {
+            return Err(invalid());
+        }
+
+        let subclass = subclass(&main, &extension).ok_or_else(invalid)?;
+        Ok(UspcCode { class, subclass: Some(subclass), digest: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_width() {
+        let cases = [
+            (" 43115", "43/115"),
+            ("43115", "43/115"),
+            ("D 2860", "D2/860"),
+            ("D14138", "D14/138"),
+            ("PLT156", "PLT/156"),
+            ("713168", "713/168"),
+            ("380 30", "380/30"),
+            ("4241301", "424/130.1"),
+            ("435 691", "435/69.1"),
+            ("606 32R", "606/32R"),
+            ("257E27112", "257/E27.112"),
+            ("514DIG3", "514/DIG3"),
+            ("  2  2", "2/2"),
+            ("43115R", "43/115R"),
+            ("2  2", "2/2"),
+            ("428  1.1", "428/1.1"),
+            ("4281.1", "4/281.1"),
+        ];
+
+        for (raw, canonical) in cases.iter() {
+            let code = UspcCode::from_fixed_width(raw).unwrap();
+            assert_eq!(code.to_string(), *canonical, "{:?}", raw);
+            assert_eq!(canonical.parse::<UspcCode>().unwrap(), code);
+        }
+
+        assert_eq!("D02/860".parse::<UspcCode>().unwrap().to_string(), "D2/860");
+        assert_eq!("002/002".parse::<UspcCode>().unwrap().to_string(), "2/2");
+        assert!(UspcCode::from_fixed_width("D 2860").unwrap().is_design());
+        assert!(UspcCode::from_fixed_width("").is_err());
+        assert!(UspcCode::from_fixed_width("XYZ123").is_err());
+        assert!(UspcCode::from_fixed_width("424   5").is_err());
+    }
+}
@@ -1,7 +1,9 @@
 use quick_xml::events::Event;
+use snafu::OptionExt;
 use std::io::BufRead;
 
 use crate::error::Error;
+use crate::error::Deser;
 
 #[macro_export]
 macro_rules! try_some {
@@ -31,8 +33,8 @@ macro_rules! parse_struct_update {
                             match $rdr.read_event($buf) {
                                 Ok(Event::Start(ref e)) => {
                                     match e.name() {
-                                        $($xml_field => $data_struct.$data_struct_field = deser_text_from(e.name(), $rdr,)?,)*
-                                        $($xml_field_opt => $data_struct.$data_struct_field_opt = Some(deser_text_from(e.name(), $rdr,)?),)*
+                                        $($xml_field => $data_struct.$data_struct_field = deser_text_from(e.name(), $rdr,)?.into(),)*
+                                        $($xml_field_opt => $data_struct.$data_struct_field_opt = Some(deser_text_from(e.name(), $rdr,)?.into()),)*
                                         _ => return Err(Error::Deser { src: format!("unrecognized element {:?} in {}", std::str::from_utf8(e.name()), $xml_element) }),
                                     }
                                 },
@@ -68,8 +70,8 @@ macro_rules! parse_struct_update_from {
             match $rdr.read_event($buf) {
                 Ok(Event::Start(ref e)) => {
                     match e.name() {
-                        $($xml_field => $data_struct.$data_struct_field = deser_text_from(e.name(), $rdr,)?,)*
-                        $($xml_field_opt => $data_struct.$data_struct_field_opt = Some(deser_text_from(e.name(), $rdr,)?),)*
+                        $($xml_field => $data_struct.$data_struct_field = deser_text_from(e.name(), $rdr,)?.into(),)*
+                        $($xml_field_opt => $data_struct.$data_struct_field_opt = Some(deser_text_from(e.name(), $rdr,)?.into()),)*
                         _ => return Err(Error::Deser { src: format!("unrecognized element {:?} in {}", std::str::from_utf8(e.name()), $xml_element) }),
                     }
                 },
@@ -133,3 +135,74 @@ pub fn skip_to_tag_within<B: BufRead>(
     }
 }
 
+
+/// iso-latin-1 entity names (ISO 8879 isolat1, as used in the html 4 dtd), for code points
+/// 160 to 255 in order. Older grants use these in attributes even though the xml doesn't
+/// declare them.
+const LATIN1_ENTITIES: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect",
+    "uml", "copy", "ordf", "laquo", "not", "shy", "reg", "macr",
+    "deg", "plusmn", "sup2", "sup3", "acute", "micro", "para", "middot",
+    "cedil", "sup1", "ordm", "raquo", "frac14", "frac12", "frac34", "iquest",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil",
+    "Egrave", "Eacute", "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml",
+    "ETH", "Ntilde", "Ograve", "Oacute", "Ocirc", "Otilde", "Ouml", "times",
+    "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute", "THORN", "szlig",
+    "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil",
+    "egrave", "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml",
+    "eth", "ntilde", "ograve", "oacute", "ocirc", "otilde", "ouml", "divide",
+    "oslash", "ugrave", "uacute", "ucirc", "uuml", "yacute", "thorn", "yuml",
+];
+
+/// fallback for when quick-xml can't unescape and decode a value.
+///
+/// bytes are decoded as utf-8 if valid, otherwise as latin-1 (every byte maps to the
+/// same code point). Then predefined xml entities, numeric character references, and
+/// iso-latin-1 named entities are resolved.
+///
+/// returns an error for unterminated or unknown entities, instead of guessing.
+pub fn decode_latin1_value(raw: &[u8]) -> Result<String, Error> {
+    let s: String = match std::str::from_utf8(raw) {
+        Ok(s) => s.to_owned(),
+        Err(_) => raw.iter().map(|&b| b as char).collect(),
+    };
+
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s.as_str();
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp + 1..];
+
+        let semi = rest.find(';')
+            .context(Deser { src: format!("unterminated entity in {:?}", s) })?;
+        let name = &rest[..semi];
+        let c = entity_char(name)
+            .context(Deser { src: format!("unknown entity &{}; in {:?}", name, s) })?;
+
+        out.push(c);
+        rest = &rest[semi + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+fn entity_char(name: &str) -> Option<char> {
+    let code = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(dec) = name.strip_prefix('#') {
+        dec.parse::<u32>().ok()?
+    } else {
+        match name {
+            "amp" => '&' as u32,
+            "lt" => '<' as u32,
+            "gt" => '>' as u32,
+            "quot" => '"' as u32,
+            "apos" => '\'' as u32,
+            _ => LATIN1_ENTITIES.iter().position(|e| *e == name)? as u32 + 160,
+        }
+    };
+
+    std::char::from_u32(code)
+}
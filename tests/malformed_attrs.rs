@@ -0,0 +1,71 @@
+// Corpus of grants with malformed attributes in us-applicant, inventor, and agent.
+// Each one must come back as Ok or Err from the iterator; none may panic.
+
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use uspto::data::PatentGrant;
+use uspto::{Error, PatentGrants};
+
+fn parse(file_name: &str) -> Vec<Result<PatentGrant, Error>> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data/malformed_attrs")
+        .join(file_name);
+    let f = fs::File::open(path).expect("missing test corpus file");
+
+    PatentGrants::from_reader(BufReader::new(f)).collect()
+}
+
+/// after an error the iterator may keep yielding junk from the rest of the file,
+/// so only the first grant is checked.
+fn parse_first(file_name: &str) -> Result<PatentGrant, Error> {
+    parse(file_name).remove(0)
+}
+
+#[test]
+fn corpus_never_panics() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/malformed_attrs");
+
+    for entry in fs::read_dir(dir).unwrap() {
+        let file_name = entry.unwrap().file_name();
+        // only asserting that this returns at all
+        let _ = parse(file_name.to_str().unwrap());
+    }
+}
+
+#[test]
+fn latin1_byte_applicant() {
+    let grant = parse_first("latin1_byte_applicant.xml").unwrap();
+    let applicant = &grant.us_bibliographic_data_grant.us_applicants[0];
+
+    assert_eq!(applicant.applicant_authority_category.as_ref().unwrap(), "inventor-décédé");
+}
+
+#[test]
+fn latin1_entity_inventor() {
+    let grant = parse_first("latin1_entity_inventor.xml").unwrap();
+
+    assert_eq!(grant.us_bibliographic_data_grant.inventors[0].sequence, "001é");
+}
+
+#[test]
+fn numeric_ref_agent() {
+    let grant = parse_first("numeric_ref_agent.xml").unwrap();
+
+    assert_eq!(grant.us_bibliographic_data_grant.agents[0].rep_type, "attornéyé");
+}
+
+#[test]
+fn unterminated_entity_agent() {
+    assert!(parse_first("unterminated_entity_agent.xml").is_err());
+}
+
+#[test]
+fn unknown_entity_inventor() {
+    assert!(parse_first("unknown_entity_inventor.xml").is_err());
+}
+
+#[test]
+fn invalid_numeric_ref_applicant() {
+    assert!(parse_first("invalid_numeric_ref_applicant.xml").is_err());
+}
@@ -0,0 +1,70 @@
+// Parses tests/data/ipgb_sample.xml: one utility grant with cpc/ipcr classifications and
+// citations, and one design grant with a locarno classification.
+
+use std::fs;
+use std::io::BufReader;
+use std::path::Path;
+use uspto::data::PatentGrant;
+use uspto::PatentGrants;
+
+fn sample() -> Vec<PatentGrant> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/ipgb_sample.xml");
+    let f = fs::File::open(path).expect("missing sample file");
+
+    PatentGrants::from_reader(BufReader::new(f))
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn classifications() {
+    let grants = sample();
+    assert_eq!(grants.len(), 2);
+
+    let dg = &grants[0].us_bibliographic_data_grant;
+    let cpc = &dg.classifications_cpc;
+    assert_eq!(cpc.main_cpc.as_ref().unwrap().symbol(), "H04L 9/3226");
+    assert_eq!(cpc.main_cpc.as_ref().unwrap().action_date.raw(), "20190101");
+    assert_eq!(cpc.further_cpc.len(), 1);
+    assert_eq!(cpc.further_cpc[0].symbol(), "G06F 21/6245");
+
+    assert_eq!(dg.classifications_ipcr.len(), 2);
+    assert_eq!(dg.classifications_ipcr[0].symbol(), "H04L 9/32");
+    assert_eq!(dg.classifications_ipcr[0].generating_office.as_ref().unwrap(), "US");
+
+    // the citations' classification-national must not overwrite the grant's
+    assert_eq!(dg.classification_national.main_classification, "713168");
+
+    let dg = &grants[1].us_bibliographic_data_grant;
+    assert!(dg.classifications_cpc.main_cpc.is_none());
+    assert_eq!(dg.classification_locarno.main_classification, "0202");
+    assert_eq!(dg.classification_national.main_classification, "D 2860");
+}
+
+#[test]
+fn citations() {
+    let grants = sample();
+    let citations = &grants[0].us_bibliographic_data_grant.us_references_cited;
+    assert_eq!(citations.len(), 3);
+
+    let patcit = citations[0].patcit.as_ref().unwrap();
+    assert_eq!(citations[0].sequence, "00001");
+    assert_eq!(patcit.doc_number, "5241599");
+    assert_eq!(patcit.name.as_ref().unwrap(), "Bellovin et al.");
+    assert_eq!(citations[0].category.as_ref().unwrap(), "cited by examiner");
+    assert_eq!(citations[0].classification_national.as_ref().unwrap().main_classification, "380 30");
+
+    assert!(citations[2].patcit.is_none());
+    assert!(citations[2].nplcit.as_ref().unwrap().starts_with("Diffie et al."));
+}
+
+#[test]
+fn dates() {
+    let grants = sample();
+    let dg = &grants[0].us_bibliographic_data_grant;
+
+    assert_eq!(dg.grant_year().unwrap(), 2019);
+    assert_eq!(dg.grant_iso_week().unwrap(), (2019, 1));
+    // filed 2016-10-27, granted 2019-01-01
+    assert_eq!(dg.pendency_days().unwrap(), 796);
+}